@@ -0,0 +1,27 @@
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+        let config = cbindgen::Config { usize_is_size_t: true, ..Default::default() };
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_crate(&crate_dir)
+            .with_language(cbindgen::Language::C)
+            .with_include_guard("PIXEL_H")
+            .generate()
+            .expect("C header can be generated")
+            .write_to_file(format!("{}/include/pixel.h", crate_dir));
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+    }
+
+    #[cfg(feature = "node")]
+    napi_build::setup();
+
+    // protox parses the service definition, so building doesn't need protoc installed
+    #[cfg(feature = "grpc")]
+    {
+        let descriptors = protox::compile(["proto/pixel.proto"], ["proto"]).expect("proto/pixel.proto is valid");
+        tonic_prost_build::configure().build_client(false).compile_fds(descriptors).expect("gRPC service can be generated");
+        println!("cargo:rerun-if-changed=proto/pixel.proto");
+    }
+}
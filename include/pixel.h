@@ -0,0 +1,66 @@
+#ifndef PIXEL_H
+#define PIXEL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Outcome of a call; free it with `pixel_free_result`.
+ */
+typedef struct PixelResult {
+  /**
+   * NUL-terminated error message, or null on success
+   */
+  char *error;
+  /**
+   * The JSON map from `pixel_map_rgba`, or the RGBA pixels from `pixel_reconstruct`, followed
+   * by a NUL byte; null on error
+   */
+  uint8_t *data;
+  /**
+   * Length of `data` in bytes, without the NUL
+   */
+  size_t len;
+  /**
+   * Size of the image from `pixel_reconstruct` in pixels, 0 otherwise
+   */
+  uint32_t width;
+  uint32_t height;
+} PixelResult;
+
+/**
+ * Map `width` x `height` RGBA pixels (row-major, 4 bytes each) over `block_size` blocks,
+ * merging colors within `tolerance`, to a JSON map.
+ *
+ * # Safety
+ *
+ * `rgba` must point to `width * height * 4` readable bytes.
+ */
+struct PixelResult *pixel_map_rgba(const uint8_t *rgba,
+                                   uint32_t width,
+                                   uint32_t height,
+                                   uint32_t block_size,
+                                   double tolerance);
+
+/**
+ * Paint the NUL-terminated JSON map `json` (with a single matrix) back into RGBA pixels.
+ *
+ * # Safety
+ *
+ * `json` must be a NUL-terminated string.
+ */
+struct PixelResult *pixel_reconstruct(const char *json);
+
+/**
+ * Free a result returned by the other functions; null is ignored.
+ *
+ * # Safety
+ *
+ * `result` must come from this library and not be freed already.
+ */
+void pixel_free_result(struct PixelResult *result);
+
+#endif  /* PIXEL_H */
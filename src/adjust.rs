@@ -0,0 +1,183 @@
+use clap::Args;
+use image::{ImageBuffer, Rgba};
+use std::str::FromStr;
+
+use crate::stream::RowSource;
+
+/// Input levels: channels at or below `black` become 0, at or above `white` full, and those
+/// between are stretched with `gamma`.
+#[derive(Clone, Copy, Debug)]
+pub struct Levels {
+    black: f64,
+    white: f64,
+    gamma: f64,
+}
+
+impl FromStr for Levels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Expected BLACK,WHITE[,GAMMA] with 0 <= BLACK < WHITE <= 255 and GAMMA above 0, got {}", s);
+        let parts: Vec<f64> = s.split(',').map(|part| part.trim().parse()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+        let (black, white, gamma) = match *parts.as_slice() {
+            [black, white] => (black, white, 1.0),
+            [black, white, gamma] => (black, white, gamma),
+            _ => return Err(invalid()),
+        };
+        if !(0.0 <= black && black < white && white <= 255.0 && gamma > 0.0) {
+            return Err(invalid());
+        }
+        Ok(Levels { black: black / 255.0, white: white / 255.0, gamma })
+    }
+}
+
+/// Color adjustments made to the input before it is mapped.
+#[derive(Args, Clone, Copy, Debug, Default)]
+pub struct Adjustments {
+    /// Stretch the input levels before mapping: channels at or below BLACK become 0, at or above
+    /// WHITE full, with GAMMA (default 1) brightening the midtones above 1 and darkening below
+    #[arg(id = "input_levels", long = "levels", value_name = "BLACK,WHITE[,GAMMA]")]
+    levels: Option<Levels>,
+
+    /// Brighten (up to 100) or darken (down to -100) before mapping
+    #[arg(long, value_name = "PERCENT", default_value_t = 0.0, allow_negative_numbers = true, value_parser = percent)]
+    brightness: f64,
+
+    /// Raise (up to 100) or lower (down to -100, flat gray) contrast before mapping
+    #[arg(long, value_name = "PERCENT", default_value_t = 0.0, allow_negative_numbers = true, value_parser = percent)]
+    contrast: f64,
+
+    /// Rotate hues by this many degrees before mapping
+    #[arg(long, value_name = "DEGREES", default_value_t = 0.0, allow_negative_numbers = true)]
+    hue_shift: f64,
+
+    /// Raise (up to 100) or lower (down to -100, gray) saturation before mapping
+    #[arg(long, value_name = "PERCENT", default_value_t = 0.0, allow_negative_numbers = true, value_parser = percent)]
+    saturation: f64,
+
+    /// Raise (up to 100, white) or lower (down to -100, black) lightness before mapping
+    #[arg(long, value_name = "PERCENT", default_value_t = 0.0, allow_negative_numbers = true, value_parser = percent)]
+    lightness: f64,
+}
+
+fn percent(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(percent) if (-100.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("Expected a percentage from -100 to 100, got {}", s)),
+    }
+}
+
+// Moves `value` from 0 to 1 toward 1 by `amount` above 0, or toward 0 by `amount` below
+fn push(value: f64, amount: f64) -> f64 {
+    if amount > 0.0 { value + (1.0 - value) * amount } else { value * (1.0 + amount) }
+}
+
+fn to_hsl([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let lightness = (max + min) / 2.0;
+    let chroma = max - min;
+    if chroma == 0.0 {
+        return [0.0, 0.0, lightness];
+    }
+    let saturation = chroma / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        (g - b) / chroma
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    [(hue * 60.0).rem_euclid(360.0), saturation.min(1.0), lightness]
+}
+
+fn from_hsl([hue, saturation, lightness]: [f64; 3]) -> [f64; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r + m, g + m, b + m]
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        self.levels.is_none() && self.brightness == 0.0 && self.contrast == 0.0 && self.hue_shift % 360.0 == 0.0 && self.saturation == 0.0 && self.lightness == 0.0
+    }
+
+    /// The adjustments as `name=value` pairs, for manifest keys.
+    pub fn key(&self) -> String {
+        let levels = self.levels.map_or("none".to_string(), |levels| format!("{},{},{}", levels.black * 255.0, levels.white * 255.0, levels.gamma));
+        format!(
+            "levels={} brightness={} contrast={} hue_shift={} saturation={} lightness={}",
+            levels, self.brightness, self.contrast, self.hue_shift, self.saturation, self.lightness
+        )
+    }
+
+    /// `rgb` with each channel from 0 to 1, adjusted: levels first, then brightness, contrast
+    /// and the HSL adjustments.
+    fn rgb(&self, rgb: [f64; 3]) -> [f64; 3] {
+        // 100% contrast is a threshold at mid gray
+        let contrast = ((100.0 + self.contrast) / (100.0 - self.contrast)).min(f64::MAX);
+        let rgb = rgb.map(|c| {
+            let c = match self.levels {
+                Some(Levels { black, white, gamma }) => ((c - black) / (white - black)).clamp(0.0, 1.0).powf(1.0 / gamma),
+                None => c,
+            };
+            (((c + self.brightness / 100.0).clamp(0.0, 1.0) - 0.5) * contrast + 0.5).clamp(0.0, 1.0)
+        });
+        let [hue, saturation, lightness] = to_hsl(rgb);
+        let hue = (hue + self.hue_shift).rem_euclid(360.0);
+        from_hsl([hue, push(saturation, self.saturation / 100.0), push(lightness, self.lightness / 100.0)]).map(|c| c.clamp(0.0, 1.0))
+    }
+
+    /// Adjust the color channels of RGBA `pixels`; alpha is kept.
+    pub fn pixels(&self, pixels: &mut [u8]) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            let rgb = self.rgb([pixel[0], pixel[1], pixel[2]].map(|c| c as f64 / 255.0));
+            for (c, adjusted) in pixel.iter_mut().zip(rgb) {
+                *c = (adjusted * 255.0).round() as u8;
+            }
+        }
+    }
+
+    /// [`Adjustments::pixels`] for 16-bit images.
+    pub fn image16(&self, image: &mut ImageBuffer<Rgba<u16>, Vec<u16>>) {
+        for pixel in image.pixels_mut() {
+            let rgb = self.rgb([pixel[0], pixel[1], pixel[2]].map(|c| c as f64 / 65535.0));
+            for (c, adjusted) in pixel.0.iter_mut().zip(rgb) {
+                *c = (adjusted * 65535.0).round() as u16;
+            }
+        }
+    }
+}
+
+/// Rows of `source` with [`Adjustments`] made as they're read.
+pub struct Rows {
+    source: Box<dyn RowSource>,
+    adjustments: Adjustments,
+}
+
+impl Rows {
+    pub fn new(source: Box<dyn RowSource>, adjustments: Adjustments) -> Self {
+        Rows { source, adjustments }
+    }
+}
+
+impl RowSource for Rows {
+    fn dimensions(&self) -> (u32, u32) {
+        self.source.dimensions()
+    }
+
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.source.read_row(buf)?;
+        self.adjustments.pixels(buf);
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Delay, Frame, Frames as DecodedFrames, ImageFormat, ImageResult, RgbaImage};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor};
+use std::path::Path;
+
+use crate::mapped;
+
+/// Frames of an animation, each composited onto the full canvas, with its delay in milliseconds.
+pub type Frames = Box<dyn Iterator<Item = ImageResult<(u32, RgbaImage)>>>;
+
+/// One frame of a map file written for an animated input.
+#[derive(Deserialize)]
+pub struct FrameMap {
+    pub delay_ms: u32,
+    matrix: Option<Vec<Vec<u32>>>,
+    /// `[x, y, id]` for each cell that differs from the previous frame, in delta-encoded maps
+    changes: Option<Vec<[u32; 3]>>,
+}
+
+/// A frame's delay in milliseconds and its full matrix.
+pub type ResolvedFrame = (u32, Vec<Vec<u32>>);
+
+/// Full matrix of every frame with its delay, applying delta-encoded frames to the one before.
+pub fn resolve(frames: Vec<FrameMap>) -> Result<Vec<ResolvedFrame>, String> {
+    let mut resolved: Vec<ResolvedFrame> = Vec::with_capacity(frames.len());
+    for (i, frame) in frames.into_iter().enumerate() {
+        let matrix = match (frame.matrix, frame.changes) {
+            (Some(matrix), None) => matrix,
+            (None, Some(changes)) => {
+                let Some((_, previous)) = resolved.last() else {
+                    return Err("The first frame needs a full matrix".to_string());
+                };
+                let mut matrix = previous.clone();
+                for [x, y, id] in changes {
+                    let cell = matrix.get_mut(y as usize).and_then(|row| row.get_mut(x as usize));
+                    *cell.ok_or_else(|| format!("Frame {} changes cell ({}, {}) outside the matrix", i, x, y))? = id;
+                }
+                matrix
+            }
+            _ => return Err(format!("Frame {} needs either a matrix or changes", i)),
+        };
+        resolved.push((frame.delay_ms, matrix));
+    }
+    Ok(resolved)
+}
+
+fn with_delays(frames: DecodedFrames<'static>) -> Frames {
+    Box::new(frames.map(|frame| {
+        let frame = frame?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        Ok((numer / denom.max(1), frame.into_buffer()))
+    }))
+}
+
+/// Decode the frames of `path` if it is a GIF, an APNG or an animated WebP (judged by extension, or by contents
+/// when the extension says nothing); still images yield `None`.
+pub fn frames(path: &Path) -> Result<Option<Frames>, Box<dyn std::error::Error>> {
+    let map = mapped::map_file(path)?;
+    let format = ImageFormat::from_path(path).or_else(|_| image::guess_format(&map[..]));
+    match format.ok() {
+        Some(ImageFormat::Gif) => Ok(Some(with_delays(GifDecoder::new(Cursor::new(map))?.into_frames()))),
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(Cursor::new(map))?;
+            if !decoder.is_apng()? {
+                return Ok(None);
+            }
+            Ok(Some(with_delays(decoder.apng()?.into_frames())))
+        }
+        Some(ImageFormat::WebP) => {
+            if !WebPDecoder::new(Cursor::new(&map[..]))?.has_animation() {
+                return Ok(None);
+            }
+            // image's decoder is off by one in blended frames; libwebp's is exact
+            let decoder = webp_animation::Decoder::new(&map)?;
+            let (width, height) = decoder.dimensions();
+            let mut frames = Vec::new();
+            let mut end = 0;
+            for frame in decoder {
+                // Timestamps mark where each frame ends
+                let delay = (frame.timestamp() - end).max(0) as u32;
+                end = frame.timestamp();
+                let image = RgbaImage::from_raw(width, height, frame.data().to_vec()).ok_or("Truncated WebP frame")?;
+                frames.push(Ok((delay, image)));
+            }
+            Ok(Some(Box::new(frames.into_iter())))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Whether `path` names a format `write` can produce an animation in.
+pub fn can_write(path: &Path) -> bool {
+    matches!(ImageFormat::from_path(path), Ok(ImageFormat::Gif | ImageFormat::Png | ImageFormat::WebP))
+}
+
+/// Write `frames` as an endlessly looping GIF, APNG or WebP, chosen by the extension of `path`.
+/// All frames must have the same size.
+pub fn write(path: &Path, frames: &[(u32, RgbaImage)]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some((_, first)) = frames.first() else {
+        return Err("No frames to write".into());
+    };
+    match ImageFormat::from_path(path)? {
+        ImageFormat::Gif => write_gif(path, frames),
+        ImageFormat::Png => write_apng(path, first, frames),
+        ImageFormat::WebP => write_webp(path, first, frames),
+        format => Err(format!("Can't write animations as {:?}", format).into()),
+    }
+}
+
+fn write_gif(path: &Path, frames: &[(u32, RgbaImage)]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(Repeat::Infinite)?;
+    for (delay, image) in frames {
+        encoder.encode_frame(Frame::from_parts(image.clone(), 0, 0, Delay::from_numer_denom_ms(*delay, 1)))?;
+    }
+    Ok(())
+}
+
+fn write_webp(path: &Path, first: &RgbaImage, frames: &[(u32, RgbaImage)]) -> Result<(), Box<dyn std::error::Error>> {
+    // libwebp defaults to lossy frames; map colors must come back exact
+    let options = webp_animation::EncoderOptions { encoding_config: Some(Default::default()), ..Default::default() };
+    let mut encoder = webp_animation::Encoder::new_with_options(first.dimensions(), options)?;
+    let mut timestamp = 0i32;
+    for (delay, image) in frames {
+        encoder.add_frame(image, timestamp)?;
+        timestamp = timestamp.saturating_add(*delay as i32);
+    }
+    fs::write(path, &*encoder.finalize(timestamp)?)?;
+    Ok(())
+}
+
+fn write_apng(path: &Path, first: &RgbaImage, frames: &[(u32, RgbaImage)]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), first.width(), first.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+
+    let mut writer = encoder.write_header()?;
+    for (delay, image) in frames {
+        // Delays are stored as a fraction of a second with a 16-bit numerator
+        writer.set_frame_delay((*delay).min(u16::MAX as u32) as u16, 1000)?;
+        writer.write_image_data(image)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::{Output, failure, hex_to_rgba};
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+const LAYER_CHUNK: u16 = 0x2004;
+const CEL_CHUNK: u16 = 0x2005;
+const PALETTE_CHUNK: u16 = 0x2019;
+// Cel type for zlib-compressed pixels
+const COMPRESSED_IMAGE: u16 = 2;
+
+fn chunk(kind: u16, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(6 + data.len());
+    out.extend_from_slice(&(6 + data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&kind.to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+fn layer_chunk(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&3u16.to_le_bytes()); // visible, editable
+    data.extend_from_slice(&[0; 10]); // normal layer, child level, default size, blend mode
+    data.extend_from_slice(&[255, 0, 0, 0]); // opacity, reserved
+    data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    data.extend_from_slice(name.as_bytes());
+    chunk(LAYER_CHUNK, &data)
+}
+
+/// Write the map as an indexed-color `.aseprite` file: one frame, one layer and one cel, with
+/// map colors as the palette in ID order. Indexed mode is limited to 256 colors.
+pub fn write(map: &Output, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut ids: Vec<u32> = map.colors.keys().copied().collect();
+    ids.sort_unstable();
+    if ids.len() > 256 {
+        return Err(failure::bad_input(format!("Aseprite's indexed mode holds at most 256 colors, the map has {}; quantize it first", ids.len())));
+    }
+    let index: HashMap<u32, u8> = ids.iter().enumerate().map(|(i, &id)| (id, i as u8)).collect();
+    let transparent = index.get(&0).copied().unwrap_or(0);
+
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let height = map.matrix.len();
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(failure::bad_input("Aseprite sprites are at most 65535 cells wide and high"));
+    }
+
+    let mut palette = Vec::new();
+    palette.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+    palette.extend_from_slice(&0u32.to_le_bytes());
+    palette.extend_from_slice(&(ids.len().max(1) as u32 - 1).to_le_bytes());
+    palette.extend_from_slice(&[0; 8]);
+    for id in &ids {
+        palette.extend_from_slice(&0u16.to_le_bytes()); // no name
+        palette.extend_from_slice(&hex_to_rgba(&map.colors[id])?.0);
+    }
+
+    // Cells past a short row's end and unknown IDs are transparent
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in &map.matrix {
+        pixels.extend((0..width).map(|x| row.get(x).and_then(|id| index.get(id)).copied().unwrap_or(transparent)));
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&pixels)?;
+
+    let mut cel = Vec::new();
+    cel.extend_from_slice(&[0; 6]); // layer 0 at (0, 0)
+    cel.push(255);
+    cel.extend_from_slice(&COMPRESSED_IMAGE.to_le_bytes());
+    cel.extend_from_slice(&[0; 7]); // z-index, reserved
+    cel.extend_from_slice(&(width as u16).to_le_bytes());
+    cel.extend_from_slice(&(height as u16).to_le_bytes());
+    cel.extend_from_slice(&encoder.finish()?);
+
+    let chunks = [layer_chunk("Layer 1"), chunk(PALETTE_CHUNK, &palette), chunk(CEL_CHUNK, &cel)];
+    let chunks_len: usize = chunks.iter().map(Vec::len).sum();
+
+    let mut frame = Vec::with_capacity(16 + chunks_len);
+    frame.extend_from_slice(&(16 + chunks_len as u32).to_le_bytes());
+    frame.extend_from_slice(&FRAME_MAGIC.to_le_bytes());
+    frame.extend_from_slice(&(chunks.len() as u16).to_le_bytes());
+    frame.extend_from_slice(&100u16.to_le_bytes()); // duration in ms
+    frame.extend_from_slice(&[0; 2]);
+    frame.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    for chunk in &chunks {
+        frame.extend_from_slice(chunk);
+    }
+
+    let mut file = Vec::with_capacity(128 + frame.len());
+    file.extend_from_slice(&(128 + frame.len() as u32).to_le_bytes());
+    file.extend_from_slice(&HEADER_MAGIC.to_le_bytes());
+    file.extend_from_slice(&1u16.to_le_bytes()); // frames
+    file.extend_from_slice(&(width as u16).to_le_bytes());
+    file.extend_from_slice(&(height as u16).to_le_bytes());
+    file.extend_from_slice(&8u16.to_le_bytes()); // bits per pixel: indexed
+    file.extend_from_slice(&1u32.to_le_bytes()); // layer opacity is valid
+    file.extend_from_slice(&100u16.to_le_bytes()); // deprecated speed
+    file.extend_from_slice(&[0; 8]);
+    file.push(transparent);
+    file.extend_from_slice(&[0; 3]);
+    file.extend_from_slice(&(ids.len() as u16 % 256).to_le_bytes()); // 0 means 256
+    file.extend_from_slice(&[1, 1]); // square pixels
+    file.extend_from_slice(&[0; 4]); // grid position
+    file.extend_from_slice(&16u16.to_le_bytes());
+    file.extend_from_slice(&16u16.to_le_bytes());
+    file.extend_from_slice(&[0; 84]);
+    file.extend_from_slice(&frame);
+
+    fs::write(output, file)?;
+    Ok(())
+}
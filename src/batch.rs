@@ -0,0 +1,177 @@
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::{cache, failure, mapped};
+
+const MANIFEST_NAME: &str = ".pixel-manifest.json";
+
+/// An input file and the path its output is named after, relative to the output directory.
+pub struct Input {
+    pub path: PathBuf,
+    pub relative: PathBuf,
+}
+
+fn is_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+fn is_supported_image(path: &Path) -> bool {
+    #[cfg(feature = "video")]
+    if crate::video::is_video(path) {
+        return true;
+    }
+    ImageFormat::from_path(path).is_ok_and(|format| format.reading_enabled())
+}
+
+/// Expand glob patterns among `inputs`, keeping literal paths as given and dropping repeats.
+///
+/// With `recursive`, directories are walked for supported images, and each image's output is
+/// named after its path inside the directory. `skip` (the output directory) is never entered.
+pub fn expand_inputs(inputs: &[PathBuf], recursive: bool, skip: Option<&Path>) -> Result<Vec<Input>, Box<dyn std::error::Error>> {
+    let skip = skip.and_then(|dir| fs::canonicalize(dir).ok());
+    let mut seen = HashSet::new();
+    let mut expanded = Vec::new();
+    let mut push = |path: PathBuf, relative: PathBuf| {
+        if seen.insert(path.clone()) {
+            expanded.push(Input { path, relative });
+        }
+    };
+
+    for input in inputs {
+        let paths = if is_pattern(input) {
+            let paths = glob::glob(&input.to_string_lossy())?.collect::<Result<Vec<_>, _>>()?;
+            if paths.is_empty() {
+                return Err(failure::bad_input(format!("No files match {}", input.display())));
+            }
+            paths
+        } else {
+            vec![input.clone()]
+        };
+
+        for path in paths {
+            if recursive && path.is_dir() {
+                let mut files = Vec::new();
+                walk(&path, skip.as_deref(), &mut files)?;
+                files.sort();
+                for file in files {
+                    let relative = file.strip_prefix(&path)?.to_path_buf();
+                    push(file, relative);
+                }
+            } else if !is_pattern(input) || path.is_file() {
+                let relative = PathBuf::from(path.file_name().unwrap_or(path.as_os_str()));
+                push(path, relative);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+fn walk(dir: &Path, skip: Option<&Path>, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if skip.is_some() && fs::canonicalize(dir).ok().as_deref() == skip {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, skip, files)?;
+        } else if is_supported_image(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Pair every input with `out_dir/<relative path>.<extension>`, refusing to let two inputs
+/// write the same output.
+pub fn plan_outputs(inputs: Vec<Input>, out_dir: &Path, extension: &str) -> Result<Vec<(Input, PathBuf)>, Box<dyn std::error::Error>> {
+    let mut taken = HashSet::new();
+    let mut jobs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let stem = input.relative.file_stem().ok_or_else(|| format!("Can't derive an output name from {}", input.path.display()))?;
+        let mut name = stem.to_os_string();
+        name.push(".");
+        name.push(extension);
+        let output = match input.relative.parent() {
+            Some(parent) => out_dir.join(parent).join(name),
+            None => out_dir.join(name),
+        };
+        if !taken.insert(output.clone()) {
+            return Err(format!("{} would be written by more than one input", output.display()).into());
+        }
+        jobs.push((input, output));
+    }
+    Ok(jobs)
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct FileState {
+    modified_ns: u128,
+    len: u64,
+    hash: u64,
+    options: String,
+}
+
+/// Record of which inputs produced the outputs in a directory, and with which options.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Manifest {
+    #[serde(serialize_with = "pixel::sorted")]
+    files: HashMap<String, FileState>,
+}
+
+impl Manifest {
+    pub fn load(out_dir: &Path) -> Self {
+        File::open(out_dir.join(MANIFEST_NAME))
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, out_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        serde_json::to_writer(BufWriter::new(File::create(out_dir.join(MANIFEST_NAME))?), self)?;
+        Ok(())
+    }
+
+    fn state(path: &Path, hash: Option<u64>, options: &str) -> io::Result<FileState> {
+        let metadata = fs::metadata(path)?;
+        let modified_ns = metadata.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        let hash = match hash {
+            Some(hash) => hash,
+            None => cache::hash_pixels(cache::HASH_SEED, &mapped::map_file(path)?),
+        };
+        Ok(FileState { modified_ns, len: metadata.len(), hash, options: options.to_string() })
+    }
+
+    /// Whether `output` exists and was produced from the current contents of `input` with
+    /// the same `options`. Matching mtime and size is trusted; otherwise contents are hashed.
+    pub fn is_current(&mut self, input: &Input, output: &Path, options: &str) -> io::Result<bool> {
+        let key = input.relative.to_string_lossy().into_owned();
+        let Some(known) = self.files.get(&key).cloned() else {
+            return Ok(false);
+        };
+        if !output.exists() || known.options != options {
+            return Ok(false);
+        }
+        let quick = Self::state(&input.path, Some(known.hash), options)?;
+        if quick == known {
+            return Ok(true);
+        }
+        let current = Self::state(&input.path, None, options)?;
+        if current.hash != known.hash {
+            return Ok(false);
+        }
+        // Touched but not changed; remember the new mtime so the hash isn't needed next time
+        self.files.insert(key, current);
+        Ok(true)
+    }
+
+    pub fn record(&mut self, input: &Input, options: &str) -> io::Result<()> {
+        let state = Self::state(&input.path, None, options)?;
+        self.files.insert(input.relative.to_string_lossy().into_owned(), state);
+        Ok(())
+    }
+}
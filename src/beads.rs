@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use crate::Output;
+use crate::chart::{self, Key, Pagination};
+use crate::failure::{self, Kind};
+use crate::pdf::Document;
+use crate::physical::{Length, Sizing};
+
+// Large square pegboards of all three brands hold 29x29 beads
+const PEGBOARD: usize = 29;
+// Midi beads sit 5 mm apart on the pegboard
+const BEAD: Length = Length { mm: 5.0 };
+
+/// A brand's fuse bead colors: code, name and approximate color as `0xRRGGBB`.
+pub struct Catalog {
+    pub brand: &'static str,
+    pub beads: &'static [(&'static str, &'static str, u32)],
+}
+
+pub const PERLER: Catalog = Catalog {
+    brand: "Perler",
+    beads: &[
+        ("P01", "White", 0xf1f1f1),
+        ("P02", "Cream", 0xe0dea9),
+        ("P03", "Yellow", 0xecd800),
+        ("P04", "Orange", 0xed6120),
+        ("P05", "Red", 0xbf2633),
+        ("P06", "Bubble Gum", 0xdd6698),
+        ("P07", "Purple", 0x604089),
+        ("P08", "Dark Blue", 0x2b3f87),
+        ("P09", "Light Blue", 0x3370c0),
+        ("P10", "Dark Green", 0x1c753e),
+        ("P11", "Light Green", 0x56ba9f),
+        ("P12", "Brown", 0x513630),
+        ("P17", "Gray", 0x8a8d91),
+        ("P18", "Black", 0x2e2f32),
+        ("P20", "Rust", 0x8c3a24),
+        ("P21", "Light Brown", 0x815d34),
+        ("P33", "Peach", 0xeebab2),
+        ("P35", "Tan", 0xbc9371),
+        ("P38", "Magenta", 0xf0589a),
+        ("P52", "Pastel Blue", 0x5f9fd8),
+        ("P53", "Pastel Green", 0x85d188),
+        ("P54", "Pastel Lavender", 0x8a72c1),
+        ("P56", "Pastel Yellow", 0xf8f18c),
+        ("P57", "Cheddar", 0xf1aa0c),
+        ("P58", "Toothpaste", 0x93c8d4),
+        ("P59", "Hot Coral", 0xff3556),
+        ("P60", "Plum", 0xa23ca7),
+        ("P61", "Kiwi Lime", 0x6bbf3a),
+        ("P62", "Turquoise", 0x2b89c6),
+        ("P63", "Blush", 0xff8396),
+        ("P79", "Light Pink", 0xf6b3dd),
+        ("P83", "Pink", 0xe44d9b),
+        ("P88", "Raspberry", 0xa5164d),
+        ("P90", "Butterscotch", 0xd17f20),
+        ("P91", "Parrot Green", 0x00955e),
+        ("P92", "Dark Gray", 0x4f5155),
+        ("P93", "Blueberry Cream", 0x7b9ccb),
+        ("P96", "Cranapple", 0x7f3645),
+        ("P97", "Prickly Pear", 0xbfd840),
+        ("P98", "Sand", 0xe4c893),
+    ],
+};
+
+pub const HAMA: Catalog = Catalog {
+    brand: "Hama",
+    beads: &[
+        ("H01", "White", 0xecedec),
+        ("H02", "Cream", 0xf0e8b9),
+        ("H03", "Yellow", 0xf0b901),
+        ("H04", "Orange", 0xe64f27),
+        ("H05", "Red", 0xb63136),
+        ("H06", "Pink", 0xe1889f),
+        ("H07", "Purple", 0x694a82),
+        ("H08", "Blue", 0x2c4690),
+        ("H09", "Light Blue", 0x305cb0),
+        ("H10", "Green", 0x256847),
+        ("H11", "Light Green", 0x49ae89),
+        ("H12", "Brown", 0x534137),
+        ("H17", "Gray", 0x83888a),
+        ("H18", "Black", 0x2e2f31),
+        ("H20", "Reddish Brown", 0x7f332a),
+        ("H21", "Light Brown", 0xa5693f),
+        ("H22", "Dark Red", 0xa52d36),
+        ("H26", "Flesh", 0xde9b90),
+        ("H27", "Beige", 0xdeb48b),
+        ("H28", "Dark Green", 0x363f38),
+        ("H29", "Claret", 0xb9395e),
+        ("H30", "Burgundy", 0x682139),
+        ("H31", "Turquoise", 0x6da7b2),
+        ("H43", "Pastel Yellow", 0xf3e474),
+        ("H44", "Pastel Red", 0xe8717a),
+        ("H45", "Pastel Purple", 0x8c79b5),
+        ("H46", "Pastel Blue", 0x6e9fd7),
+        ("H47", "Pastel Green", 0x84c996),
+        ("H48", "Pastel Pink", 0xe690c5),
+        ("H49", "Azure", 0x3f9ec9),
+        ("H60", "Teddy Bear Brown", 0xb2822e),
+        ("H70", "Light Gray", 0xc2c4c4),
+        ("H71", "Dark Gray", 0x505357),
+        ("H75", "Tan", 0xa2835d),
+        ("H76", "Nougat", 0x8a5b45),
+        ("H77", "Off White", 0xe5e2d4),
+        ("H78", "Light Peach", 0xf1cfb7),
+        ("H79", "Apricot", 0xf09c55),
+        ("H82", "Plum", 0x8f2f6e),
+        ("H83", "Petrol", 0x1c6b7d),
+    ],
+};
+
+pub const ARTKAL: Catalog = Catalog {
+    brand: "Artkal",
+    beads: &[
+        ("S01", "White", 0xffffff),
+        ("S02", "Black", 0x000000),
+        ("S03", "Light Gray", 0xc8c8c8),
+        ("S04", "Gray", 0x8c8c8c),
+        ("S05", "Dark Gray", 0x4f4f4f),
+        ("S06", "Red", 0xd2232a),
+        ("S07", "Dark Red", 0x9e1b32),
+        ("S08", "Orange", 0xf37021),
+        ("S09", "Light Orange", 0xf9a65a),
+        ("S10", "Yellow", 0xffde17),
+        ("S11", "Light Yellow", 0xfff27a),
+        ("S12", "Cream", 0xf5e8c0),
+        ("S13", "Lime", 0xa6ce39),
+        ("S14", "Green", 0x00a651),
+        ("S15", "Dark Green", 0x006838),
+        ("S16", "Mint", 0x9fd9b4),
+        ("S17", "Turquoise", 0x00a99d),
+        ("S18", "Sky Blue", 0x6dcff6),
+        ("S19", "Blue", 0x0072bc),
+        ("S20", "Dark Blue", 0x1b2a6b),
+        ("S21", "Lavender", 0xb29dd9),
+        ("S22", "Purple", 0x662d91),
+        ("S23", "Magenta", 0xec008c),
+        ("S24", "Pink", 0xf49ac1),
+        ("S25", "Light Pink", 0xfbd0e0),
+        ("S26", "Peach", 0xfbc8a8),
+        ("S27", "Tan", 0xc69c6d),
+        ("S28", "Light Brown", 0xa0643c),
+        ("S29", "Brown", 0x6b3e26),
+        ("S30", "Dark Brown", 0x3e2415),
+    ],
+};
+
+/// Write a fuse bead pattern PDF: the map matched to the `catalog` colors, one 29x29
+/// pegboard per page, followed by the number of beads of each color.
+pub fn write(map: &Output, catalog: &Catalog, output: &Path, sizing: &Sizing, pagination: &Pagination) -> Result<(), Box<dyn std::error::Error>> {
+    let colors: Vec<u32> = catalog.beads.iter().map(|&(_, _, rgb)| rgb).collect();
+    let matched = chart::match_colors(map, &colors).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    let keys: Vec<Key> = matched
+        .used
+        .iter()
+        .zip(chart::symbols(matched.used.len()))
+        .map(|(&(index, _), symbol)| Key { symbol, color: chart::opaque(colors[index]) })
+        .collect();
+
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let boards = width.div_ceil(PEGBOARD) * map.matrix.len().div_ceil(PEGBOARD);
+    let total: usize = matched.used.iter().map(|&(_, count)| count).sum();
+    let title = format!(
+        "{} beads: {}x{} ({}), {} pegboards, {} beads in {} colors",
+        catalog.brand,
+        width,
+        map.matrix.len(),
+        sizing.describe(width, map.matrix.len(), BEAD),
+        boards,
+        total,
+        keys.len()
+    );
+
+    let mut doc = Document::new(pagination.paper());
+    chart::draw(&mut doc, &title, &matched.cells, &keys, (PEGBOARD, PEGBOARD), PEGBOARD, pagination).map_err(failure::bad_input)?;
+    let rows: Vec<(&Key, Vec<String>)> = keys
+        .iter()
+        .zip(&matched.used)
+        .map(|(key, &(index, count))| {
+            let (code, name, _) = catalog.beads[index];
+            (key, vec![code.to_string(), name.to_string(), count.to_string()])
+        })
+        .collect();
+    chart::legend(&mut doc, &title, &["Code", "Name", "Beads"], &rows);
+    doc.save(output)
+}
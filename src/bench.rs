@@ -0,0 +1,63 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use indicatif::ProgressBar;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::edges::Blocks;
+use crate::stream::ImageRows;
+use crate::{map_rows, write_json, ColorMapper};
+
+// Cheap integer mixer so synthetic images are identical between runs and releases
+fn mix(mut v: u32) -> u32 {
+    v ^= v >> 16;
+    v = v.wrapping_mul(0x7feb_352d);
+    v ^= v >> 15;
+    v = v.wrapping_mul(0x846c_a68b);
+    v ^ (v >> 16)
+}
+
+/// Square test image painted in 4x4 patches drawn from `colors` distinct opaque colors.
+fn synthetic_image(size: u32, colors: u32) -> RgbaImage {
+    let palette: Vec<Rgba<u8>> = (0..colors)
+        .map(|i| {
+            let h = mix(i).to_le_bytes();
+            Rgba([h[0], h[1], h[2], 255])
+        })
+        .collect();
+    RgbaImage::from_fn(size, size, |x, y| palette[(mix((y / 4) * size + x / 4) % colors) as usize])
+}
+
+/// Map synthetic images of every size/palette/block combination and print the best time
+/// of `iterations` runs, including JSON serialization.
+pub fn run(sizes: &[u32], palettes: &[u32], block_sizes: &[u32], tolerance: f64, iterations: u32) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{:>6} {:>8} {:>6} {:>10} {:>10} {:>8}", "size", "palette", "block", "time", "Mpx/s", "colors");
+    for &size in sizes {
+        for &colors in palettes {
+            let img = DynamicImage::ImageRgba8(synthetic_image(size, colors.max(1)));
+            for &block_size in block_sizes {
+                let mut best = Duration::MAX;
+                let mut found = 0;
+                for _ in 0..iterations.max(1) {
+                    let mut source = ImageRows::new(img.clone());
+                    let start = Instant::now();
+                    let mut mapper = ColorMapper::new(tolerance);
+                    let matrix = map_rows(&mut source, Blocks::plain(block_size), &mut mapper, None, None, &ProgressBar::hidden())?;
+                    write_json(&matrix, &mapper.id_to_color, &[], &mut io::sink())?;
+                    best = best.min(start.elapsed());
+                    found = mapper.id_to_color.len();
+                }
+                let megapixels = size as f64 * size as f64 / 1e6;
+                println!(
+                    "{:>6} {:>8} {:>6} {:>8.1}ms {:>10.2} {:>8}",
+                    size,
+                    colors,
+                    block_size,
+                    best.as_secs_f64() * 1e3,
+                    megapixels / best.as_secs_f64(),
+                    found
+                );
+            }
+        }
+    }
+    Ok(())
+}
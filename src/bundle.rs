@@ -0,0 +1,158 @@
+use clap::{Arg, Command, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+use crate::preset::Preset;
+use crate::{config, failure};
+
+#[derive(Subcommand, Debug)]
+pub enum PresetAction {
+    /// Save options under a name, for `--preset <NAME>` to set them as defaults
+    Save {
+        /// Name of the preset
+        name: String,
+
+        /// Options to save, as on the command line (`--block-size 8 --tolerance 12`)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        options: Vec<String>,
+    },
+    /// List the saved presets with their options
+    List,
+    /// Delete a saved preset
+    Delete {
+        /// Name of the preset
+        name: String,
+    },
+}
+
+fn presets_dir() -> PathBuf {
+    config::user_path().with_file_name("presets")
+}
+
+fn check_name(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if name.is_empty() || name.starts_with('.') || !name.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c)) {
+        return Err(failure::bad_input(format!("Invalid preset name {}: use letters, digits, '.', '_' and '-'", name)));
+    }
+    if Preset::from_str(name, true).is_ok() {
+        return Err(failure::bad_input(format!("{} is a platform palette of --preset", name)));
+    }
+    Ok(())
+}
+
+fn path(name: &str) -> PathBuf {
+    presets_dir().join(format!("{}.toml", name))
+}
+
+/// Take `--preset <NAME>` out of `args` wherever NAME is a saved preset, returning the remaining
+/// arguments and the presets in order. Platform palettes are left for the `--preset` option.
+pub fn extract(args: impl IntoIterator<Item = OsString>) -> (Vec<OsString>, Vec<String>) {
+    let saved = |name: &str| check_name(name).is_ok() && path(name).is_file();
+    let (mut rest, mut names) = (Vec::new(), Vec::new());
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            rest.push(arg);
+            rest.extend(args);
+            break;
+        }
+        match arg.to_str() {
+            Some("--preset") if args.peek().and_then(|name| name.to_str()).is_some_and(saved) => {
+                names.extend(args.next().and_then(|name| name.into_string().ok()));
+            }
+            Some(option) if option.strip_prefix("--preset=").is_some_and(saved) => names.push(option["--preset=".len()..].to_string()),
+            _ => rest.push(arg),
+        }
+    }
+    (rest, names)
+}
+
+/// Set the options of the saved preset `name` as defaults of `command`.
+pub fn apply(command: &mut Command, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path(name);
+    let table: Table = toml::from_str(&fs::read_to_string(&path)?).map_err(|e| failure::bad_input(format!("{}: {}", path.display(), e)))?;
+    config::apply_table(command, &table, &path)
+}
+
+// The option of `command` or any of its subcommands `matches` picks
+fn find_arg(command: &Command, matches: &dyn Fn(&Arg) -> bool) -> Option<Arg> {
+    command.get_arguments().find(|arg| matches(arg)).cloned().or_else(|| command.get_subcommands().find_map(|subcommand| find_arg(subcommand, matches)))
+}
+
+// Numbers are kept as numbers so the saved file reads naturally
+fn toml_value(text: &str) -> Value {
+    text.parse().map(Value::Integer).or_else(|_| text.parse().map(Value::Float)).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+// Command-line options as a table of defaults, checked against the options of `command`;
+// repeated options collect their values in an array
+fn options_table(command: &Command, options: &[String]) -> Result<Table, Box<dyn std::error::Error>> {
+    let mut table = Table::new();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let (flag, inline) = match option.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (option.as_str(), None),
+        };
+        let arg = match (flag.strip_prefix("--"), flag.strip_prefix('-').and_then(|short| short.parse::<char>().ok())) {
+            (Some(long), _) => find_arg(command, &|arg| arg.get_long() == Some(long)),
+            (None, Some(short)) => find_arg(command, &|arg| arg.get_short() == Some(short)),
+            (None, None) => return Err(failure::bad_input(format!("Expected an option, got {}", option))),
+        };
+        let Some(arg) = arg else {
+            return Err(failure::bad_input(format!("No option {}", flag)));
+        };
+        let value = if arg.get_action().takes_values() {
+            let value = inline.or_else(|| options.next().map(String::as_str)).ok_or_else(|| failure::bad_input(format!("{} needs a value", flag)))?;
+            toml_value(value)
+        } else {
+            Value::Boolean(true)
+        };
+        match table.get_mut(arg.get_id().as_str()) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(first) => *first = Value::Array(vec![first.clone(), value]),
+            None => {
+                table.insert(arg.get_id().to_string(), value);
+            }
+        }
+    }
+    Ok(table)
+}
+
+/// Save, list or delete the named presets in `~/.config/pixel/presets`, one TOML file of option
+/// defaults each, like the configuration file. `command` is the whole command line interface,
+/// to check saved options against.
+pub fn run(action: &PresetAction, command: &Command) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        PresetAction::Save { name, options } => {
+            check_name(name)?;
+            let table = options_table(command, options)?;
+            fs::create_dir_all(presets_dir()).map_err(failure::write)?;
+            fs::write(path(name), toml::to_string(&table)?).map_err(failure::write)?;
+        }
+        PresetAction::List => {
+            let mut names: Vec<String> = match fs::read_dir(presets_dir()) {
+                Ok(entries) => entries.filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".toml").map(str::to_string)).collect(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e.into()),
+            };
+            names.sort();
+            for name in names {
+                let table: Table = toml::from_str(&fs::read_to_string(path(&name))?)?;
+                let options: Vec<String> = table.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+                println!("{}: {}", name, options.join(" "));
+            }
+        }
+        PresetAction::Delete { name } => {
+            check_name(name)?;
+            match fs::remove_file(path(name)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(failure::bad_input(format!("No preset {}", name))),
+                Err(e) => return Err(failure::write(e)),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Block hashes and color assignments from a previous run over the same input.
+#[derive(Serialize, Deserialize)]
+pub struct BlockCache {
+    pub width: u32,
+    pub height: u32,
+    pub block_size: u32,
+    pub tolerance: f64,
+    /// (source pixel hash, color ID) per block, row-major
+    pub blocks: Vec<Vec<(u64, u32)>>,
+    #[serde(serialize_with = "pixel::sorted")]
+    pub colors: HashMap<u32, String>,
+    /// Every hex color seen, including fuzzy-matched ones, mapped to its ID
+    #[serde(serialize_with = "pixel::sorted")]
+    pub aliases: HashMap<String, u32>,
+}
+
+impl BlockCache {
+    pub fn new(block_size: u32, tolerance: f64) -> Self {
+        BlockCache {
+            width: 0,
+            height: 0,
+            block_size,
+            tolerance,
+            blocks: Vec::new(),
+            colors: HashMap::new(),
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+/// Cache file for `input` inside `dir`, unique per canonical input path.
+pub fn path_for(dir: &Path, input: &Path) -> PathBuf {
+    let canonical = fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf());
+    let hash = hash_pixels(HASH_SEED, canonical.as_os_str().as_encoded_bytes());
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    dir.join(format!("{}-{:016x}.json", stem, hash))
+}
+
+/// Load a cache usable with these options; missing, unreadable or stale caches yield `None`.
+pub fn load(path: &Path, block_size: u32, tolerance: f64) -> Option<BlockCache> {
+    let file = File::open(path).ok()?;
+    let cache: BlockCache = serde_json::from_reader(BufReader::new(file)).ok()?;
+    // IDs only mean the same thing if colors were grouped the same way
+    (cache.block_size == block_size && cache.tolerance == tolerance).then_some(cache)
+}
+
+pub fn save(path: &Path, cache: &BlockCache) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    serde_json::to_writer(BufWriter::new(File::create(path)?), cache)?;
+    Ok(())
+}
+
+/// Finished results on disk, by the content of their input and the options they were made with,
+/// so identical requests are answered without redoing the work. Inputs are told apart by
+/// SHA-256, as a client could otherwise craft two images that share an entry.
+pub struct ResultCache {
+    dir: PathBuf,
+}
+
+impl ResultCache {
+    pub fn new(dir: &Path) -> Self {
+        ResultCache { dir: dir.to_path_buf() }
+    }
+
+    fn path(&self, kind: &str, input: &[u8], key: &str) -> PathBuf {
+        let content: String = Sha256::digest(input).iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}-{}-{:016x}", kind, content, hash_pixels(HASH_SEED, key.as_bytes())))
+    }
+
+    pub fn get(&self, kind: &str, input: &[u8], key: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(kind, input, key)).ok()
+    }
+
+    /// Store `result`; a temporary file renamed into place keeps concurrent readers from seeing
+    /// it half written.
+    pub fn put(&self, kind: &str, input: &[u8], key: &str, result: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        file.write_all(result)?;
+        file.persist(self.path(kind, input, key))?;
+        Ok(())
+    }
+}
+
+pub const HASH_SEED: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a over bytes, continuing from `hash`, with a final avalanche so every input bit
+/// reaches every output bit. Unlike `DefaultHasher` it is stable across builds, so caches
+/// survive upgrades.
+pub fn hash_pixels(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash = (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    // The finalizer of MurmurHash3
+    hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash = (hash ^ (hash >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_differing_in_high_bits_get_their_own_results() {
+        // Two 59-byte PPMs whose bytes 23 and 31 differ by the top bit
+        let mut first = b"P6\n4 4\n255\n".to_vec();
+        first.resize(59, 0x40);
+        let mut second = first.clone();
+        second[23] ^= 0x80;
+        second[31] ^= 0x80;
+        let cache = ResultCache::new(Path::new("results"));
+        assert_ne!(cache.path("map", &first, "b=1"), cache.path("map", &second, "b=1"));
+    }
+
+    #[test]
+    fn high_bits_of_two_words_change_the_hash() {
+        // Alpha of pixels 1 and 3 of an RGBA row, each the top byte of an 8-byte word
+        let before = [200, 50, 50, 255, 200, 50, 50, 255, 200, 50, 50, 255, 200, 50, 50, 255];
+        let mut after = before;
+        after[7] ^= 0x80;
+        after[15] ^= 0x80;
+        assert_ne!(hash_pixels(HASH_SEED, &before), hash_pixels(HASH_SEED, &after));
+    }
+
+    #[test]
+    fn hashing_continues_from_a_previous_hash() {
+        assert_ne!(hash_pixels(HASH_SEED, b"block"), hash_pixels(hash_pixels(HASH_SEED, b"other"), b"block"));
+    }
+}
@@ -0,0 +1,265 @@
+use clap::Args;
+use image::Rgba;
+use std::collections::HashMap;
+
+use crate::pdf::{Document, Font, MARGIN, Page, Paper};
+use crate::{Output, color_distance_sq, hex_to_rgba};
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const GRID: Rgba<u8> = Rgba([128, 128, 128, 255]);
+const HEADER_HEIGHT: f32 = 28.0;
+const LABEL_SPACE: f32 = 18.0;
+const LEGEND_ROW: f32 = 16.0;
+
+/// A color of a printed pattern and the symbol marking its cells.
+pub struct Key {
+    pub symbol: String,
+    pub color: Rgba<u8>,
+}
+
+/// A map's cells matched to the nearest colors of a catalog of threads, beads or bricks.
+pub struct Matched {
+    /// Catalog index and cell count of each color in use, in catalog order
+    pub used: Vec<(usize, usize)>,
+    /// Index into `used` per cell; `None` for transparent cells
+    pub cells: Vec<Vec<Option<usize>>>,
+}
+
+/// `0xRRGGBB` as an opaque color.
+pub fn opaque(rgb: u32) -> Rgba<u8> {
+    Rgba([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255])
+}
+
+/// Match every opaque color of `map` to the nearest `catalog` color (`0xRRGGBB`), ignoring alpha.
+pub fn match_colors(map: &Output, catalog: &[u32]) -> Result<Matched, String> {
+    let mut nearest = HashMap::new();
+    for (&id, hex) in &map.colors {
+        let color = hex_to_rgba(hex).map_err(|e| e.to_string())?;
+        if color[3] == 0 {
+            continue;
+        }
+        let color = Rgba([color[0], color[1], color[2], 255]);
+        let index = (0..catalog.len()).min_by_key(|&i| color_distance_sq(&color, &opaque(catalog[i]))).ok_or("Color catalog is empty")?;
+        nearest.insert(id, index);
+    }
+
+    let mut counts = vec![0; catalog.len()];
+    for id in map.matrix.iter().flatten() {
+        if let Some(&index) = nearest.get(id) {
+            counts[index] += 1;
+        }
+    }
+    let used: Vec<(usize, usize)> = counts.into_iter().enumerate().filter(|&(_, count)| count > 0).collect();
+    let position: HashMap<usize, usize> = used.iter().enumerate().map(|(i, &(index, _))| (index, i)).collect();
+    let cells = map.matrix.iter().map(|row| row.iter().map(|id| nearest.get(id).map(|index| position[index])).collect()).collect();
+    Ok(Matched { used, cells })
+}
+
+/// `count` distinct symbols: single characters first, then pairs of them.
+pub fn symbols(count: usize) -> Vec<String> {
+    const SINGLE: &str = "XO+#*=%@&/S<>VZTHNAKEWMCUDLPR";
+    let single: Vec<char> = SINGLE.chars().collect();
+    let pairs = single.iter().flat_map(|a| single.iter().map(move |b| format!("{}{}", a, b)));
+    single.iter().map(char::to_string).chain(pairs).take(count).collect()
+}
+
+/// Alphanumeric codes for `count` colors: A-Z, then AA, AB and so on.
+pub fn letter_codes(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|mut i| {
+            let mut code = Vec::new();
+            loop {
+                code.push(b'A' + (i % 26) as u8);
+                if i < 26 {
+                    break;
+                }
+                i = i / 26 - 1;
+            }
+            code.iter().rev().map(|&c| c as char).collect()
+        })
+        .collect()
+}
+
+fn ink(color: Rgba<u8>) -> Rgba<u8> {
+    let [r, g, b, _] = color.0;
+    if 299 * r as u32 + 587 * g as u32 + 114 * b as u32 > 128_000 { BLACK } else { WHITE }
+}
+
+fn header(page: &mut Page, title: &str, subtitle: &str) {
+    page.text(MARGIN, MARGIN + 12.0, Font::Bold, 12.0, BLACK, title);
+    page.text(MARGIN, MARGIN + 24.0, Font::Regular, 9.0, BLACK, subtitle);
+}
+
+/// Printing of charts too big for one page.
+#[derive(Args, Clone, Copy, Debug, Default)]
+pub struct Pagination {
+    /// Paper to print on; also adds an overview page showing how the pages fit together
+    #[arg(long, value_enum, value_name = "PAPER")]
+    paginate: Option<Paper>,
+
+    /// Rows and columns repeated at the edges of neighbouring pages, to line them up
+    #[arg(long, default_value_t = 0, requires = "paginate")]
+    overlap: usize,
+}
+
+impl Pagination {
+    pub fn paper(&self) -> Paper {
+        self.paginate.unwrap_or_default()
+    }
+}
+
+fn chart_area(doc: &Document) -> (f32, f32) {
+    (doc.width - 2.0 * MARGIN - LABEL_SPACE, doc.height - 2.0 * MARGIN - HEADER_HEIGHT - LABEL_SPACE)
+}
+
+/// Columns and rows of `cell`-point cells that fit on a chart page of `paper`, rounded down to
+/// multiples of `round_to`.
+pub fn fit(paper: Paper, cell: f32, round_to: usize) -> (usize, usize) {
+    let (area_width, area_height) = chart_area(&Document::new(paper));
+    let round = |n: f32| ((n / cell) as usize / round_to * round_to).max(round_to);
+    (round(area_width), round(area_height))
+}
+
+// Start of each page along an axis of `length` cells, `section` long and overlapping by `overlap`
+fn page_starts(length: usize, section: usize, overlap: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    while starts.last().unwrap() + section < length {
+        starts.push(starts.last().unwrap() + section - overlap);
+    }
+    starts
+}
+
+// The whole chart scaled to one page, with the outline and number of each page on top
+fn overview(doc: &mut Document, title: &str, cells: &[Vec<Option<usize>>], keys: &[Key], pages: &[(usize, usize, usize, usize)]) {
+    let width = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let (area_width, area_height) = chart_area(doc);
+    let scale = (area_width / width as f32).min(area_height / cells.len().max(1) as f32);
+    let (left, top) = (MARGIN + LABEL_SPACE, MARGIN + HEADER_HEIGHT + LABEL_SPACE);
+    let page = doc.add_page();
+    header(page, title, &format!("Overview: {} pages", pages.len()));
+    for (y, row) in cells.iter().enumerate() {
+        // One rectangle per run of equal cells keeps the page small
+        let mut x = 0;
+        while x < row.len() {
+            let run = row[x..].iter().take_while(|&&cell| cell == row[x]).count();
+            if let Some(key) = row[x].and_then(|i| keys.get(i)) {
+                page.fill_rect(left + x as f32 * scale, top + y as f32 * scale, run as f32 * scale, scale, key.color);
+            }
+            x += run;
+        }
+    }
+    for (n, &(x0, y0, x1, y1)) in pages.iter().enumerate() {
+        let (x, y) = (left + x0 as f32 * scale, top + y0 as f32 * scale);
+        let (w, h) = ((x1 - x0) as f32 * scale, (y1 - y0) as f32 * scale);
+        page.stroke_rect(x, y, w, h, 1.2);
+        page.fill_rect(x + 2.0, y + 2.0, 16.0, 12.0, WHITE);
+        page.text(x + 4.0, y + 11.0, Font::Bold, 9.0, BLACK, &(n + 1).to_string());
+    }
+}
+
+/// Chart `cells` (indices into `keys`, `None` left blank) over as many pages as it takes to
+/// show at most `section` (columns, rows) cells each, with bold lines every `bold_every` cells.
+pub fn draw(
+    doc: &mut Document,
+    title: &str,
+    cells: &[Vec<Option<usize>>],
+    keys: &[Key],
+    section: (usize, usize),
+    bold_every: usize,
+    pagination: &Pagination,
+) -> Result<(), String> {
+    let width = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let height = cells.len();
+    let (section_columns, section_rows) = (section.0.max(1), section.1.max(1));
+    if pagination.overlap >= section_columns.min(section_rows) {
+        return Err(format!("Overlap must be less than the {}x{} cells on a page", section_columns, section_rows));
+    }
+    let starts_x = page_starts(width, section_columns, pagination.overlap);
+    let starts_y = page_starts(height, section_rows, pagination.overlap);
+    let pages: Vec<(usize, usize, usize, usize)> = starts_y
+        .iter()
+        .flat_map(|&y0| starts_x.iter().map(move |&x0| (x0, y0, (x0 + section_columns).min(width), (y0 + section_rows).min(height))))
+        .collect();
+    if pagination.paginate.is_some() {
+        overview(doc, title, cells, keys, &pages);
+    }
+
+    let (area_width, area_height) = chart_area(doc);
+    let cell = (area_width / section_columns as f32).min(area_height / section_rows as f32).min(20.0);
+    let (left, top) = (MARGIN + LABEL_SPACE, MARGIN + HEADER_HEIGHT + LABEL_SPACE);
+
+    for (n, &(x0, y0, x1, y1)) in pages.iter().enumerate() {
+        let page = doc.add_page();
+        header(
+            page,
+            title,
+            &format!(
+                "Page {} of {} (row {}, column {}): columns {}-{}, rows {}-{}",
+                n + 1,
+                pages.len(),
+                n / starts_x.len() + 1,
+                n % starts_x.len() + 1,
+                x0 + 1,
+                x1,
+                y0 + 1,
+                y1
+            ),
+        );
+
+        for (y, row) in cells.iter().enumerate().take(y1).skip(y0) {
+            for x in x0..x1 {
+                let Some(key) = row.get(x).copied().flatten().and_then(|i| keys.get(i)) else {
+                    continue;
+                };
+                let (cx, cy) = (left + (x - x0) as f32 * cell, top + (y - y0) as f32 * cell);
+                page.fill_rect(cx, cy, cell, cell, key.color);
+                let size = (cell * 0.7).min(cell * 1.2 / key.symbol.len() as f32);
+                page.mono_centered(cx + cell / 2.0, cy + cell / 2.0, size, ink(key.color), &key.symbol);
+            }
+        }
+
+        let (right, bottom) = (left + (x1 - x0) as f32 * cell, top + (y1 - y0) as f32 * cell);
+        for x in x0..=x1 {
+            let bold = x % bold_every == 0 || x == x0 || x == x1;
+            let gx = left + (x - x0) as f32 * cell;
+            page.line((gx, top), (gx, bottom), if bold { 1.2 } else { 0.3 }, if bold { BLACK } else { GRID });
+            if (x % bold_every == 0 || x == x0) && x < x1 {
+                page.text(gx + 1.0, top - 4.0, Font::Regular, 7.0, BLACK, &(x + 1).to_string());
+            }
+        }
+        for y in y0..=y1 {
+            let bold = y % bold_every == 0 || y == y0 || y == y1;
+            let gy = top + (y - y0) as f32 * cell;
+            page.line((left, gy), (right, gy), if bold { 1.2 } else { 0.3 }, if bold { BLACK } else { GRID });
+            if (y % bold_every == 0 || y == y0) && y < y1 {
+                page.text(MARGIN, gy + 7.0, Font::Regular, 7.0, BLACK, &(y + 1).to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// List each key with its symbol on a swatch, followed by `columns` of text under `headers`.
+pub fn legend(doc: &mut Document, title: &str, headers: &[&str], rows: &[(&Key, Vec<String>)]) {
+    let per_page = ((doc.height - 2.0 * MARGIN - HEADER_HEIGHT - LEGEND_ROW) / LEGEND_ROW) as usize;
+    let column_width = (doc.width - 2.0 * MARGIN - 40.0) / headers.len().max(1) as f32;
+    let column_x = |i: usize| MARGIN + 40.0 + i as f32 * column_width;
+    for (n, chunk) in rows.chunks(per_page.max(1)).enumerate() {
+        let page = doc.add_page();
+        header(page, title, &format!("Legend, page {} of {}", n + 1, rows.len().div_ceil(per_page.max(1))));
+        let mut y = MARGIN + HEADER_HEIGHT + LEGEND_ROW;
+        for (i, text) in headers.iter().enumerate() {
+            page.text(column_x(i), y - 4.0, Font::Bold, 9.0, BLACK, text);
+        }
+        for (key, columns) in chunk {
+            page.fill_rect(MARGIN, y + 1.0, 28.0, LEGEND_ROW - 2.0, key.color);
+            page.stroke_rect(MARGIN, y + 1.0, 28.0, LEGEND_ROW - 2.0, 0.5);
+            page.mono_centered(MARGIN + 14.0, y + LEGEND_ROW / 2.0, 9.0, ink(key.color), &key.symbol);
+            for (i, text) in columns.iter().enumerate() {
+                page.text(column_x(i), y + LEGEND_ROW - 4.0, Font::Regular, 9.0, BLACK, text);
+            }
+            y += LEGEND_ROW;
+        }
+    }
+}
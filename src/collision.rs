@@ -0,0 +1,75 @@
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::failure::{self, Kind};
+use crate::matrix::Matrix;
+use crate::{hex_to_rgba, load_map, mapped, write_rows};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SolidBy {
+    /// Cells that aren't fully transparent
+    Alpha,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum CollisionFormat {
+    /// `{"width", "height", "solid": [[0, 1, ...], ...]}`
+    #[default]
+    Json,
+    /// One line of comma-separated 0s and 1s per row
+    Csv,
+    /// Packed bits, each row padded to whole bytes with its leftmost cell in the high bit
+    Bitmask,
+}
+
+/// Which cells of a map block movement.
+pub enum Solid<'a> {
+    Ids(&'a [u32]),
+    By(SolidBy),
+}
+
+/// Write a grid of the map's solid cells (1) and open cells (0) to `output`. Short rows are
+/// padded with open cells.
+pub fn run(input: &Path, output: &Path, solid: Solid, format: CollisionFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let map = load_map(input)?;
+    let solid_ids: HashSet<u32> = match solid {
+        Solid::Ids(ids) => ids.iter().copied().collect(),
+        Solid::By(SolidBy::Alpha) => {
+            let mut ids = HashSet::new();
+            for (&id, hex) in &map.colors {
+                if hex_to_rgba(hex).map_err(|e| failure::tag(Kind::InvalidJson, e))?[3] > 0 {
+                    ids.insert(id);
+                }
+            }
+            ids
+        }
+    };
+
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let grid: Vec<Vec<u32>> = map.matrix.iter().map(|row| (0..width).map(|x| row.get(x).is_some_and(|id| solid_ids.contains(id)) as u32).collect()).collect();
+
+    mapped::write_file(output, |w| match format {
+        CollisionFormat::Json => {
+            write!(w, "{{\n  \"width\": {},\n  \"height\": {},\n  \"solid\": [\n", width, grid.len())?;
+            write_rows(&Matrix::Memory(grid.clone()), b"    ", w)?;
+            w.write_all(b"  ]\n}")
+        }
+        CollisionFormat::Csv => {
+            for row in &grid {
+                let cells: Vec<String> = row.iter().map(u32::to_string).collect();
+                writeln!(w, "{}", cells.join(","))?;
+            }
+            Ok(())
+        }
+        CollisionFormat::Bitmask => {
+            for row in &grid {
+                let bytes: Vec<u8> = row.chunks(8).map(|cells| cells.iter().enumerate().fold(0u8, |byte, (i, &cell)| byte | (cell as u8) << (7 - i))).collect();
+                w.write_all(&bytes)?;
+            }
+            Ok(())
+        }
+    })
+    .map_err(failure::write)?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use clap::builder::FalseyValueParser;
+use clap::{ArgAction, Command};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use crate::failure;
+
+/// Path of the user's configuration file, under `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn user_path() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default();
+    base.join("pixel").join("config.toml")
+}
+
+fn value_string(key: &str, value: &Value) -> Result<String, Box<dyn std::error::Error>> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => Ok(value.to_string()),
+        _ => Err(failure::bad_input(format!("Unsupported value for {}: {}", key, value))),
+    }
+}
+
+// Arrays give all the values of options that take several
+fn value_strings(key: &str, value: &Value) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    match value {
+        Value::Array(values) => values.iter().map(|value| value_string(key, value)).collect(),
+        value => Ok(vec![value_string(key, value)?]),
+    }
+}
+
+// Make `values` the default of `key` in `command` and every subcommand that has it; returns
+// whether any did
+fn set_default(command: &mut Command, key: &str, values: &[String]) -> bool {
+    let id = key.replace('-', "_");
+    let mut found = false;
+    if command.get_arguments().any(|arg| arg.get_id() == id.as_str()) {
+        *command = std::mem::take(command).mut_arg(&id, |arg| arg.default_values(values));
+        found = true;
+    }
+    for subcommand in command.get_subcommands_mut() {
+        found |= set_default(subcommand, key, values);
+    }
+    found
+}
+
+/// Apply the options in `table` as defaults of `command`: plain values to every command with
+/// that option, tables to the subcommand they're named after.
+pub fn apply_table(command: &mut Command, table: &Table, source: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    for (key, value) in table {
+        if let Value::Table(options) = value {
+            let Some(subcommand) = command.find_subcommand_mut(key) else {
+                return Err(failure::bad_input(format!("{}: no command {}", source.display(), key)));
+            };
+            apply_table(subcommand, options, source)?;
+        } else if !set_default(command, key, &value_strings(key, value)?) {
+            return Err(failure::bad_input(format!("{}: no option {} in {}", source.display(), key, command.get_name())));
+        }
+    }
+    Ok(())
+}
+
+/// Default option values, read from `~/.config/pixel/config.toml` and then `./pixel.toml`, each
+/// overriding the one before; flags given on the command line override both.
+///
+/// ```toml
+/// # Options of any command that has them
+/// block_size = 8
+/// tolerance = 12.5
+/// preset = "pico8"
+///
+/// # Options of one command only
+/// [export]
+/// format = "tiled"
+/// tile_size = "16x16"
+/// ```
+///
+/// Options go by their long names, with `_` or `-` between words.
+pub fn apply(mut command: Command) -> Result<Command, Box<dyn std::error::Error>> {
+    for path in [user_path(), PathBuf::from("pixel.toml")] {
+        if !path.is_file() {
+            continue;
+        }
+        let table: Table = toml::from_str(&fs::read_to_string(&path)?).map_err(|e| failure::bad_input(format!("{}: {}", path.display(), e)))?;
+        apply_table(&mut command, &table, &path)?;
+    }
+    Ok(command)
+}
+
+/// Let every option of `command` also be set by a `PIXEL_<NAME>` environment variable, as in
+/// `PIXEL_BLOCK_SIZE=8` for `--block-size 8`; flags override them, and they override the defaults.
+pub fn env(command: Command) -> Command {
+    command
+        .mut_args(|arg| match (arg.get_long(), arg.get_action()) {
+            (None, _) | (_, ArgAction::Count) => arg,
+            (Some(long), action) => {
+                let name = format!("PIXEL_{}", long.replace('-', "_").to_uppercase());
+                // Flags read `1`, `yes` and `on` as set, `0`, `no`, `off` and empty as not
+                let is_flag = matches!(action, ArgAction::SetTrue);
+                let arg = arg.env(name);
+                if is_flag { arg.value_parser(FalseyValueParser::new()) } else { arg }
+            }
+        })
+        .mut_subcommands(env)
+}
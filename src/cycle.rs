@@ -0,0 +1,118 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::failure::{self, Kind};
+use crate::style::StyleOptions;
+use crate::{MapFile, animation, mapped};
+
+// Most frames written, with --frames or until the cycles line up again
+pub const MAX_FRAMES: u32 = 1000;
+
+/// IDs whose colors rotate, from `A-B`: each color moves one ID up per step, or down when
+/// `A` is above `B`. `A-B@N` moves only every N frames.
+#[derive(Clone, Copy, Debug)]
+pub struct Cycle {
+    first: u32,
+    last: u32,
+    every: u32,
+}
+
+impl FromStr for Cycle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (range, every) = s.split_once('@').unwrap_or((s, "1"));
+        let parsed = range.split_once('-').and_then(|(first, last)| Some((first.parse().ok()?, last.parse().ok()?, every.parse().ok()?)));
+        match parsed {
+            Some((first, last, every)) if first != last && every > 0 => Ok(Cycle { first, last, every }),
+            _ => Err(format!("Expected a range of two different IDs as A-B or A-B@N, got {}", s)),
+        }
+    }
+}
+
+impl Cycle {
+    fn len(&self) -> u64 {
+        self.first.abs_diff(self.last) as u64 + 1
+    }
+
+    // Frames until the colors are back where they started
+    fn period(&self) -> u64 {
+        self.len() * self.every as u64
+    }
+
+    /// ID whose original color `id` shows `frame` frames in.
+    fn source(&self, id: u32, frame: u64) -> u32 {
+        let (low, len) = (self.first.min(self.last), self.len());
+        let shift = frame / self.every as u64 % len;
+        let i = (id - low) as u64;
+        let i = if self.first < self.last { (i + len - shift) % len } else { (i + shift) % len };
+        low + i as u32
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Render the single matrix of the map in `input` once per frame of `cycles` rotating its
+/// colors, and write the frames to `output` as a looping GIF, APNG or WebP at `fps`. Without
+/// `frames`, the animation runs until every cycle is back at its start.
+pub fn run(input: &Path, output: &Path, cycles: &[Cycle], fps: f64, frames: Option<u32>, style: &StyleOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if !fps.is_finite() || fps <= 0.0 {
+        return Err(failure::bad_input("--fps must be greater than 0"));
+    }
+    if !animation::can_write(output) {
+        return Err(failure::bad_input("Palette cycles can only be written as GIF, PNG (APNG) or WebP"));
+    }
+    style.validate()?;
+    let mut ranges: Vec<(u32, u32)> = cycles.iter().map(|cycle| (cycle.first.min(cycle.last), cycle.first.max(cycle.last))).collect();
+    ranges.sort_unstable();
+    if let Some(pair) = ranges.windows(2).find(|pair| pair[1].0 <= pair[0].1) {
+        return Err(failure::bad_input(format!("Cycles {}-{} and {}-{} overlap", pair[0].0, pair[0].1, pair[1].0, pair[1].1)));
+    }
+
+    let MapFile { matrix, frames: map_frames, colors, grid, .. } = serde_json::from_slice(&mapped::map_file(input)?)?;
+    grid.require_square()?;
+    let (Some(matrix), None) = (matrix, map_frames) else {
+        return Err(failure::bad_input("Palette cycles take a map with a single matrix"));
+    };
+    let count = match frames {
+        Some(frames) => frames as u64,
+        None => {
+            let count = cycles.iter().fold(1, |count, cycle| (count / gcd(count, cycle.period())).saturating_mul(cycle.period()));
+            if count > MAX_FRAMES as u64 {
+                return Err(failure::bad_input(format!("The cycles take {} frames to line up again; pass --frames to cut the loop short", count)));
+            }
+            count
+        }
+    };
+
+    let delay = (1000.0 / fps).round() as u32;
+    let mut missing = BTreeSet::new();
+    let mut images = Vec::new();
+    for frame in 0..count {
+        let mut shown: HashMap<u32, String> = colors.clone();
+        for cycle in cycles {
+            for id in cycle.first.min(cycle.last)..=cycle.first.max(cycle.last) {
+                let source = cycle.source(id, frame);
+                let hex = colors.get(&source).cloned().unwrap_or_else(|| {
+                    missing.insert(source);
+                    "#00000000".to_string()
+                });
+                shown.insert(id, hex);
+            }
+        }
+        let image = pixel::render(&matrix, &shown, |id| {
+            missing.insert(id);
+        })
+        .map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+        images.push((delay, style.apply(image)?));
+    }
+    for id in missing {
+        warn!(id, "color ID not found in map");
+    }
+    animation::write(output, &images).map_err(failure::write)?;
+    Ok(())
+}
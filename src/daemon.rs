@@ -0,0 +1,188 @@
+use image::RgbaImage;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::adjust::Adjustments;
+use crate::cache::ResultCache;
+use crate::depth::Depth;
+use crate::edges::Blocks;
+use crate::failure;
+use crate::style::StyleOptions;
+use crate::{Output, manifest_key, mapped, preview, reconstruct_image};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Failures of the request itself, with the `--error-format json` object as `data`
+const REQUEST_FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+struct MapParams {
+    input: PathBuf,
+    #[serde(default = "one")]
+    block_size: u32,
+    #[serde(default)]
+    tolerance: f64,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+struct ReconstructParams {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct PreviewParams {
+    input: PathBuf,
+    #[serde(default)]
+    width: Option<u32>,
+}
+
+/// Decoded images by path, dropped when the file's modification time changes.
+#[derive(Default)]
+struct ImageCache {
+    images: HashMap<PathBuf, (SystemTime, Arc<RgbaImage>)>,
+}
+
+impl ImageCache {
+    fn get(&mut self, path: &Path, decode: impl FnOnce(&Path) -> Result<RgbaImage, Box<dyn std::error::Error>>) -> Result<Arc<RgbaImage>, Box<dyn std::error::Error>> {
+        let modified = std::fs::metadata(path)?.modified()?;
+        if let Some((cached, image)) = self.images.get(path)
+            && *cached == modified
+        {
+            return Ok(Arc::clone(image));
+        }
+        let image = Arc::new(decode(path)?);
+        self.images.insert(path.to_path_buf(), (modified, Arc::clone(&image)));
+        Ok(image)
+    }
+}
+
+enum Failed {
+    Rpc(i64, String),
+    Request(Box<dyn std::error::Error>),
+}
+
+impl From<Box<dyn std::error::Error>> for Failed {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        Failed::Request(error)
+    }
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, Failed> {
+    serde_json::from_value(params).map_err(|e| Failed::Rpc(INVALID_PARAMS, e.to_string()))
+}
+
+fn map(params: MapParams, cache: &mut ImageCache, results: Option<&ResultCache>) -> Result<Value, Box<dyn std::error::Error>> {
+    if params.block_size == 0 {
+        return Err(failure::bad_input("Block size must be greater than 0"));
+    }
+    let key = manifest_key(Blocks::plain(params.block_size), params.tolerance, Depth::Eight, None, &Adjustments::default(), None);
+    let input = results.map(|_| mapped::map_file(&params.input)).transpose()?;
+    if let (Some(results), Some(input)) = (results, &input)
+        && let Some(json) = results.get("map", input, &key)
+        && let Ok(map) = serde_json::from_slice(&json)
+    {
+        return Ok(map);
+    }
+
+    let image = cache.get(&params.input, |path| Ok(mapped::open_image(path, true)?.to_rgba8()))?;
+    let mut mapper = pixel::ColorMapper::new(params.tolerance);
+    let matrix = pixel::map_image(&image, params.block_size, &mut mapper);
+    let json = serde_json::to_vec(&Output { matrix, colors: mapper.id_to_color })?;
+    if let (Some(results), Some(input)) = (results, &input) {
+        results.put("map", input, &key, &json).map_err(failure::write)?;
+    }
+    Ok(serde_json::from_slice(&json)?)
+}
+
+// Results are cached by the output's extension, which picks the format
+fn reconstruct(params: ReconstructParams, results: Option<&ResultCache>) -> Result<Value, Box<dyn std::error::Error>> {
+    let key = params.output.extension().unwrap_or_default().to_string_lossy().to_ascii_lowercase();
+    let input = results.map(|_| mapped::map_file(&params.input)).transpose()?;
+    if let (Some(results), Some(input)) = (results, &input)
+        && let Some(image) = results.get("reconstruct", input, &key)
+    {
+        std::fs::write(&params.output, image).map_err(failure::write)?;
+        return Ok(json!({ "output": params.output }));
+    }
+
+    reconstruct_image(&params.input, &params.output, None, None, &StyleOptions::default(), true)?;
+    if let (Some(results), Some(input)) = (results, &input) {
+        results.put("reconstruct", input, &key, &std::fs::read(&params.output)?).map_err(failure::write)?;
+    }
+    Ok(json!({ "output": params.output }))
+}
+
+fn call(method: &str, raw: Value, cache: &mut ImageCache, results: Option<&ResultCache>) -> Result<Value, Failed> {
+    match method {
+        "map" => Ok(map(params(raw)?, cache, results)?),
+        "reconstruct" => Ok(reconstruct(params(raw)?, results)?),
+        "preview" => {
+            let params: PreviewParams = params(raw)?;
+            if params.width == Some(0) {
+                return Err(failure::bad_input("Width must be greater than 0").into());
+            }
+            let image = cache.get(&params.input, preview::load)?;
+            let mut text = Vec::new();
+            preview::write_ansi(&preview::fit((*image).clone(), params.width.unwrap_or(80)), &mut text).map_err(|e| Failed::Request(e.into()))?;
+            Ok(json!({ "text": String::from_utf8_lossy(&text) }))
+        }
+        _ => Err(Failed::Rpc(METHOD_NOT_FOUND, format!("No method {}", method))),
+    }
+}
+
+fn respond(line: &str, cache: &mut ImageCache, results: Option<&ResultCache>) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Some(json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": e.to_string() } })),
+    };
+    // Notifications (no id) get no response
+    let id = request.get("id").cloned();
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some(method) => call(method, request.get("params").cloned().unwrap_or(Value::Null), cache, results),
+        None => Err(Failed::Rpc(INVALID_REQUEST, "Request has no method".to_string())),
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(Failed::Rpc(code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        Err(Failed::Request(e)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": REQUEST_FAILED, "message": e.to_string(), "data": failure::to_json(e.as_ref()) } }),
+    })
+}
+
+/// Answer JSON-RPC 2.0 requests, one per line on stdin, with one response per line on stdout
+/// until stdin closes. Methods:
+///
+/// - `map {input, block_size = 1, tolerance = 0}` returns the map `{matrix, colors}`
+/// - `reconstruct {input, output}` renders a map file to an image file
+/// - `preview {input, width = 80}` returns `{text}`, the map or image in ANSI colors
+///
+/// Decoded images are kept between requests until their file changes. With `cache_dir`, map
+/// and reconstruct results are also kept there by input content and options, across runs.
+pub fn run(cache_dir: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cache = ImageCache::default();
+    let results = cache_dir.map(ResultCache::new);
+    let mut out = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(&line, &mut cache, results.as_ref()) {
+            writeln!(out, "{}", response).and_then(|()| out.flush()).map_err(failure::write)?;
+        }
+    }
+    Ok(())
+}
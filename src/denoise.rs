@@ -0,0 +1,127 @@
+use image::{ImageBuffer, Pixel, Rgba, RgbaImage};
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use crate::stream::RowSource;
+
+// Widest median window; larger ones wipe out detail the blocks would keep anyway
+const MAX_SIZE: u32 = 15;
+
+/// Noise filter run on the input before it is mapped, from `median:SIZE`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Denoise {
+    /// Median of each channel over a SIZE x SIZE window, with SIZE odd
+    Median(u32),
+}
+
+impl FromStr for Denoise {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = s.strip_prefix("median:").ok_or_else(|| format!("Expected median:SIZE, got {}", s))?;
+        match size.parse() {
+            Ok(size) if (3..=MAX_SIZE).contains(&size) && size % 2 == 1 => Ok(Denoise::Median(size)),
+            _ => Err(format!("Median size must be odd, from 3 to {}; got {}", MAX_SIZE, size)),
+        }
+    }
+}
+
+impl Denoise {
+    /// The filter as `median:SIZE`, for manifest keys.
+    pub fn key(&self) -> String {
+        match self {
+            Denoise::Median(size) => format!("median:{}", size),
+        }
+    }
+
+    fn radius(&self) -> usize {
+        match *self {
+            Denoise::Median(size) => size as usize / 2,
+        }
+    }
+
+    /// Filter `image`, an RGBA image of any channel type; alpha is filtered too, so specks of
+    /// stray transparency go as well.
+    fn buffer<T: Copy + Ord>(&self, image: &ImageBuffer<Rgba<T>, Vec<T>>) -> ImageBuffer<Rgba<T>, Vec<T>>
+    where
+        Rgba<T>: Pixel<Subpixel = T>,
+    {
+        let (width, height) = image.dimensions();
+        let row_len = width as usize * 4;
+        let radius = self.radius();
+        let mut out = ImageBuffer::new(width, height);
+        for (y, dst) in out.chunks_exact_mut(row_len).enumerate() {
+            let rows: Vec<&[T]> = (y.saturating_sub(radius)..(y + radius + 1).min(height as usize)).map(|i| &image.as_raw()[i * row_len..(i + 1) * row_len]).collect();
+            filter_row(&rows, radius, dst);
+        }
+        out
+    }
+
+    pub fn image(&self, image: &RgbaImage) -> RgbaImage {
+        self.buffer(image)
+    }
+
+    /// [`Denoise::image`] for 16-bit images.
+    pub fn image16(&self, image: &ImageBuffer<Rgba<u16>, Vec<u16>>) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+        self.buffer(image)
+    }
+}
+
+// Median of each channel of each pixel over `rows`, the rows around it that are in the image,
+// and the `radius` pixels to each side that are in the image, into `out`
+fn filter_row<T: Copy + Ord>(rows: &[&[T]], radius: usize, out: &mut [T]) {
+    let width = out.len() / 4;
+    let mut window = Vec::with_capacity(rows.len() * (2 * radius + 1));
+    for x in 0..width {
+        let columns = x.saturating_sub(radius)..(x + radius + 1).min(width);
+        for c in 0..4 {
+            window.clear();
+            for row in rows {
+                window.extend(columns.clone().map(|column| row[column * 4 + c]));
+            }
+            let middle = window.len() / 2;
+            out[x * 4 + c] = *window.select_nth_unstable(middle).1;
+        }
+    }
+}
+
+/// Rows of `source` put through a [`Denoise`] filter as they're read, keeping only the rows
+/// the filter window needs.
+pub struct Rows {
+    source: Box<dyn RowSource>,
+    radius: usize,
+    // Source rows from `first` on, as far as they've been read
+    window: VecDeque<Vec<u8>>,
+    first: usize,
+    y: usize,
+}
+
+impl Rows {
+    pub fn new(source: Box<dyn RowSource>, denoise: Denoise) -> Self {
+        Rows { source, radius: denoise.radius(), window: VecDeque::new(), first: 0, y: 0 }
+    }
+}
+
+impl RowSource for Rows {
+    fn dimensions(&self) -> (u32, u32) {
+        self.source.dimensions()
+    }
+
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let height = self.source.dimensions().1 as usize;
+        let last = (self.y + self.radius).min(height - 1);
+        while self.first + self.window.len() <= last {
+            let mut row = vec![0; buf.len()];
+            self.source.read_row(&mut row)?;
+            self.window.push_back(row);
+        }
+        while self.first < self.y.saturating_sub(self.radius) {
+            self.window.pop_front();
+            self.first += 1;
+        }
+        let rows: Vec<&[u8]> = self.window.iter().map(Vec::as_slice).collect();
+        filter_row(&rows, self.radius, buf);
+        self.y += 1;
+        Ok(())
+    }
+}
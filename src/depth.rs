@@ -0,0 +1,91 @@
+use clap::ValueEnum;
+use image::{ImageBuffer, Rgba};
+use std::collections::HashMap;
+
+use crate::edges::Edges;
+
+/// Bits per channel that blocks are averaged and colors written with.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Depth {
+    /// `#RRGGBBAA` colors; 16-bit inputs are reduced to 8 bits as they're decoded
+    #[default]
+    #[value(name = "8")]
+    Eight,
+    /// `#RRRRGGGGBBBBAAAA` colors, averaged and merged at 16 bits
+    #[value(name = "16")]
+    Sixteen,
+}
+
+// Tolerances are on the 8-bit scale, where one step is 257 16-bit steps
+const STEP: f64 = 257.0;
+
+/// Assigns color IDs to 16-bit colors the way [`pixel::ColorMapper`] does to 8-bit ones: ID 0
+/// is transparent, and a new color takes the ID of the first one within tolerance.
+struct Mapper {
+    tolerance_sq: f64,
+    ids: HashMap<[u16; 4], u32>,
+    palette: Vec<(u32, [u16; 4])>,
+    colors: HashMap<u32, String>,
+}
+
+impl Mapper {
+    fn new(tolerance: f64) -> Self {
+        let transparent = [0; 4];
+        Mapper {
+            tolerance_sq: (tolerance * STEP).powi(2),
+            ids: HashMap::from([(transparent, 0)]),
+            palette: Vec::new(),
+            colors: HashMap::from([(0, pixel::rgba16_to_hex(&Rgba(transparent)))]),
+        }
+    }
+
+    fn id_for(&mut self, color: [u16; 4]) -> u32 {
+        if let Some(&id) = self.ids.get(&color) {
+            return id;
+        }
+        let near = |known: &[u16; 4]| known.iter().zip(&color).map(|(&a, &b)| (a as f64 - b as f64).powi(2)).sum::<f64>() <= self.tolerance_sq;
+        let found = if self.tolerance_sq > 0.0 && color[3] > 0 { self.palette.iter().find(|(_, known)| near(known)).map(|&(id, _)| id) } else { None };
+        let id = found.unwrap_or_else(|| {
+            let id = self.palette.len() as u32 + 1;
+            self.palette.push((id, color));
+            self.colors.insert(id, pixel::rgba16_to_hex(&Rgba(color)));
+            id
+        });
+        self.ids.insert(color, id);
+        id
+    }
+}
+
+// Average color of a block, like `pixel::block_color` at 16 bits
+fn block_color(sum: [u64; 4], count: u64) -> [u16; 4] {
+    let average = sum.map(|c| (c / count) as u16);
+    if average[3] == 0 { [0; 4] } else { average }
+}
+
+/// Average `image` over the blocks `edges` lays out and assign each a color ID within
+/// `tolerance`, keeping 16 bits per channel; returns the matrix and the colors by ID.
+pub fn map_image(image: &ImageBuffer<Rgba<u16>, Vec<u16>>, block_size: u32, edges: Edges, tolerance: f64) -> (Vec<Vec<u32>>, HashMap<u32, String>) {
+    let (x_spans, y_spans) = (edges.spans(image.width(), block_size), edges.spans(image.height(), block_size));
+    let mut mapper = Mapper::new(tolerance);
+    let mut matrix = Vec::with_capacity(y_spans.len());
+    for y_span in &y_spans {
+        let mut row = Vec::with_capacity(x_spans.len());
+        for x_span in &x_spans {
+            let mut sum = [0u64; 4];
+            for y in y_span.start..y_span.end {
+                // The last row and column of a padded block also stand in for those it's short of
+                let row_weight = if y + 1 == y_span.end { 1 + y_span.pad as u64 } else { 1 };
+                for x in x_span.start..x_span.end {
+                    let weight = row_weight * if x + 1 == x_span.end { 1 + x_span.pad as u64 } else { 1 };
+                    for (total, &c) in sum.iter_mut().zip(&image.get_pixel(x, y).0) {
+                        *total += c as u64 * weight;
+                    }
+                }
+            }
+            let count = (x_span.end - x_span.start + x_span.pad) as u64 * (y_span.end - y_span.start + y_span.pad) as u64;
+            row.push(mapper.id_for(block_color(sum, count)));
+        }
+        matrix.push(row);
+    }
+    (matrix, mapper.colors)
+}
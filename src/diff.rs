@@ -0,0 +1,55 @@
+use image::{Rgba, RgbaImage};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::{failure, preview, rgba_to_hex};
+
+// Columns between the two maps in the preview
+const GAP: u32 = 2;
+
+// Unchanged cells are drawn at this fraction of their brightness so changes stand out
+fn dim(pixel: Rgba<u8>) -> Rgba<u8> {
+    let [r, g, b, a] = pixel.0;
+    Rgba([r / 3, g / 3, b / 3, a])
+}
+
+/// Compare the maps (or images) `before` and `after` cell by cell by color, so maps with
+/// different IDs compare equal when they look the same. Prints every changed cell, or with
+/// `preview` both maps side by side with the unchanged cells dimmed.
+pub fn run(before: &Path, after: &Path, preview: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (old, new) = (preview::load(before)?, preview::load(after)?);
+    let (width, height) = (old.width().max(new.width()), old.height().max(new.height()));
+    let blank = Rgba([0, 0, 0, 0]);
+    let at = |image: &RgbaImage, x: u32, y: u32| if x < image.width() && y < image.height() { *image.get_pixel(x, y) } else { blank };
+    // Fully transparent cells are equal whatever their RGB
+    let visible = |pixel: Rgba<u8>| if pixel[3] == 0 { blank } else { pixel };
+
+    let mut changes = Vec::new();
+    let mut side_by_side = RgbaImage::new(width * 2 + GAP, height);
+    for y in 0..height {
+        for x in 0..width {
+            let (a, b) = (visible(at(&old, x, y)), visible(at(&new, x, y)));
+            let changed = a != b;
+            if changed {
+                changes.push((x, y, a, b));
+            }
+            side_by_side.put_pixel(x, y, if changed { a } else { dim(a) });
+            side_by_side.put_pixel(width + GAP + x, y, if changed { b } else { dim(b) });
+        }
+    }
+
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let write = || -> std::io::Result<()> {
+        writeln!(out, "{}x{} -> {}x{}: {} of {} cells changed", old.width(), old.height(), new.width(), new.height(), changes.len(), width * height)?;
+        if preview {
+            preview::write_ansi(&preview::fit(side_by_side, preview::terminal_columns()), &mut out)?;
+        } else {
+            for (x, y, a, b) in &changes {
+                writeln!(out, "{},{}: {} -> {}", x, y, rgba_to_hex(a), rgba_to_hex(b))?;
+            }
+        }
+        out.flush()
+    };
+    write().map_err(failure::write)?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+/// DMC six-strand embroidery floss: number, name and approximate color as `0xRRGGBB`.
+pub const FLOSS: &[(&str, &str, u32)] = &[
+    ("B5200", "Snow White", 0xffffff),
+    ("White", "White", 0xfcfbf8),
+    ("Ecru", "Ecru", 0xf0eada),
+    ("208", "Very Dark Lavender", 0x835b8b),
+    ("209", "Dark Lavender", 0xa37ba7),
+    ("210", "Medium Lavender", 0xc39fc3),
+    ("211", "Light Lavender", 0xe3cbe3),
+    ("304", "Medium Red", 0xb71f33),
+    ("307", "Lemon", 0xfded54),
+    ("309", "Dark Rose", 0xba4a4a),
+    ("310", "Black", 0x000000),
+    ("317", "Pewter Gray", 0x6c6c6c),
+    ("318", "Light Steel Gray", 0xababab),
+    ("319", "Very Dark Pistachio Green", 0x205f2e),
+    ("321", "Red", 0xc72b3b),
+    ("326", "Very Dark Rose", 0xb33b4b),
+    ("333", "Very Dark Blue Violet", 0x5c5478),
+    ("335", "Rose", 0xee546e),
+    ("340", "Medium Blue Violet", 0xada7c7),
+    ("341", "Light Blue Violet", 0xb7bfdd),
+    ("349", "Dark Coral", 0xd21035),
+    ("350", "Medium Coral", 0xe04848),
+    ("351", "Coral", 0xe96a67),
+    ("352", "Light Coral", 0xfd9c97),
+    ("353", "Peach", 0xfed7cc),
+    ("355", "Dark Terra Cotta", 0x984436),
+    ("356", "Medium Terra Cotta", 0xc56a5b),
+    ("367", "Dark Pistachio Green", 0x617a52),
+    ("368", "Light Pistachio Green", 0xa6c298),
+    ("369", "Very Light Pistachio Green", 0xd7edcc),
+    ("413", "Dark Pewter Gray", 0x565656),
+    ("414", "Dark Steel Gray", 0x8c8c8c),
+    ("415", "Pearl Gray", 0xd3d3d6),
+    ("433", "Medium Brown", 0x7a451f),
+    ("434", "Light Brown", 0x985e33),
+    ("435", "Very Light Brown", 0xb87748),
+    ("436", "Tan", 0xcb9051),
+    ("437", "Light Tan", 0xe4bb8e),
+    ("444", "Dark Lemon", 0xffd600),
+    ("469", "Avocado Green", 0x72843c),
+    ("470", "Light Avocado Green", 0x94ab4f),
+    ("471", "Very Light Avocado Green", 0xaebf79),
+    ("472", "Ultra Light Avocado Green", 0xd8e498),
+    ("498", "Dark Red", 0xa7132b),
+    ("550", "Very Dark Violet", 0x5c184e),
+    ("552", "Medium Violet", 0x803a6b),
+    ("553", "Violet", 0xa3638b),
+    ("554", "Light Violet", 0xdbb3cb),
+    ("600", "Very Dark Cranberry", 0xcd2f63),
+    ("601", "Dark Cranberry", 0xd1286a),
+    ("602", "Medium Cranberry", 0xe24874),
+    ("603", "Cranberry", 0xffa4be),
+    ("604", "Light Cranberry", 0xffb0be),
+    ("605", "Very Light Cranberry", 0xffc0cd),
+    ("666", "Bright Red", 0xe31d42),
+    ("699", "Green", 0x056517),
+    ("700", "Bright Green", 0x07731b),
+    ("701", "Light Green", 0x3f8f29),
+    ("702", "Kelly Green", 0x47a72f),
+    ("703", "Chartreuse", 0x7bb547),
+    ("704", "Bright Chartreuse", 0x9ecf34),
+    ("712", "Cream", 0xfffbef),
+    ("718", "Plum", 0x9c2462),
+    ("725", "Medium Light Topaz", 0xffc840),
+    ("726", "Light Topaz", 0xfdd755),
+    ("727", "Very Light Topaz", 0xfff1af),
+    ("738", "Very Light Tan", 0xeccc9e),
+    ("739", "Ultra Very Light Tan", 0xf8e4c8),
+    ("740", "Tangerine", 0xff8b00),
+    ("741", "Medium Tangerine", 0xffa32b),
+    ("742", "Light Tangerine", 0xffbf57),
+    ("743", "Medium Yellow", 0xfed376),
+    ("745", "Light Pale Yellow", 0xffe9ad),
+    ("754", "Light Peach", 0xf7cbbf),
+    ("758", "Very Light Terra Cotta", 0xeeaa9b),
+    ("762", "Very Light Pearl Gray", 0xececec),
+    ("780", "Ultra Very Dark Topaz", 0x94631a),
+    ("781", "Very Dark Topaz", 0xa26d20),
+    ("782", "Dark Topaz", 0xae7720),
+    ("783", "Medium Topaz", 0xce9124),
+    ("796", "Dark Royal Blue", 0x11416d),
+    ("797", "Royal Blue", 0x13477d),
+    ("798", "Dark Delft Blue", 0x466a8e),
+    ("799", "Medium Delft Blue", 0x748eb6),
+    ("800", "Pale Delft Blue", 0xc0ccde),
+    ("801", "Dark Coffee Brown", 0x653919),
+    ("809", "Delft Blue", 0x94a8c6),
+    ("813", "Light Blue", 0xa1c2d7),
+    ("814", "Dark Garnet", 0x7b001b),
+    ("815", "Medium Garnet", 0x87071f),
+    ("816", "Garnet", 0x970b23),
+    ("817", "Very Dark Coral Red", 0xbb051f),
+    ("820", "Very Dark Royal Blue", 0x0e365c),
+    ("825", "Dark Blue", 0x4781a5),
+    ("826", "Medium Blue", 0x6b9ebf),
+    ("827", "Very Light Blue", 0xbddded),
+    ("828", "Ultra Very Light Blue", 0xc5e8ed),
+    ("890", "Ultra Dark Pistachio Green", 0x174923),
+    ("898", "Very Dark Coffee Brown", 0x492a13),
+    ("899", "Medium Rose", 0xf27688),
+    ("900", "Dark Burnt Orange", 0xd15807),
+    ("909", "Very Dark Emerald Green", 0x156f49),
+    ("910", "Dark Emerald Green", 0x187e56),
+    ("911", "Medium Emerald Green", 0x189065),
+    ("912", "Light Emerald Green", 0x1b9d6b),
+    ("913", "Medium Nile Green", 0x6dab77),
+    ("915", "Dark Plum", 0x820043),
+    ("917", "Medium Plum", 0x9b1359),
+    ("930", "Dark Antique Blue", 0x455c71),
+    ("931", "Medium Antique Blue", 0x6a859e),
+    ("932", "Light Antique Blue", 0xa2b5c6),
+    ("938", "Ultra Dark Coffee Brown", 0x361f0e),
+    ("945", "Tawny", 0xfbd5bb),
+    ("946", "Medium Burnt Orange", 0xeb6307),
+    ("947", "Burnt Orange", 0xff7b4d),
+    ("951", "Light Tawny", 0xffe2cf),
+    ("954", "Nile Green", 0x88ba91),
+    ("955", "Light Nile Green", 0xa2d6ad),
+    ("970", "Light Pumpkin", 0xf78b13),
+    ("971", "Pumpkin", 0xf67f00),
+    ("972", "Deep Canary", 0xffb515),
+    ("973", "Bright Canary", 0xffe300),
+    ("986", "Very Dark Forest Green", 0x405230),
+    ("987", "Dark Forest Green", 0x587141),
+    ("988", "Medium Forest Green", 0x738b5b),
+    ("989", "Forest Green", 0x8da675),
+    ("995", "Dark Electric Blue", 0x2696b6),
+    ("996", "Medium Electric Blue", 0x30c2ec),
+    ("3078", "Very Light Golden Yellow", 0xfdf9cd),
+    ("3326", "Light Rose", 0xfbadb4),
+    ("3347", "Medium Yellow Green", 0x71823c),
+    ("3348", "Light Yellow Green", 0xccd9b1),
+    ("3371", "Black Brown", 0x1e1108),
+    ("3607", "Light Plum", 0xc54982),
+    ("3608", "Very Light Plum", 0xea9cc4),
+    ("3609", "Ultra Light Plum", 0xf4aed5),
+    ("3705", "Dark Melon", 0xff7992),
+    ("3706", "Medium Melon", 0xffadbc),
+    ("3708", "Light Melon", 0xffcbd5),
+    ("3750", "Very Dark Antique Blue", 0x384c5e),
+    ("3770", "Very Light Tawny", 0xffeee3),
+    ("3778", "Light Terra Cotta", 0xd98978),
+    ("3799", "Very Dark Pewter Gray", 0x424242),
+    ("3830", "Terra Cotta", 0xb95544),
+    ("3837", "Ultra Dark Lavender", 0x6c3a6e),
+    ("3843", "Electric Blue", 0x14aad0),
+    ("3844", "Dark Bright Turquoise", 0x12aeba),
+    ("3845", "Medium Bright Turquoise", 0x04c4ca),
+    ("3846", "Light Bright Turquoise", 0x06e3e6),
+    ("3865", "Winter White", 0xf9f7f1),
+];
@@ -0,0 +1,86 @@
+use image::{ImageFormat, ImageReader};
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// What mapping an input would produce, worked out from its header alone.
+pub struct Report {
+    pub width: u32,
+    pub height: u32,
+    pub columns: u64,
+    pub rows: u64,
+    /// Upper bound on palette entries, including the reserved transparent ID
+    pub max_colors: u64,
+    pub estimated_bytes: u64,
+}
+
+impl Report {
+    pub fn cells(&self) -> u64 {
+        self.columns * self.rows
+    }
+}
+
+// Indexed formats can't produce more colors than their palette holds
+fn palette_limit(path: &Path, format: Option<ImageFormat>) -> Option<u64> {
+    match format? {
+        ImageFormat::Gif => Some(256),
+        ImageFormat::Png => {
+            let reader = png::Decoder::new(BufReader::new(File::open(path).ok()?)).read_info().ok()?;
+            let info = reader.info();
+            (info.color_type == png::ColorType::Indexed).then(|| info.palette.as_ref().map_or(256, |p| p.len() as u64 / 3))
+        }
+        _ => None,
+    }
+}
+
+fn digits(n: u64) -> u64 {
+    n.checked_ilog10().unwrap_or(0) as u64 + 1
+}
+
+/// Read the header of `input` and estimate the result of mapping it with `block_size`.
+pub fn report(input: &Path, block_size: u32) -> Result<Report, Box<dyn std::error::Error>> {
+    let reader = ImageReader::open(input)?.with_guessed_format()?;
+    let format = reader.format();
+    let (width, height) = reader.into_dimensions()?;
+
+    let columns = width.div_ceil(block_size) as u64;
+    let rows = height.div_ceil(block_size) as u64;
+    let cells = columns * rows;
+    let max_colors = palette_limit(input, format).unwrap_or(u64::MAX).min(cells) + 1;
+
+    // Mirrors write_json: `    [1,2,3],\n` per row, `  "12": "#rrggbbaa",\n` per color
+    let id_digits = digits(max_colors - 1);
+    let matrix_bytes = rows * 7 + cells * (id_digits + 1);
+    let colors_bytes = max_colors * (id_digits + 19);
+    let estimated_bytes = 32 + matrix_bytes + colors_bytes;
+
+    Ok(Report { width, height, columns, rows, max_colors, estimated_bytes })
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", value, UNITS[unit]) }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} -> {}x{} matrix ({} cells), up to {} colors, ~{} of JSON",
+            self.width,
+            self.height,
+            self.columns,
+            self.rows,
+            self.cells(),
+            self.max_colors,
+            format_bytes(self.estimated_bytes)
+        )
+    }
+}
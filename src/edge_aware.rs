@@ -0,0 +1,61 @@
+use image::Rgba;
+use pixel::color_distance_sq;
+
+use crate::edges::Span;
+
+// Squared RGBA distance between the two sides of a block above which they count as split by
+// a strong edge
+const EDGE_SQ: u32 = 96 * 96;
+
+// Rounds of two-means to settle which side of the edge each pixel is on
+const ROUNDS: usize = 4;
+
+// Average of pixels and how many each stands for, or `None` without any
+fn mean(pixels: impl Iterator<Item = (Rgba<u8>, u64)>) -> Option<Rgba<u8>> {
+    let mut sum = [0u64; 4];
+    let mut count = 0;
+    for (pixel, weight) in pixels {
+        for c in 0..4 {
+            sum[c] += pixel[c] as u64 * weight;
+        }
+        count += weight;
+    }
+    (count > 0).then(|| pixel::block_color(sum, count))
+}
+
+/// Color of the block at `x_span` of `strip`, the rows of `y_span` `width` pixels wide. A block
+/// split by a strong edge takes the average of its larger side only, so silhouettes stay crisp
+/// instead of blending into halo colors; other blocks are averaged as usual.
+pub fn block_color(strip: &[u8], width: u32, x_span: &Span, y_span: &Span) -> Rgba<u8> {
+    let rows = y_span.end - y_span.start;
+    let pixels = || {
+        (0..rows).flat_map(|row| (x_span.start..x_span.end).map(move |x| (row, x))).map(|(row, x)| {
+            let i = (row as usize * width as usize + x as usize) * 4;
+            let weight = if row + 1 == rows { 1 + y_span.pad as u64 } else { 1 } * if x + 1 == x_span.end { 1 + x_span.pad as u64 } else { 1 };
+            (Rgba([strip[i], strip[i + 1], strip[i + 2], strip[i + 3]]), weight)
+        })
+    };
+    let average = mean(pixels()).unwrap_or(Rgba([0; 4]));
+
+    // Seed the sides with the pixel furthest from the average and the one furthest from that
+    let furthest = |from: Rgba<u8>| pixels().map(|(pixel, _)| pixel).max_by_key(|pixel| color_distance_sq(pixel, &from)).unwrap_or(from);
+    let mut a = furthest(average);
+    let mut b = furthest(a);
+    if color_distance_sq(&a, &b) <= EDGE_SQ {
+        return average;
+    }
+    let mut near_a = Vec::new();
+    for _ in 0..ROUNDS {
+        near_a = pixels().map(|(pixel, _)| color_distance_sq(&pixel, &a) <= color_distance_sq(&pixel, &b)).collect();
+        let side = |on_a: bool| mean(pixels().zip(&near_a).filter(|&(_, &near)| near == on_a).map(|(pixel, _)| pixel));
+        match (side(true), side(false)) {
+            (Some(side_a), Some(side_b)) => (a, b) = (side_a, side_b),
+            _ => return average,
+        }
+    }
+    if color_distance_sq(&a, &b) <= EDGE_SQ {
+        return average;
+    }
+    let (weight_a, weight_b) = pixels().zip(&near_a).fold((0, 0), |(on_a, on_b), ((_, weight), &near)| if near { (on_a + weight, on_b) } else { (on_a, on_b + weight) });
+    if weight_a >= weight_b { a } else { b }
+}
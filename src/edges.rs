@@ -0,0 +1,78 @@
+use clap::ValueEnum;
+
+use crate::grid::Grid;
+
+/// What to do with the blocks at the edges when the image isn't a multiple of the block size.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Edges {
+    /// Average the partial blocks on the right and bottom over the pixels they have
+    #[default]
+    Average,
+    /// Fold the leftover pixels into the last full blocks, which grow to cover them
+    Extend,
+    /// Fill the partial blocks up to full size with copies of the edge pixels
+    Pad,
+    /// Center the grid, splitting the leftover pixels between partial blocks on both sides
+    Center,
+}
+
+/// How an image is cut into blocks, and how each block is averaged.
+#[derive(Clone, Copy, Debug)]
+pub struct Blocks {
+    pub size: u32,
+    pub edges: Edges,
+    /// Average blocks split by a strong edge over their larger side only
+    pub edge_aware: bool,
+    pub grid: Grid,
+}
+
+impl Blocks {
+    /// Blocks of `size` averaged over all their pixels, partial ones at the edges included.
+    pub fn plain(size: u32) -> Self {
+        Blocks { size, edges: Edges::Average, edge_aware: false, grid: Grid::Square }
+    }
+}
+
+/// Pixels `start..end` of a block along one axis, and how many copies of its last pixel fill it
+/// up to the block size.
+pub struct Span {
+    pub start: u32,
+    pub end: u32,
+    pub pad: u32,
+}
+
+impl Edges {
+    pub fn name(self) -> &'static str {
+        match self {
+            Edges::Average => "average",
+            Edges::Extend => "extend",
+            Edges::Pad => "pad",
+            Edges::Center => "center",
+        }
+    }
+
+    /// The blocks along an axis of `size` pixels, in order.
+    pub fn spans(self, size: u32, block_size: u32) -> Vec<Span> {
+        let span = |start: u32, end: u32| Span { start, end, pad: 0 };
+        let grid = |offset: u32| {
+            let mut spans: Vec<Span> = (offset > 0).then(|| span(0, offset)).into_iter().collect();
+            spans.extend((offset..size).step_by(block_size as usize).map(|start| span(start, start.saturating_add(block_size).min(size))));
+            spans
+        };
+        match self {
+            Edges::Average => grid(0),
+            Edges::Extend => {
+                let mut spans = grid(0);
+                if spans.len() > 1 && !size.is_multiple_of(block_size) {
+                    let last = spans.pop().expect("more than one span");
+                    spans.last_mut().expect("more than one span").end = last.end;
+                }
+                spans
+            }
+            Edges::Pad => grid(0).into_iter().map(|span| Span { pad: block_size - (span.end - span.start), ..span }).collect(),
+            // A single block stays whole rather than being split in two
+            Edges::Center if size > block_size => grid(size % block_size / 2),
+            Edges::Center => grid(0),
+        }
+    }
+}
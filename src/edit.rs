@@ -0,0 +1,268 @@
+use image::Rgba;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::failure::{self, Kind};
+use crate::matrix::Matrix;
+use crate::{MapFile, hex_to_rgba, mapped, write_json};
+
+const HELP: &str = "arrows/hjkl move  space paint  f fill  [ ] color  i pick  u undo  U redo  s save  q quit";
+
+/// Cells changed by one edit, with the IDs they had before it.
+type Edit = Vec<(usize, usize, u32)>;
+
+struct Editor {
+    matrix: Vec<Vec<u32>>,
+    colors: HashMap<u32, String>,
+    /// Color IDs in order, with their colors
+    palette: Vec<(u32, Rgba<u8>)>,
+    cursor: (usize, usize),
+    selected: usize,
+    scroll: (usize, usize),
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    dirty: bool,
+    /// Asked to quit with unsaved changes; a second quit goes through
+    confirm_quit: bool,
+    message: String,
+}
+
+fn color(rgba: Rgba<u8>) -> Color {
+    Color::Rgb(rgba[0], rgba[1], rgba[2])
+}
+
+impl Editor {
+    fn width(&self) -> usize {
+        self.matrix.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    fn id_at(&self, (x, y): (usize, usize)) -> u32 {
+        self.matrix.get(y).and_then(|row| row.get(x)).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, (x, y): (usize, usize), id: u32) -> u32 {
+        let row = &mut self.matrix[y];
+        if row.len() <= x {
+            row.resize(x + 1, 0);
+        }
+        std::mem::replace(&mut row[x], id)
+    }
+
+    /// Set each of `cells` to `id`, recording the edit for undo.
+    fn apply(&mut self, cells: Vec<(usize, usize)>, id: u32) {
+        let edit: Edit = cells.into_iter().map(|(x, y)| (x, y, self.set((x, y), id))).filter(|&(_, _, old)| old != id).collect();
+        if !edit.is_empty() {
+            self.undo.push(edit);
+            self.redo.clear();
+            self.dirty = true;
+        }
+    }
+
+    // Put back the IDs recorded in the last edit of `from`, recording the replaced ones on `to`
+    fn revert(from: &mut Vec<Edit>, to: &mut Vec<Edit>, matrix: &mut [Vec<u32>]) -> bool {
+        let Some(edit) = from.pop() else {
+            return false;
+        };
+        to.push(edit.into_iter().map(|(x, y, id)| (x, y, std::mem::replace(&mut matrix[y][x], id))).collect());
+        true
+    }
+
+    fn undo(&mut self) {
+        if Self::revert(&mut self.undo, &mut self.redo, &mut self.matrix) {
+            self.dirty = true;
+        } else {
+            self.message = "Nothing to undo".to_string();
+        }
+    }
+
+    fn redo(&mut self) {
+        if Self::revert(&mut self.redo, &mut self.undo, &mut self.matrix) {
+            self.dirty = true;
+        } else {
+            self.message = "Nothing to redo".to_string();
+        }
+    }
+
+    fn selected_id(&self) -> u32 {
+        self.palette.get(self.selected).map_or(0, |&(id, _)| id)
+    }
+
+    // Cells connected to the cursor through the same ID
+    fn region(&self) -> Vec<(usize, usize)> {
+        let target = self.id_at(self.cursor);
+        let (width, height) = (self.width(), self.matrix.len());
+        let mut seen = vec![vec![false; width]; height];
+        let mut stack = vec![self.cursor];
+        let mut cells = Vec::new();
+        while let Some((x, y)) = stack.pop() {
+            if x >= width || y >= height || seen[y][x] || self.id_at((x, y)) != target {
+                continue;
+            }
+            seen[y][x] = true;
+            cells.push((x, y));
+            stack.extend([(x + 1, y), (x.wrapping_sub(1), y), (x, y + 1), (x, y.wrapping_sub(1))]);
+        }
+        cells
+    }
+
+    fn save(&mut self, path: &Path) {
+        let matrix = Matrix::Memory(self.matrix.clone());
+        self.message = match mapped::write_file(path, |w| write_json(&matrix, &self.colors, &[], w)) {
+            Ok(()) => {
+                self.dirty = false;
+                format!("Saved {}", path.display())
+            }
+            Err(e) => format!("Save failed: {}", e),
+        };
+    }
+
+    /// Handle a key press; returns whether to quit.
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers, path: &Path) -> bool {
+        let quitting = matches!(code, KeyCode::Char('q') | KeyCode::Esc);
+        if !quitting {
+            self.confirm_quit = false;
+        }
+        self.message.clear();
+        let (x, y) = self.cursor;
+        match code {
+            KeyCode::Left | KeyCode::Char('h') => self.cursor.0 = x.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.cursor.0 = (x + 1).min(self.width().saturating_sub(1)),
+            KeyCode::Up | KeyCode::Char('k') => self.cursor.1 = y.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor.1 = (y + 1).min(self.matrix.len().saturating_sub(1)),
+            KeyCode::Char(' ') | KeyCode::Enter => self.apply(vec![self.cursor], self.selected_id()),
+            KeyCode::Char('f') => self.apply(self.region(), self.selected_id()),
+            KeyCode::Char(']') | KeyCode::Tab => self.selected = (self.selected + 1) % self.palette.len().max(1),
+            KeyCode::Char('[') | KeyCode::BackTab => self.selected = (self.selected + self.palette.len().max(1) - 1) % self.palette.len().max(1),
+            KeyCode::Char('i') => {
+                let id = self.id_at(self.cursor);
+                match self.palette.iter().position(|&(entry, _)| entry == id) {
+                    Some(index) => self.selected = index,
+                    None => self.message = format!("ID {} is not in the palette", id),
+                }
+            }
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char('U') => self.redo(),
+            KeyCode::Char('r') if modifiers.contains(KeyModifiers::CONTROL) => self.redo(),
+            KeyCode::Char('s') => self.save(path),
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if !self.dirty || self.confirm_quit {
+                    return true;
+                }
+                self.confirm_quit = true;
+                self.message = "Unsaved changes; press q again to quit without saving".to_string();
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn draw_map(&mut self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(format!(" {}x{}  ({}, {}) ID {} ", self.width(), self.matrix.len(), self.cursor.0, self.cursor.1, self.id_at(self.cursor)));
+        let inner = block.inner(area);
+        // Two characters per cell keep cells roughly square
+        let (columns, rows) = ((inner.width / 2).max(1) as usize, inner.height.max(1) as usize);
+        let (x, y) = self.cursor;
+        self.scroll.0 = self.scroll.0.clamp(x.saturating_sub(columns - 1), x);
+        self.scroll.1 = self.scroll.1.clamp(y.saturating_sub(rows - 1), y);
+
+        let colors: HashMap<u32, Rgba<u8>> = self.palette.iter().copied().collect();
+        let lines: Vec<Line> = (self.scroll.1..(self.scroll.1 + rows).min(self.matrix.len()))
+            .map(|cy| {
+                let spans: Vec<Span> = (self.scroll.0..(self.scroll.0 + columns).min(self.width()))
+                    .map(|cx| {
+                        let rgba = colors.get(&self.id_at((cx, cy))).copied().filter(|rgba| rgba[3] > 0);
+                        let style = rgba.map_or(Style::new().fg(Color::DarkGray), |rgba| Style::new().bg(color(rgba)));
+                        match ((cx, cy) == self.cursor, rgba) {
+                            (true, _) => Span::styled("[]", style.fg(Color::White).bg(rgba.map_or(Color::Reset, color)).add_modifier(Modifier::BOLD | Modifier::REVERSED)),
+                            (false, Some(_)) => Span::styled("  ", style),
+                            (false, None) => Span::styled("··", style),
+                        }
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_palette(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Palette ");
+        let rows = block.inner(area).height.max(1) as usize;
+        let first = self.selected.saturating_sub(rows - 1);
+        let lines: Vec<Line> = self
+            .palette
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(i, &(id, rgba))| {
+                let marker = if i == self.selected { "> " } else { "  " };
+                Line::from(vec![Span::raw(marker), Span::styled("  ", Style::new().bg(color(rgba))), Span::raw(format!(" {:>4} {}", id, self.colors[&id]))])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [map, palette] = Layout::horizontal([Constraint::Min(10), Constraint::Length(26)]).areas(main);
+        self.draw_map(frame, map);
+        self.draw_palette(frame, palette);
+        let text = if self.message.is_empty() { HELP.to_string() } else { self.message.clone() };
+        let text = if self.dirty { format!("* {}", text) } else { text };
+        frame.render_widget(Paragraph::new(text), status);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && self.key(key.code, key.modifiers, path)
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Open the map in `input` in a terminal editor, saving back to it.
+pub fn run(input: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let data: MapFile = serde_json::from_slice(&mapped::map_file(input)?)?;
+    data.grid.require_square()?;
+    let Some(matrix) = data.matrix else {
+        return Err(failure::bad_input("Only maps with a single matrix can be edited"));
+    };
+    if matrix.is_empty() {
+        return Err(failure::tag(Kind::InvalidJson, "Matrix is empty"));
+    }
+    let mut palette = Vec::with_capacity(data.colors.len());
+    for (&id, hex) in &data.colors {
+        palette.push((id, hex_to_rgba(hex).map_err(|e| failure::tag(Kind::InvalidJson, e))?));
+    }
+    palette.sort_by_key(|&(id, _)| id);
+
+    let mut editor = Editor {
+        matrix,
+        colors: data.colors,
+        palette,
+        cursor: (0, 0),
+        selected: 0,
+        scroll: (0, 0),
+        undo: Vec::new(),
+        redo: Vec::new(),
+        dirty: false,
+        confirm_quit: false,
+        message: String::new(),
+    };
+    let mut terminal = ratatui::try_init()?;
+    let result = editor.run(&mut terminal, input);
+    ratatui::try_restore()?;
+    result
+}
@@ -0,0 +1,17 @@
+use thiserror::Error;
+
+/// Why a library call failed, for callers to tell failures apart.
+#[derive(Debug, Error)]
+pub enum PixelError {
+    /// An image that can't be decoded, or encoded
+    #[error(transparent)]
+    Decode(#[from] image::ImageError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Map data that can't be read, such as an invalid hex color or a ragged matrix
+    #[error("{0}")]
+    Parse(String),
+    /// An argument out of range, such as a block size of 0
+    #[error("{0}")]
+    InvalidArgument(String),
+}
@@ -0,0 +1,89 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::chart::Pagination;
+use crate::failure::{self, Kind};
+use crate::physical::Sizing;
+use crate::text::{self, TextOptions};
+use crate::tiles::{self, TileSize};
+use crate::{aseprite, beads, gameboy, godot, lego, load_map, nes, rubik, tiled, unity};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// Tiled map (TMX) with its tileset (TSX) and tileset image
+    Tiled,
+    /// Godot 4 scene (`.tscn`) with a TileMap, its TileSet (`.tres`) and atlas image
+    Godot,
+    /// Unity: point-filtered texture with its `.meta`, and JSON (palette, grid, pivot) for a
+    /// ScriptableObject
+    Unity,
+    /// Indexed-color Aseprite sprite with the map's palette (up to 256 colors)
+    Aseprite,
+    /// Game Boy 2bpp tile data (4 shades, 8x8 tiles) and a tilemap; ignores `--tile-size`
+    #[value(name = "gb-2bpp")]
+    Gb2bpp,
+    /// NES CHR pattern table, nametable with attribute table (`.nam`) and palettes; ignores
+    /// `--tile-size`
+    Nes,
+    /// Perler fuse bead pattern (PDF), one 29x29 pegboard per page, with bead counts
+    Perler,
+    /// Hama fuse bead pattern (PDF)
+    Hama,
+    /// Artkal fuse bead pattern (PDF)
+    Artkal,
+    /// LEGO mosaic of 1x1 plates: build guide (PDF) by 32x32 baseplate, and a BrickLink XML
+    /// parts list
+    Lego,
+    /// Rubik's cube mosaic plan (JSON): the six sticker colors laid out as one 3x3 face per cube
+    Rubik,
+    /// Plain-text ASCII art, one character per cell from `--ramp`
+    Ascii,
+    /// Unicode Braille art, one character per 2x4 cells dotted where darker than `--threshold`
+    Braille,
+}
+
+impl ExportFormat {
+    /// Endings of the files written next to the output, each after the output's stem.
+    pub fn companions(self) -> &'static [&'static str] {
+        match self {
+            ExportFormat::Tiled => &[".tsx", "-tiles.png"],
+            ExportFormat::Godot => &[".tres", "-tiles.png"],
+            ExportFormat::Unity => &[".png", ".png.meta"],
+            ExportFormat::Gb2bpp => &[".tilemap"],
+            ExportFormat::Nes => &[".nam", "-palettes.json"],
+            ExportFormat::Lego => &["-bricklink.xml"],
+            _ => &[],
+        }
+    }
+}
+
+/// Convert the map in `input` to `format`, written to `output` and files next to it.
+pub fn run(input: &Path, output: &Path, format: ExportFormat, tile_size: TileSize, sizing: &Sizing, pagination: &Pagination, text: &TextOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let map = load_map(input)?;
+    if map.matrix.is_empty() {
+        return Err(failure::tag(Kind::InvalidJson, "Matrix is empty"));
+    }
+
+    match format {
+        ExportFormat::Tiled => {
+            let tiles = tiles::slice(&map, tile_size, false);
+            tiled::write(&map, &tiles, output).map_err(failure::write)?;
+        }
+        ExportFormat::Godot => {
+            let tiles = tiles::slice(&map, tile_size, false);
+            godot::write(&map, &tiles, output).map_err(failure::write)?;
+        }
+        ExportFormat::Unity => unity::write(&map, output).map_err(failure::write)?,
+        ExportFormat::Aseprite => aseprite::write(&map, output).map_err(failure::write)?,
+        ExportFormat::Gb2bpp => gameboy::write(&map, output).map_err(failure::write)?,
+        ExportFormat::Nes => nes::write(&map, output).map_err(failure::write)?,
+        ExportFormat::Perler => beads::write(&map, &beads::PERLER, output, sizing, pagination).map_err(failure::write)?,
+        ExportFormat::Hama => beads::write(&map, &beads::HAMA, output, sizing, pagination).map_err(failure::write)?,
+        ExportFormat::Artkal => beads::write(&map, &beads::ARTKAL, output, sizing, pagination).map_err(failure::write)?,
+        ExportFormat::Lego => lego::write(&map, output, sizing, pagination).map_err(failure::write)?,
+        ExportFormat::Rubik => rubik::write(&map, output).map_err(failure::write)?,
+        ExportFormat::Ascii => text::write_ascii(&map, text, output).map_err(failure::write)?,
+        ExportFormat::Braille => text::write_braille(&map, text, output).map_err(failure::write)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use image::DynamicImage;
+use rustface::{Detector, ImageData};
+use std::path::Path;
+
+use crate::failure;
+use crate::redact::Region;
+
+// Smallest face found, in pixels; also the smallest the detector can look for
+const MIN_FACE: u32 = 20;
+
+/// Loads the SeetaFace model at `path` once, to look for faces in any number of images.
+pub struct Faces {
+    detector: Box<dyn Detector>,
+}
+
+impl Faces {
+    pub fn new(model: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let model = rustface::load_model(&model.to_string_lossy()).map_err(|e| failure::bad_input(format!("Can't load face model {}: {}", model.display(), e)))?;
+        let mut detector = rustface::create_detector_with_model(model);
+        detector.set_min_face_size(MIN_FACE);
+        detector.set_score_thresh(2.0);
+        detector.set_pyramid_scale_factor(0.8);
+        detector.set_slide_window_step(4, 4);
+        Ok(Faces { detector })
+    }
+
+    /// Regions of the faces in `image`, grown by a quarter of their size on every side to
+    /// cover hair and chin.
+    pub fn find(&mut self, image: &DynamicImage) -> Vec<Region> {
+        let gray = image.to_luma8();
+        let (width, height) = gray.dimensions();
+        if width < MIN_FACE || height < MIN_FACE {
+            return Vec::new();
+        }
+        let faces = self.detector.detect(&ImageData::new(&gray, width, height));
+        faces
+            .iter()
+            .map(|face| {
+                let bbox = face.bbox();
+                let (margin_x, margin_y) = (bbox.width() as i32 / 4, bbox.height() as i32 / 4);
+                let (x, y) = ((bbox.x() - margin_x).max(0), (bbox.y() - margin_y).max(0));
+                let right = (bbox.x() + bbox.width() as i32 + margin_x).min(width as i32);
+                let bottom = (bbox.y() + bbox.height() as i32 + margin_y).min(height as i32);
+                Region { x: x as u32, y: y as u32, width: (right - x).max(1) as u32, height: (bottom - y).max(1) as u32 }
+            })
+            .collect()
+    }
+}
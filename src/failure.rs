@@ -0,0 +1,164 @@
+use clap::ValueEnum;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// What went wrong, as far as a calling script cares; each kind has its own exit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Other,
+    /// Missing or unusable inputs and invalid options
+    BadInput,
+    /// An image that can't be decoded
+    Decode,
+    /// A map file that isn't valid JSON or doesn't describe a map
+    InvalidJson,
+    /// An output that can't be written or encoded
+    Write,
+}
+
+impl Kind {
+    pub fn code(self) -> i32 {
+        match self {
+            Kind::Other => 1,
+            // Same as clap's usage errors
+            Kind::BadInput => 2,
+            Kind::Decode => 3,
+            Kind::InvalidJson => 4,
+            Kind::Write => 5,
+        }
+    }
+
+    /// The kind to report for a batch whose files failed with `self` and `other`.
+    pub fn merge(self, other: Kind) -> Kind {
+        if self == other { self } else { Kind::Other }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Other => "other",
+            Kind::BadInput => "bad_input",
+            Kind::Decode => "decode",
+            Kind::InvalidJson => "invalid_json",
+            Kind::Write => "write",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum ErrorFormat {
+    /// `Error: <message>`
+    #[default]
+    Text,
+    /// `{"error": <kind>, "code": <exit code>, "message": <message>}`
+    Json,
+}
+
+/// An error tagged with the kind it should be reported as.
+#[derive(Debug)]
+pub struct Failure {
+    pub kind: Kind,
+    source: Box<dyn Error>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl Error for Failure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Tag `error` with `kind`, unless it was already tagged closer to where it happened.
+pub fn tag(kind: Kind, error: impl Into<Box<dyn Error>>) -> Box<dyn Error> {
+    let error = error.into();
+    if error.is::<Failure>() {
+        return error;
+    }
+    Box::new(Failure { kind, source: error })
+}
+
+pub fn bad_input(error: impl Into<Box<dyn Error>>) -> Box<dyn Error> {
+    tag(Kind::BadInput, error)
+}
+
+pub fn write(error: impl Into<Box<dyn Error>>) -> Box<dyn Error> {
+    tag(Kind::Write, error)
+}
+
+fn io_kind(error: &io::Error) -> Kind {
+    match error.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied | io::ErrorKind::IsADirectory => Kind::BadInput,
+        _ => Kind::Other,
+    }
+}
+
+/// Kind of an error that wasn't tagged where it happened, judged by its type.
+pub fn classify(error: &(dyn Error + 'static)) -> Kind {
+    if let Some(failure) = error.downcast_ref::<Failure>() {
+        return failure.kind;
+    }
+    if let Some(error) = error.downcast_ref::<io::Error>() {
+        return io_kind(error);
+    }
+    if let Some(error) = error.downcast_ref::<image::ImageError>() {
+        return match error {
+            image::ImageError::IoError(error) => match io_kind(error) {
+                Kind::Other => Kind::Decode,
+                kind => kind,
+            },
+            image::ImageError::Encoding(_) => Kind::Write,
+            image::ImageError::Parameter(_) => Kind::BadInput,
+            _ => Kind::Decode,
+        };
+    }
+    if let Some(error) = error.downcast_ref::<pixel::PixelError>() {
+        return match error {
+            pixel::PixelError::Decode(error) => classify(error),
+            pixel::PixelError::Io(error) => io_kind(error),
+            pixel::PixelError::Parse(_) => Kind::InvalidJson,
+            pixel::PixelError::InvalidArgument(_) => Kind::BadInput,
+        };
+    }
+    if error.is::<png::DecodingError>() || error.is::<tiff::TiffError>() {
+        return Kind::Decode;
+    }
+    if error.is::<serde_json::Error>() {
+        return Kind::InvalidJson;
+    }
+    if error.is::<toml::de::Error>() || error.is::<glob::PatternError>() {
+        return Kind::BadInput;
+    }
+    Kind::Other
+}
+
+/// `error` as `{"error": <kind>, "code": <exit code>, "message": <message>}`.
+pub fn to_json(error: &(dyn Error + 'static)) -> serde_json::Value {
+    let kind = classify(error);
+    serde_json::json!({ "error": kind.name(), "code": kind.code(), "message": error.to_string() })
+}
+
+/// Print `error` to stderr in `format` and return the exit code for it.
+pub fn report(error: &(dyn Error + 'static), format: ErrorFormat) -> i32 {
+    match format {
+        ErrorFormat::Text => eprintln!("Error: {}", error),
+        ErrorFormat::Json => eprintln!("{}", to_json(error)),
+    }
+    classify(error).code()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_tagged_error_is_the_source() {
+        let error = write(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied"));
+        let source = error.source().expect("tagged error has a source");
+        assert!(source.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied));
+    }
+}
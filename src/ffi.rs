@@ -0,0 +1,109 @@
+use image::RgbaImage;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, UnwindSafe};
+use std::ptr;
+
+use crate::{ColorMapper, Output, map_image, render};
+
+/// Outcome of a call; free it with `pixel_free_result`.
+#[repr(C)]
+pub struct PixelResult {
+    /// NUL-terminated error message, or null on success
+    pub error: *mut c_char,
+    /// The JSON map from `pixel_map_rgba`, or the RGBA pixels from `pixel_reconstruct`, followed
+    /// by a NUL byte; null on error
+    pub data: *mut u8,
+    /// Length of `data` in bytes, without the NUL
+    pub len: usize,
+    /// Size of the image from `pixel_reconstruct` in pixels, 0 otherwise
+    pub width: u32,
+    pub height: u32,
+}
+
+fn result(outcome: Result<(Vec<u8>, u32, u32), String>) -> *mut PixelResult {
+    let result = match outcome {
+        Ok((mut data, width, height)) => {
+            let len = data.len();
+            data.push(0);
+            PixelResult { error: ptr::null_mut(), data: Box::into_raw(data.into_boxed_slice()).cast(), len, width, height }
+        }
+        Err(message) => {
+            let error = CString::new(message.replace('\0', " ")).unwrap_or_default().into_raw();
+            PixelResult { error, data: ptr::null_mut(), len: 0, width: 0, height: 0 }
+        }
+    };
+    Box::into_raw(Box::new(result))
+}
+
+// Panics must not unwind into C
+fn guarded(f: impl FnOnce() -> Result<(Vec<u8>, u32, u32), String> + UnwindSafe) -> *mut PixelResult {
+    result(panic::catch_unwind(f).unwrap_or_else(|_| Err("Internal error".to_string())))
+}
+
+/// Map `width` x `height` RGBA pixels (row-major, 4 bytes each) over `block_size` blocks,
+/// merging colors within `tolerance`, to a JSON map.
+///
+/// # Safety
+///
+/// `rgba` must point to `width * height * 4` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pixel_map_rgba(rgba: *const u8, width: u32, height: u32, block_size: u32, tolerance: f64) -> *mut PixelResult {
+    let len = width as usize * height as usize * 4;
+    if rgba.is_null() || len == 0 {
+        return result(Err("Image is empty".to_string()));
+    }
+    // SAFETY: the caller guarantees `len` readable bytes at `rgba`
+    let pixels = unsafe { std::slice::from_raw_parts(rgba, len) };
+    guarded(|| {
+        if block_size == 0 {
+            return Err("Block size must be greater than 0".to_string());
+        }
+        let image = RgbaImage::from_raw(width, height, pixels.to_vec()).ok_or("Image is too large")?;
+        let mut mapper = ColorMapper::new(tolerance);
+        let matrix = map_image(&image, block_size, &mut mapper);
+        let json = serde_json::to_vec(&Output { matrix, colors: mapper.id_to_color }).map_err(|e| e.to_string())?;
+        Ok((json, 0, 0))
+    })
+}
+
+/// Paint the NUL-terminated JSON map `json` (with a single matrix) back into RGBA pixels.
+///
+/// # Safety
+///
+/// `json` must be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pixel_reconstruct(json: *const c_char) -> *mut PixelResult {
+    if json.is_null() {
+        return result(Err("Map is null".to_string()));
+    }
+    // SAFETY: the caller guarantees a NUL-terminated string
+    let json = unsafe { CStr::from_ptr(json) };
+    guarded(|| {
+        let map: Output = serde_json::from_slice(json.to_bytes()).map_err(|e| e.to_string())?;
+        let image = render(&map.matrix, &map.colors, |_| {}).map_err(|e| e.to_string())?;
+        let (width, height) = image.dimensions();
+        Ok((image.into_raw(), width, height))
+    })
+}
+
+/// Free a result returned by the other functions; null is ignored.
+///
+/// # Safety
+///
+/// `result` must come from this library and not be freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pixel_free_result(result: *mut PixelResult) {
+    if result.is_null() {
+        return;
+    }
+    // SAFETY: `result` and its buffers were allocated by `result()` above
+    unsafe {
+        let result = Box::from_raw(result);
+        if !result.error.is_null() {
+            drop(CString::from_raw(result.error));
+        }
+        if !result.data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(result.data, result.len + 1)));
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::tiles::{self, TileSize};
+use crate::{Output, failure, hex_to_rgba};
+
+const TILE_SIZE: u32 = 8;
+
+// 0 is the lightest shade (and transparent in sprites), 3 the darkest, as in the default palette
+fn shade(hex: &str) -> Result<u32, String> {
+    let [r, g, b, a] = hex_to_rgba(hex).map_err(|e| e.to_string())?.0;
+    if a == 0 {
+        return Ok(0);
+    }
+    let luma = (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000;
+    Ok(3 - luma * 4 / 256)
+}
+
+/// Write the map as Game Boy 2bpp tile data: `output` holds the distinct 8x8 tiles (16 bytes
+/// each, tile 0 blank) and `<stem>.tilemap` one tile index byte per grid cell, row-major.
+/// Colors are reduced to 4 shades by luminance.
+pub fn write(map: &Output, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let stem = output.file_stem().ok_or("Output has no file name")?.to_string_lossy().into_owned();
+    let tilemap_path = output.with_file_name(format!("{}.tilemap", stem));
+    if tilemap_path == output {
+        return Err(failure::bad_input("Output must not end in .tilemap; the tilemap is written next to it"));
+    }
+
+    let mut shades = HashMap::new();
+    for (&id, hex) in &map.colors {
+        shades.insert(id, shade(hex)?);
+    }
+    // Shade 0 is left out of the colors so blank tiles fold into tile 0
+    let shaded = Output {
+        matrix: map.matrix.iter().map(|row| row.iter().map(|id| shades.get(id).copied().unwrap_or(0)).collect()).collect(),
+        colors: (1..4).map(|shade| (shade, String::new())).collect(),
+    };
+    let tiles = tiles::slice(&shaded, TileSize::square(TILE_SIZE), false);
+    if tiles.tiles.len() > 255 {
+        return Err(failure::bad_input(format!("The map has {} distinct tiles; a Game Boy tilemap indexes at most 256 including the blank tile", tiles.tiles.len())));
+    }
+
+    let mut data = vec![0u8; 16];
+    for tile in &tiles.tiles {
+        for row in tile.chunks(TILE_SIZE as usize) {
+            let (mut low, mut high) = (0u8, 0u8);
+            for (x, &shade) in row.iter().enumerate() {
+                low |= (shade as u8 & 1) << (7 - x);
+                high |= (shade as u8 >> 1) << (7 - x);
+            }
+            data.extend_from_slice(&[low, high]);
+        }
+    }
+    fs::write(output, data)?;
+
+    let tilemap: Vec<u8> = tiles.grid.iter().flatten().map(|tile| tile.map_or(0, |placement| placement.tile as u8 + 1)).collect();
+    fs::write(tilemap_path, tilemap)?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::Path;
+
+use crate::Output;
+use crate::tiles::Tiles;
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Node names can't contain these
+fn node_name(text: &str) -> String {
+    text.replace(['.', ':', '@', '/', '"', '%'], "_")
+}
+
+/// Write `tiles` for Godot 4: `output` (a `.tscn` scene with a TileMap) using the tileset
+/// `<stem>.tres`, whose atlas texture is `<stem>-tiles.png`, all in the same directory.
+pub fn write(map: &Output, tiles: &Tiles, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let stem = output.file_stem().ok_or("Output has no file name")?.to_string_lossy().into_owned();
+    let tres_name = format!("{}.tres", stem);
+    let image_name = format!("{}-tiles.png", stem);
+    let (width, height) = (tiles.size.width, tiles.size.height);
+
+    let (columns, _, _) = tiles.save_atlas(&map.colors, &output.with_file_name(&image_name))?;
+    let atlas_coords = |tile: usize| (tile as u32 % columns, tile as u32 / columns);
+
+    // Relative paths are resolved against the resource's own directory
+    let mut tres = format!(
+        "[gd_resource type=\"TileSet\" load_steps=3 format=3]\n\n\
+         [ext_resource type=\"Texture2D\" path={} id=\"1\"]\n\n\
+         [sub_resource type=\"TileSetAtlasSource\" id=\"TileSetAtlasSource_1\"]\n\
+         texture = ExtResource(\"1\")\n\
+         texture_region_size = Vector2i({width}, {height})\n",
+        quote(&image_name)
+    );
+    for tile in 0..tiles.tiles.len() {
+        let (x, y) = atlas_coords(tile);
+        tres.push_str(&format!("{}:{}/0 = 0\n", x, y));
+    }
+    tres.push_str(&format!("\n[resource]\ntile_size = Vector2i({width}, {height})\nsources/0 = SubResource(\"TileSetAtlasSource_1\")\n"));
+    fs::write(output.with_file_name(&tres_name), tres)?;
+
+    // Three ints per cell: x | y << 16, source | atlas x << 16, atlas y | alternative << 16
+    let mut cells = Vec::new();
+    for (y, row) in tiles.grid.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            let Some(placement) = tile else {
+                continue;
+            };
+            let (ax, ay) = atlas_coords(placement.tile);
+            cells.push(format!("{}, {}, {}", (x as u32 | (y as u32) << 16) as i32, (ax << 16) as i32, ay));
+        }
+    }
+    let tscn = format!(
+        "[gd_scene load_steps=2 format=3]\n\n\
+         [ext_resource type=\"TileSet\" path={} id=\"1\"]\n\n\
+         [node name={} type=\"TileMap\"]\n\
+         texture_filter = 1\n\
+         tile_set = ExtResource(\"1\")\n\
+         format = 2\n\
+         layer_0/tile_data = PackedInt32Array({})\n",
+        quote(&tres_name),
+        quote(&node_name(&stem)),
+        cells.join(", ")
+    );
+    fs::write(output, tscn)?;
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use clap::ValueEnum;
+use image::{Rgba, RgbaImage};
+use pixel::{ColorMapper, PixelError};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::edges::Edges;
+use crate::{failure, hex, triangle};
+
+/// Shape of the cells an image is cut into; recorded in maps as `grid` unless square.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Grid {
+    /// Square blocks, one matrix row per row of blocks
+    #[default]
+    Square,
+    /// Pointy-top hexagons as wide as the block size, odd rows shifted right by half a cell.
+    /// Matrix rows are axial `r`, and column `i` of row `r` is axial `q = i - floor(r / 2)`
+    Hex,
+    /// Alternating up and down equilateral triangles with sides as long as the block size;
+    /// triangle `c` of row `r` points up when `r + c` is even, and the first and last of each
+    /// row stick out of the image by half
+    Triangle,
+}
+
+impl Grid {
+    pub fn name(self) -> &'static str {
+        match self {
+            Grid::Square => "square",
+            Grid::Hex => "hex",
+            Grid::Triangle => "triangle",
+        }
+    }
+
+    /// Refuse maps of hex and triangle cells, for the commands that read every matrix as rows of
+    /// square cells.
+    pub fn require_square(self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Grid::Square => Ok(()),
+            _ => Err(failure::bad_input(format!("This command takes maps of square cells; {} maps only work with reconstruct and tween", self.name()))),
+        }
+    }
+
+    /// Columns and rows of cells of `block_size` covering a `width` x `height` image.
+    pub fn cells(self, width: u32, height: u32, block_size: u32, edges: Edges) -> (usize, usize) {
+        match self {
+            Grid::Square => (edges.spans(width, block_size).len(), edges.spans(height, block_size).len()),
+            Grid::Hex => hex::cells(width, height, block_size),
+            Grid::Triangle => triangle::cells(width, height, block_size),
+        }
+    }
+
+    /// Average `image` over the cells of `block_size` and assign each a color ID.
+    pub fn map_image(self, image: &RgbaImage, block_size: u32, mapper: &mut ColorMapper) -> Vec<Vec<u32>> {
+        match self {
+            Grid::Square => pixel::map_image(image, block_size, mapper),
+            Grid::Hex => hex::map_image(image, block_size, mapper),
+            Grid::Triangle => triangle::map_image(image, block_size, mapper),
+        }
+    }
+
+    /// Paint `matrix` with cells `width` pixels across, or a pixel each for square grids. IDs
+    /// missing from `colors` become transparent and are passed to `missing`.
+    pub fn render(self, matrix: &[Vec<u32>], colors: &HashMap<u32, String>, width: u32, missing: impl FnMut(u32)) -> Result<RgbaImage, PixelError> {
+        match self {
+            Grid::Square => pixel::render(matrix, colors, missing),
+            Grid::Hex => hex::render(matrix, colors, width, missing),
+            Grid::Triangle => triangle::render(matrix, colors, width, missing),
+        }
+    }
+}
+
+/// Average `image` over `cols` x `rows` cells, each pixel going to the cell `cell_at` its
+/// center, or the nearest one past the outer cells so every pixel is used; cells left without
+/// pixels are transparent.
+pub fn average_cells(image: &RgbaImage, (cols, rows): (usize, usize), cell_at: impl Fn(f64, f64) -> (i64, i64), mapper: &mut ColorMapper) -> Vec<Vec<u32>> {
+    let mut sums = vec![([0u64; 4], 0u64); cols * rows];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let (row, col) = cell_at(x as f64 + 0.5, y as f64 + 0.5);
+        let (row, col) = (row.clamp(0, rows as i64 - 1) as usize, col.clamp(0, cols as i64 - 1) as usize);
+        let (sum, count) = &mut sums[row * cols + col];
+        for c in 0..4 {
+            sum[c] += pixel[c] as u64;
+        }
+        *count += 1;
+    }
+    sums.chunks(cols).map(|row| row.iter().map(|&(sum, count)| if count == 0 { 0 } else { mapper.id_for(pixel::block_color(sum, count)) }).collect()).collect()
+}
+
+/// Paint a `width` x `height` picture of `matrix`, each pixel in the color of the cell
+/// `cell_at` its center and transparent off the matrix. IDs missing from `colors` become
+/// transparent and are passed to `missing`, once each.
+pub fn paint_cells(matrix: &[Vec<u32>], colors: &HashMap<u32, String>, (width, height): (f64, f64), cell_at: impl Fn(f64, f64) -> (i64, i64), mut missing: impl FnMut(u32)) -> Result<RgbaImage, PixelError> {
+    if width * height * 4.0 > isize::MAX as f64 || width > u32::MAX as f64 || height > u32::MAX as f64 {
+        return Err(PixelError::InvalidArgument(format!("Map of {}x{} cells is too large for an image", matrix.first().map_or(0, Vec::len), matrix.len())));
+    }
+    let mut palette = HashMap::new();
+    for (&id, hex) in colors {
+        palette.insert(id, pixel::hex_to_rgba(hex)?);
+    }
+    let mut reported = Vec::new();
+    let mut image = RgbaImage::new((width.ceil() as u32).max(1), (height.ceil() as u32).max(1));
+    for (x, y, out) in image.enumerate_pixels_mut() {
+        let (row, col) = cell_at(x as f64 + 0.5, y as f64 + 0.5);
+        let Some(&id) = usize::try_from(row).ok().and_then(|row| matrix.get(row)).and_then(|cells| usize::try_from(col).ok().and_then(|col| cells.get(col))) else {
+            continue;
+        };
+        *out = match palette.get(&id) {
+            Some(&color) => color,
+            None => {
+                if !reported.contains(&id) {
+                    reported.push(id);
+                    missing(id);
+                }
+                Rgba([0, 0, 0, 0])
+            }
+        };
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_square_grids_pass_require_square() {
+        assert!(Grid::Square.require_square().is_ok());
+        assert!(Grid::Hex.require_square().is_err());
+        assert!(Grid::Triangle.require_square().is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use std::net::ToSocketAddrs;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::info;
+
+use crate::failure;
+
+mod proto {
+    tonic::include_proto!("pixel");
+}
+
+use proto::map_response::Item;
+use proto::mapper_server::{Mapper, MapperServer};
+use proto::{Colors, MapRequest, MapResponse, Row};
+
+struct Service;
+
+#[tonic::async_trait]
+impl Mapper for Service {
+    type MapStream = Pin<Box<dyn Stream<Item = Result<MapResponse, Status>> + Send>>;
+
+    async fn map(&self, request: Request<Streaming<MapRequest>>) -> Result<Response<Self::MapStream>, Status> {
+        let mut upload = request.into_inner();
+        let mut options = None;
+        let mut bytes = Vec::new();
+        while let Some(message) = upload.message().await? {
+            options.get_or_insert(message.options.unwrap_or_default());
+            bytes.extend_from_slice(&message.chunk);
+        }
+        let options = options.unwrap_or_default();
+        let image = image::load_from_memory(&bytes).map_err(|e| Status::invalid_argument(e.to_string()))?.to_rgba8();
+
+        let (tx, rx) = mpsc::channel(64);
+        tokio::task::spawn_blocking(move || {
+            let mut mapper = pixel::ColorMapper::new(options.tolerance);
+            for ids in pixel::map_image(&image, options.block_size.max(1), &mut mapper) {
+                if tx.blocking_send(Ok(MapResponse { item: Some(Item::Row(Row { ids })) })).is_err() {
+                    return;
+                }
+            }
+            let colors = Colors { colors: mapper.id_to_color };
+            let _ = tx.blocking_send(Ok(MapResponse { item: Some(Item::Colors(colors)) }));
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+/// Serve the `Mapper` service of `proto/pixel.proto` on `host:port` until interrupted.
+pub fn run(host: &str, port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    let address = (host, port).to_socket_addrs()?.next().ok_or_else(|| failure::bad_input(format!("Can't resolve {}", host)))?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    info!(host, port, "listening for gRPC");
+    runtime.block_on(Server::builder().add_service(MapperServer::new(Service)).serve(address))?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use image::RgbaImage;
+use pixel::{ColorMapper, PixelError};
+use std::collections::HashMap;
+
+use crate::grid;
+
+// Geometry of pointy-top hexagons `width` pixels across the flats: the circumradius, and the
+// distance between rows
+fn radius(width: f64) -> f64 {
+    width / 3f64.sqrt()
+}
+
+fn row_height(width: f64) -> f64 {
+    radius(width) * 1.5
+}
+
+/// Row and column of the cell whose hexagon holds the point `(x, y)`, for cells `width` pixels
+/// across with cell (0, 0) touching the top left corner. Either may be out of the matrix.
+fn cell_at(x: f64, y: f64, width: f64) -> (i64, i64) {
+    let radius = radius(width);
+    let (x, y) = (x - width / 2.0, y - radius);
+    let q = (3f64.sqrt() / 3.0 * x - y / 3.0) / radius;
+    let r = 2.0 / 3.0 * y / radius;
+
+    // Round the cube coordinates (q, r, -q - r), fixing the one that moved most
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), (-q - r).round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs + q + r).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    let (q, r) = (rq as i64, rr as i64);
+    (r, q + r.div_euclid(2))
+}
+
+/// Columns and rows of hexagons `block_size` pixels across that cover a `width` x `height`
+/// image: those whose centers are in it, at least one each way.
+pub fn cells(width: u32, height: u32, block_size: u32) -> (usize, usize) {
+    let block = block_size as f64;
+    let cols = ((width as f64 - block / 2.0) / block).ceil().max(1.0) as u32;
+    let rows = ((height as f64 - radius(block)) / row_height(block)).ceil().max(1.0) as u32;
+    (cols as usize, rows as usize)
+}
+
+/// Average `image` over hexagons `block_size` pixels across and assign each a color ID.
+pub fn map_image(image: &RgbaImage, block_size: u32, mapper: &mut ColorMapper) -> Vec<Vec<u32>> {
+    let (width, height) = image.dimensions();
+    grid::average_cells(image, cells(width, height, block_size), |x, y| cell_at(x, y, block_size as f64), mapper)
+}
+
+/// Paint `matrix`, a hex map, with hexagons `width` pixels across. IDs missing from `colors`
+/// become transparent and are passed to `missing`.
+pub fn render(matrix: &[Vec<u32>], colors: &HashMap<u32, String>, width: u32, missing: impl FnMut(u32)) -> Result<RgbaImage, PixelError> {
+    let (cols, rows) = (matrix.first().map_or(0, Vec::len), matrix.len());
+    if cols == 0 {
+        return Err(PixelError::Parse("Matrix is empty".to_string()));
+    }
+    let block = width as f64;
+    // Odd rows stick out half a cell on the right
+    let picture_width = cols as f64 * block + if rows > 1 { block / 2.0 } else { 0.0 };
+    let picture_height = 2.0 * radius(block) + (rows - 1) as f64 * row_height(block);
+    grid::paint_cells(matrix, colors, (picture_width, picture_height), |x, y| cell_at(x, y, block), missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: f64 = 12.0;
+
+    fn center(row: i64, col: i64) -> (f64, f64) {
+        let shift = if row % 2 == 1 { WIDTH / 2.0 } else { 0.0 };
+        (WIDTH / 2.0 + col as f64 * WIDTH + shift, radius(WIDTH) + row as f64 * row_height(WIDTH))
+    }
+
+    #[test]
+    fn centers_are_in_their_own_cell() {
+        for row in 0..6 {
+            for col in 0..6 {
+                let (x, y) = center(row, col);
+                assert_eq!(cell_at(x, y, WIDTH), (row, col));
+            }
+        }
+    }
+
+    #[test]
+    fn odd_rows_are_shifted_right() {
+        // Past the middle of the shared edge, toward the neighbour below and to the right
+        for (row, col, below) in [(0, 2, (1, 2)), (1, 2, (2, 3)), (2, 0, (3, 0)), (3, 0, (4, 1))] {
+            let ((x, y), (nx, ny)) = (center(row, col), center(below.0, below.1));
+            assert_eq!(cell_at(x + (nx - x) * 0.6, y + (ny - y) * 0.6, WIDTH), below);
+        }
+        // Across the flat side on the right
+        let (x, y) = center(1, 2);
+        assert_eq!(cell_at(x + WIDTH * 0.6, y, WIDTH), (1, 3));
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::Output;
+use crate::chart::{self, Key, Pagination};
+use crate::failure::{self, Kind};
+use crate::pdf::Document;
+use crate::physical::{Length, Sizing};
+
+// 32x32 baseplates, guides every 8 studs
+const BASEPLATE: usize = 32;
+const BOLD_EVERY: usize = 8;
+const PLATE_1X1: &str = "3024";
+const STUD: Length = Length { mm: 8.0 };
+
+/// Colors Plate 1 x 1 comes in: name, BrickLink color ID and approximate color as `0xRRGGBB`.
+pub const COLORS: &[(&str, u32, u32)] = &[
+    ("White", 1, 0xf2f3f2),
+    ("Tan", 2, 0xe4cd9e),
+    ("Yellow", 3, 0xf2cd37),
+    ("Orange", 4, 0xfe8a18),
+    ("Red", 5, 0xc91a09),
+    ("Green", 6, 0x237841),
+    ("Blue", 7, 0x0055bf),
+    ("Black", 11, 0x05131d),
+    ("Pink", 23, 0xfc97ac),
+    ("Purple", 24, 0x81007b),
+    ("Nougat", 28, 0xd09168),
+    ("Lime", 34, 0xbbe90b),
+    ("Bright Green", 36, 0x4b9f4a),
+    ("Dark Turquoise", 39, 0x008f9b),
+    ("Medium Blue", 42, 0x5a93db),
+    ("Dark Pink", 47, 0xc870a0),
+    ("Sand Green", 48, 0xa0bcac),
+    ("Sand Blue", 55, 0x6074a1),
+    ("Dark Red", 59, 0x720e0f),
+    ("Dark Blue", 63, 0x0a3463),
+    ("Dark Orange", 68, 0xa95500),
+    ("Dark Tan", 69, 0x958a73),
+    ("Magenta", 71, 0x923978),
+    ("Dark Green", 80, 0x184632),
+    ("Dark Bluish Gray", 85, 0x6c6e68),
+    ("Light Bluish Gray", 86, 0xa0a5a9),
+    ("Reddish Brown", 88, 0x582a12),
+    ("Dark Purple", 89, 0x3f3691),
+    ("Light Nougat", 90, 0xf6d7b3),
+    ("Bright Light Yellow", 103, 0xfff03a),
+    ("Bright Pink", 104, 0xe4adc8),
+    ("Bright Light Blue", 105, 0x9fc3e9),
+    ("Bright Light Orange", 110, 0xf8bb3d),
+    ("Dark Brown", 120, 0x352100),
+    ("Medium Nougat", 150, 0xaa7d55),
+    ("Light Aqua", 152, 0xadc3c0),
+    ("Dark Azure", 153, 0x078bc9),
+    ("Lavender", 154, 0xe1d5ed),
+    ("Olive Green", 155, 0x9b9a5a),
+    ("Medium Azure", 156, 0x36aebf),
+    ("Medium Lavender", 157, 0xac78ba),
+    ("Yellowish Green", 158, 0xdfeea5),
+    ("Coral", 220, 0xff698f),
+];
+
+/// Write a LEGO mosaic of 1x1 plates: `output` as a PDF build guide with one 32x32 baseplate
+/// per page and a parts list, and `<stem>-bricklink.xml` as a BrickLink wanted list.
+pub fn write(map: &Output, output: &Path, sizing: &Sizing, pagination: &Pagination) -> Result<(), Box<dyn std::error::Error>> {
+    let stem = output.file_stem().ok_or("Output has no file name")?.to_string_lossy().into_owned();
+    let xml_path = output.with_file_name(format!("{}-bricklink.xml", stem));
+    if xml_path == output {
+        return Err(failure::bad_input("Output must not end in -bricklink.xml; the parts list is written next to it"));
+    }
+
+    let colors: Vec<u32> = COLORS.iter().map(|&(_, _, rgb)| rgb).collect();
+    let matched = chart::match_colors(map, &colors).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    let keys: Vec<Key> = matched
+        .used
+        .iter()
+        .zip(chart::symbols(matched.used.len()))
+        .map(|(&(index, _), symbol)| Key { symbol, color: chart::opaque(colors[index]) })
+        .collect();
+
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let baseplates = width.div_ceil(BASEPLATE) * map.matrix.len().div_ceil(BASEPLATE);
+    let total: usize = matched.used.iter().map(|&(_, count)| count).sum();
+    let title = format!(
+        "LEGO mosaic: {}x{} studs ({}), {} baseplates, {} plates in {} colors",
+        width,
+        map.matrix.len(),
+        sizing.describe(width, map.matrix.len(), STUD),
+        baseplates,
+        total,
+        keys.len()
+    );
+
+    let mut doc = Document::new(pagination.paper());
+    chart::draw(&mut doc, &title, &matched.cells, &keys, (BASEPLATE, BASEPLATE), BOLD_EVERY, pagination).map_err(failure::bad_input)?;
+    let rows: Vec<(&Key, Vec<String>)> = keys
+        .iter()
+        .zip(&matched.used)
+        .map(|(key, &(index, count))| {
+            let (name, id, _) = COLORS[index];
+            (key, vec![name.to_string(), id.to_string(), count.to_string()])
+        })
+        .collect();
+    chart::legend(&mut doc, &title, &["Color", "BrickLink color", "Plates 1 x 1"], &rows);
+    doc.save(output)?;
+
+    let mut xml = String::from("<INVENTORY>\n");
+    for &(index, count) in &matched.used {
+        writeln!(
+            xml,
+            "<ITEM><ITEMTYPE>P</ITEMTYPE><ITEMID>{}</ITEMID><COLOR>{}</COLOR><MINQTY>{}</MINQTY></ITEM>",
+            PLATE_1X1, COLORS[index].1, count
+        )?;
+    }
+    xml.push_str("</INVENTORY>\n");
+    fs::write(xml_path, xml)?;
+    Ok(())
+}
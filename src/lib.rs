@@ -0,0 +1,347 @@
+//! Core of `pixel`: assigning color IDs to blocks of an image and painting maps back. Nothing
+//! here touches files or the terminal, so it builds for WebAssembly as well (`--features wasm`),
+//! as a C library (`--features ffi`, header in `include/pixel.h`) and as a Node.js addon
+//! (`--features node`).
+
+mod error;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "node")]
+pub mod node;
+mod palette_index;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use error::PixelError;
+use image::{ImageFormat, Rgba, RgbaImage};
+use palette_index::PaletteIndex;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+
+#[derive(Serialize, Deserialize)]
+pub struct Output {
+    pub matrix: Vec<Vec<u32>>,
+    #[serde(serialize_with = "sorted")]
+    pub colors: HashMap<u32, String>,
+}
+
+/// Serialize `map` in key order, for `#[serde(serialize_with)]`, so the same map always gives
+/// the same bytes; color IDs come out in numeric order.
+pub fn sorted<S: Serializer, K: Ord + Serialize, V: Serialize>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// Squared RGBA distance; compare against `tolerance²` to avoid the square root.
+pub fn color_distance_sq(c1: &Rgba<u8>, c2: &Rgba<u8>) -> u32 {
+    let mut sum = 0;
+    for c in 0..4 {
+        let diff = c1[c] as i32 - c2[c] as i32;
+        sum += (diff * diff) as u32;
+    }
+    sum
+}
+
+/// Per-channel sums of a run of RGBA pixels.
+///
+/// Four pixels (16 bytes) are summed per step into independent lanes, which the
+/// compiler turns into packed vector adds.
+pub fn sum_rgba(pixels: &[u8]) -> [u64; 4] {
+    let mut total = [0u64; 4];
+    // u32 lanes can't overflow within 2^24 steps of at most 255 each
+    for part in pixels.chunks(16 << 24) {
+        let mut lanes = [0u32; 16];
+        let chunks = part.chunks_exact(16);
+        for (i, &v) in chunks.remainder().iter().enumerate() {
+            lanes[i] += v as u32;
+        }
+        for chunk in chunks {
+            for (lane, &v) in lanes.iter_mut().zip(chunk) {
+                *lane += v as u32;
+            }
+        }
+        for (i, lane) in lanes.iter().enumerate() {
+            total[i % 4] += *lane as u64;
+        }
+    }
+    total
+}
+
+/// Assigns color IDs, merging colors within `tolerance` of an already seen color.
+pub struct ColorMapper {
+    tolerance: f64,
+    pub color_to_id: HashMap<String, u32>,
+    pub id_to_color: HashMap<u32, String>,
+    // Canonical colors for fuzzy matching
+    palette: PaletteIndex,
+    next_id: u32,
+}
+
+impl ColorMapper {
+    pub fn new(tolerance: f64) -> Self {
+        let mut color_to_id = HashMap::new();
+        let mut id_to_color = HashMap::new();
+
+        // Reserve ID 0 for fully transparent
+        let transparent_hex = "#00000000".to_string();
+        color_to_id.insert(transparent_hex.clone(), 0);
+        id_to_color.insert(0, transparent_hex);
+
+        ColorMapper {
+            tolerance,
+            color_to_id,
+            id_to_color,
+            palette: PaletteIndex::new(tolerance),
+            next_id: 1,
+        }
+    }
+
+    /// Continue from a previous run's assignments so known colors keep their IDs.
+    pub fn restore(tolerance: f64, id_to_color: HashMap<u32, String>, color_to_id: HashMap<String, u32>) -> Self {
+        let mut mapper = ColorMapper::new(tolerance);
+        for (&id, hex) in &id_to_color {
+            if id == 0 {
+                continue;
+            }
+            if let Ok(color) = hex_to_rgba(hex) {
+                mapper.palette.insert(id, color);
+            }
+            mapper.next_id = mapper.next_id.max(id + 1);
+        }
+        mapper.id_to_color.extend(id_to_color);
+        mapper.color_to_id.extend(color_to_id);
+        mapper
+    }
+
+    pub fn id_for(&mut self, color: Rgba<u8>) -> u32 {
+        let a = color[3];
+        let hex_color = rgba_to_hex(&color);
+
+        // 1. Try exact match
+        if let Some(&existing_id) = self.color_to_id.get(&hex_color) {
+            return existing_id;
+        }
+
+        // 2. Try fuzzy match (if tolerance > 0 and not transparent)
+        let found_id = if self.tolerance > 0.0 && a > 0 { self.palette.find(&color) } else { None };
+
+        if let Some(fid) = found_id {
+            // Map this specific slightly-different hex to the existing ID for future speed
+            self.color_to_id.insert(hex_color, fid);
+            return fid;
+        }
+
+        // New color
+        let id = self.next_id;
+        self.palette.insert(id, color);
+        self.color_to_id.insert(hex_color.clone(), id);
+        self.id_to_color.insert(id, hex_color);
+        self.next_id += 1;
+        id
+    }
+}
+
+pub fn rgba_to_hex(color: &Rgba<u8>) -> String {
+    let [r, g, b, a] = color.0;
+    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+}
+
+/// `#RRRRGGGGBBBBAAAA`, for colors kept at 16 bits per channel.
+pub fn rgba16_to_hex(color: &Rgba<u16>) -> String {
+    let [r, g, b, a] = color.0;
+    format!("#{:04x}{:04x}{:04x}{:04x}", r, g, b, a)
+}
+
+/// Parse a hex color: `#RRGGBBAA`, `#RRGGBB`, `#RGBA` or `#RGB`, with or without the `#`, where
+/// a missing alpha is opaque; or `#RRRRGGGGBBBBAAAA`, rounded to 8 bits per channel.
+pub fn hex_to_rgba(hex: &str) -> Result<Rgba<u8>, PixelError> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    // (digits per channel, channels given)
+    let (width, count) = match digits.len() {
+        3 | 4 => (1, digits.len()),
+        6 | 8 => (2, digits.len() / 2),
+        16 => (4, 4),
+        _ => return Err(PixelError::Parse(format!("Invalid hex color: {}", hex))),
+    };
+    // Also keeps the slices below on character boundaries
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(PixelError::Parse(format!("Invalid hex color: {}", hex)));
+    }
+    let mut color = Rgba([255; 4]);
+    for (i, channel) in color.0.iter_mut().take(count).enumerate() {
+        let value = u32::from_str_radix(&digits[i * width..(i + 1) * width], 16).map_err(|e| PixelError::Parse(e.to_string()))?;
+        *channel = match width {
+            1 => value * 17,
+            2 => value,
+            _ => (value + 128) / 257,
+        } as u8;
+    }
+    Ok(color)
+}
+
+// Rounds of moving centroids and reassigning colors in `cluster_colors`, at most
+const CLUSTER_PASSES: usize = 8;
+
+/// Order-independent alternative to the greedy matching of [`ColorMapper`]: group `colors`, each
+/// with the number of cells using it, around frequency-weighted centroids. Colors are seeded
+/// into clusters from the most used down, joining the nearest centroid within `tolerance`, then
+/// centroids move to the weighted mean of their members and colors to their nearest centroid
+/// until nothing changes.
+///
+/// Returns the cluster of each color, `None` for fully transparent ones, and the cluster colors,
+/// heaviest first.
+pub fn cluster_colors(colors: &[(Rgba<u8>, u64)], tolerance: f64) -> (Vec<Option<usize>>, Vec<Rgba<u8>>) {
+    let mut order: Vec<usize> = (0..colors.len()).filter(|&i| colors[i].0[3] > 0).collect();
+    order.sort_by(|&a, &b| colors[b].1.cmp(&colors[a].1).then(colors[a].0.0.cmp(&colors[b].0.0)));
+    let nearest = |centroids: &[Rgba<u8>], color: &Rgba<u8>| centroids.iter().enumerate().map(|(i, centroid)| (color_distance_sq(centroid, color), i)).min();
+
+    let mut clusters = vec![None; colors.len()];
+    let mut centroids: Vec<Rgba<u8>> = Vec::new();
+    for &i in &order {
+        clusters[i] = Some(match nearest(&centroids, &colors[i].0) {
+            Some((distance, cluster)) if distance as f64 <= tolerance * tolerance => cluster,
+            _ => {
+                centroids.push(colors[i].0);
+                centroids.len() - 1
+            }
+        });
+    }
+
+    let mut weights = vec![0u64; centroids.len()];
+    for pass in 0..=CLUSTER_PASSES {
+        let mut sums = vec![[0u64; 4]; centroids.len()];
+        weights.fill(0);
+        for &i in &order {
+            let (color, count) = colors[i];
+            let cluster = clusters[i].expect("visible colors are clustered");
+            for c in 0..4 {
+                sums[cluster][c] += color[c] as u64 * count;
+            }
+            weights[cluster] += count;
+        }
+        for ((centroid, sum), &weight) in centroids.iter_mut().zip(&sums).zip(&weights) {
+            if weight > 0 {
+                *centroid = Rgba(sum.map(|sum| ((sum + weight / 2) / weight) as u8));
+            }
+        }
+        if pass == CLUSTER_PASSES {
+            break;
+        }
+        let mut changed = false;
+        for &i in &order {
+            let cluster = nearest(&centroids, &colors[i].0).map(|(_, cluster)| cluster);
+            changed |= clusters[i] != cluster;
+            clusters[i] = cluster;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Heaviest first, dropping clusters left without members
+    let mut ranked: Vec<usize> = (0..centroids.len()).filter(|&cluster| weights[cluster] > 0).collect();
+    ranked.sort_by(|&a, &b| weights[b].cmp(&weights[a]).then(centroids[a].0.cmp(&centroids[b].0)));
+    let mut rank = vec![0; centroids.len()];
+    for (position, &cluster) in ranked.iter().enumerate() {
+        rank[cluster] = position;
+    }
+    (clusters.iter().map(|cluster| cluster.map(|cluster| rank[cluster])).collect(), ranked.iter().map(|&cluster| centroids[cluster]).collect())
+}
+
+/// Average color of a block from its per-channel sums over `count` pixels; blocks that average
+/// fully transparent are plain transparent whatever their RGB.
+pub fn block_color(sum: [u64; 4], count: u64) -> Rgba<u8> {
+    let avg_a = (sum[3] / count) as u8;
+    if avg_a == 0 {
+        Rgba([0, 0, 0, 0])
+    } else {
+        Rgba([(sum[0] / count) as u8, (sum[1] / count) as u8, (sum[2] / count) as u8, avg_a])
+    }
+}
+
+/// Average `image` over `block_size` blocks and assign each block a color ID.
+pub fn map_image(image: &RgbaImage, block_size: u32, mapper: &mut ColorMapper) -> Vec<Vec<u32>> {
+    let (width, height) = image.dimensions();
+    let row_bytes = width as usize * 4;
+    let mut matrix = Vec::with_capacity(height.div_ceil(block_size) as usize);
+    for y in (0..height).step_by(block_size as usize) {
+        let y_end = y.saturating_add(block_size).min(height);
+        let mut sums = vec![[0u64; 4]; width.div_ceil(block_size) as usize];
+        for row in image.as_raw()[y as usize * row_bytes..y_end as usize * row_bytes].chunks(row_bytes) {
+            for (sum, block) in sums.iter_mut().zip(row.chunks(block_size as usize * 4)) {
+                let block_sum = sum_rgba(block);
+                for c in 0..4 {
+                    sum[c] += block_sum[c];
+                }
+            }
+        }
+        let row = sums
+            .iter()
+            .enumerate()
+            .map(|(bx, &sum)| {
+                let x = bx as u32 * block_size;
+                let count = (x.saturating_add(block_size).min(width) - x) as u64 * (y_end - y) as u64;
+                mapper.id_for(block_color(sum, count))
+            })
+            .collect();
+        matrix.push(row);
+    }
+    matrix
+}
+
+/// Rows of `matrix` whose width differs from the first row's.
+pub fn ragged_rows(matrix: &[Vec<u32>]) -> Vec<usize> {
+    let width = matrix.first().map_or(0, Vec::len);
+    matrix.iter().enumerate().filter(|(_, row)| row.len() != width).map(|(y, _)| y).collect()
+}
+
+/// Paint every cell of `matrix` with its color. IDs missing from `colors` become transparent
+/// and are passed to `missing`. Ragged matrices, whose rows differ in width, are refused.
+pub fn render(matrix: &[Vec<u32>], colors: &HashMap<u32, String>, mut missing: impl FnMut(u32)) -> Result<RgbaImage, PixelError> {
+    if matrix.is_empty() {
+        return Err(PixelError::Parse("Matrix is empty".to_string()));
+    }
+    let ragged = ragged_rows(matrix);
+    if !ragged.is_empty() {
+        let rows: Vec<String> = ragged.iter().map(usize::to_string).collect();
+        return Err(PixelError::Parse(format!("Matrix is ragged: rows {} differ in width from row 0", rows.join(", "))));
+    }
+    let (Ok(width), Ok(height)) = (u32::try_from(matrix[0].len()), u32::try_from(matrix.len())) else {
+        return Err(PixelError::InvalidArgument(format!("Matrix of {}x{} cells is too large for an image", matrix[0].len(), matrix.len())));
+    };
+    if (width as u64 * height as u64).checked_mul(4).is_none_or(|bytes| bytes > isize::MAX as u64) {
+        return Err(PixelError::InvalidArgument(format!("Matrix of {}x{} cells is too large for an image", width, height)));
+    }
+    let mut img = RgbaImage::new(width, height);
+    for (y, row) in matrix.iter().enumerate() {
+        for (x, &id) in row.iter().enumerate() {
+            let color = match colors.get(&id) {
+                Some(hex) => hex_to_rgba(hex)?,
+                None => {
+                    missing(id);
+                    Rgba([0, 0, 0, 0])
+                }
+            };
+            img.put_pixel(x as u32, y as u32, color);
+        }
+    }
+    Ok(img)
+}
+
+/// Decode an image (PNG, JPEG, GIF, ...) and map it over `block_size` blocks.
+pub fn map_encoded(bytes: &[u8], block_size: u32, tolerance: f64) -> Result<Output, PixelError> {
+    if block_size == 0 {
+        return Err(PixelError::InvalidArgument("Block size must be greater than 0".to_string()));
+    }
+    let image = image::load_from_memory(bytes)?.to_rgba8();
+    let mut mapper = ColorMapper::new(tolerance);
+    let matrix = map_image(&image, block_size, &mut mapper);
+    Ok(Output { matrix, colors: mapper.id_to_color })
+}
+
+/// Paint `map` into an encoded PNG.
+pub fn render_png(map: &Output) -> Result<Vec<u8>, PixelError> {
+    let mut png = Cursor::new(Vec::new());
+    render(&map.matrix, &map.colors, |_| {})?.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
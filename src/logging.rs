@@ -0,0 +1,28 @@
+use clap::ValueEnum;
+use std::io::IsTerminal;
+use tracing::Level;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+/// Install the global logger on stderr, colored only on a terminal. Warnings and summaries
+/// show by default, only warnings when `quiet`; each `-v` adds a level (debug, then trace).
+pub fn init(verbose: u8, quiet: bool, format: LogFormat) {
+    let level = match verbose {
+        0 if quiet => Level::WARN,
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let builder = tracing_subscriber::fmt().with_writer(std::io::stderr).with_ansi(std::io::stderr().is_terminal()).with_max_level(level).with_target(false);
+    match format {
+        LogFormat::Text => builder.without_time().init(),
+        LogFormat::Json => builder.json().with_current_span(false).init(),
+    }
+}
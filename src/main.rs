@@ -1,51 +1,261 @@
-use clap::{Parser, Subcommand};
-use image::{GenericImageView, ImageBuffer, Pixel, Rgba, RgbaImage};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+mod adjust;
+mod animation;
+mod aseprite;
+mod batch;
+mod beads;
+mod bench;
+mod bundle;
+mod cache;
+mod chart;
+mod collision;
+mod config;
+mod cycle;
+mod daemon;
+mod denoise;
+mod depth;
+mod diff;
+mod dmc;
+mod dryrun;
+mod edge_aware;
+mod edges;
+mod edit;
+mod export;
+#[cfg(feature = "faces")]
+mod faces;
+mod failure;
+mod gameboy;
+mod godot;
+mod grid;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod hex;
+mod lego;
+mod logging;
+mod mapped;
+mod materials;
+mod matrix;
+mod nes;
+mod notify;
+mod onion;
+mod overwrite;
+mod palette;
+mod pdf;
+mod physical;
+mod pico8;
+mod pipeline;
+mod posterize;
+mod preset;
+mod preview;
+mod progress;
+mod ramps;
+mod redact;
+mod remote;
+mod repair;
+mod rubik;
+mod script;
+mod segment;
+mod serve;
+mod shadow;
+mod sheet;
+mod slic;
+mod stitch;
+mod stream;
+mod style;
+mod svg;
+mod swaps;
+mod text;
+mod tiled;
+mod tiles;
+mod tileset;
+mod transform;
+mod triangle;
+mod tune;
+mod tween;
+mod unity;
+mod validate;
+#[cfg(feature = "video")]
+mod video;
+mod voronoi;
+mod watch;
+mod wizard;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use image::metadata::Orientation;
+use image::{DynamicImage, Rgba, RgbaImage};
+use failure::{ErrorFormat, Kind};
+use indicatif::ProgressBar;
+use logging::LogFormat;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use adjust::Adjustments;
+use cache::BlockCache;
+use chart::Pagination;
+use cycle::Cycle;
+use denoise::Denoise;
+use depth::Depth;
+use edges::{Blocks, Edges};
+use grid::Grid;
+use matrix::Matrix;
+use overwrite::OverwriteOptions;
+use pixel::{ColorMapper, Output, PixelError, color_distance_sq, hex_to_rgba, rgba_to_hex, sum_rgba};
+use physical::Sizing;
+use preset::Preset;
+use redact::Region;
+use repair::Repair;
+use segment::{SegmentMap, SegmentOptions, Segmentation};
+use shadow::Offset;
+use sheet::SheetSpec;
+use stream::{ImageRows, RowSource};
+use style::StyleOptions;
+use text::TextOptions;
+use tiles::TileSize;
+use tracing::{debug, error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Don't show progress bars or summaries, only warnings and errors
+    #[arg(long, global = true)]
+    quiet: bool,
+
+    /// Log more detail (-v debug, -vv trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Format of log lines on stderr
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: LogFormat,
+
+    /// Format of the final error message on stderr
+    #[arg(long, global = true, value_enum, default_value_t)]
+    error_format: ErrorFormat,
+
+    #[command(flatten)]
+    overwrite: OverwriteOptions,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Pixelate an image with a specific block size
     Pixelate {
-        /// Path to the input image
-        #[arg(short, long)]
-        input: PathBuf,
+        /// Paths, glob patterns or http(s)/s3 URLs of the input images
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<PathBuf>,
 
-        /// Pixel block size
-        #[arg(short, long, default_value_t = 10)]
-        block_size: u32,
+        /// Pixel block size, or a percentage of each image's smaller side (`5%`)
+        #[arg(short, long, default_value = "10")]
+        block_size: BlockSize,
+
+        /// Pick the block size of each image to make it at most this many cells wide
+        #[arg(long, value_name = "CELLS", conflicts_with = "block_size")]
+        target_width: Option<u32>,
 
-        /// Optional path to output file
+        /// Pick the block size of each image to make it at most this many cells tall
+        #[arg(long, value_name = "CELLS", conflicts_with = "block_size")]
+        target_height: Option<u32>,
+
+        /// Paths to output files; repeat for several, chosen by extension: the JSON map, an
+        /// image of it (`.png`, `.gif`, ...) or an SVG. Prints the JSON to stdout if not provided
         #[arg(short, long)]
-        output: Option<PathBuf>,
+        output: Vec<PathBuf>,
 
         /// Color grouping tolerance (0.0 to ~510.0)
         #[arg(short, long, default_value_t = 0.0)]
         tolerance: f64,
+
+        #[command(flatten)]
+        options: ProcessOptions,
     },
     /// Map every single pixel of the image to its color ID
     Map {
-        /// Path to the input image
+        /// Paths, glob patterns or http(s)/s3 URLs of the input images
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<PathBuf>,
+
+        /// Paths to output files; repeat for several, chosen by extension: the JSON map, an
+        /// image of it (`.png`, `.gif`, ...) or an SVG. Prints the JSON to stdout if not provided
         #[arg(short, long)]
-        input: PathBuf,
+        output: Vec<PathBuf>,
+
+        /// Color grouping tolerance (0.0 to ~510.0)
+        #[arg(short, long, default_value_t = 0.0)]
+        tolerance: f64,
+
+        #[command(flatten)]
+        options: ProcessOptions,
+    },
+    /// Reduce each color channel of an image to a few levels, for a quick stylized look without
+    /// clustering a palette; image outputs are the posterized image, JSON outputs its map
+    #[command(mut_arg("input_levels", |arg| arg.long("input-levels")))]
+    Posterize {
+        /// Paths, glob patterns or http(s)/s3 URLs of the input images
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<PathBuf>,
 
-        /// Optional path to output file
+        /// Paths to output files; repeat for several, chosen by extension: the JSON map, an
+        /// image (`.png`, `.gif`, ...) or an SVG. Prints the JSON to stdout if not provided
         #[arg(short, long)]
-        output: Option<PathBuf>,
+        output: Vec<PathBuf>,
+
+        /// Levels per channel, from 2 to 255
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(2..))]
+        levels: u8,
+
+        /// Block size in pixels to average before posterizing, or a percentage of the smaller side
+        #[arg(short, long, default_value = "1")]
+        block_size: BlockSize,
 
         /// Color grouping tolerance (0.0 to ~510.0)
         #[arg(short, long, default_value_t = 0.0)]
         tolerance: f64,
+
+        #[command(flatten)]
+        options: ProcessOptions,
+    },
+    /// Pixelate only some areas of an image, such as faces or license plates, and leave the
+    /// rest of it untouched
+    Redact {
+        /// Paths to the input images
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<PathBuf>,
+
+        /// Path to the output image
+        #[arg(short, long, required_unless_present = "out_dir")]
+        output: Option<PathBuf>,
+
+        /// Write each redacted image into this directory under its own file name
+        #[arg(long, value_name = "DIR", conflicts_with = "output")]
+        out_dir: Option<PathBuf>,
+
+        /// Block size in pixels
+        #[arg(short, long, default_value_t = 16)]
+        block_size: u32,
+
+        /// Area to pixelate as x,y,w,h in pixels; repeat for several
+        #[arg(long, value_name = "X,Y,W,H", required_unless_present_any = ["mask", "detect_faces"])]
+        region: Vec<Region>,
+
+        /// Image the size of the input whose white parts are pixelated
+        #[arg(long, value_name = "FILE")]
+        mask: Option<PathBuf>,
+
+        /// Also pixelate the faces found in each image (needs the `faces` feature)
+        #[arg(long, requires = "face_model")]
+        detect_faces: bool,
+
+        /// SeetaFace model for --detect-faces, such as `seeta_fd_frontal_v1.0.bin` from rustface
+        #[arg(long, value_name = "FILE", env = "PIXEL_FACE_MODEL")]
+        face_model: Option<PathBuf>,
     },
     /// Reconstruct an image from a JSON output file
     Reconstruct {
@@ -53,228 +263,1708 @@ enum Commands {
         #[arg(short, long)]
         input: PathBuf,
 
+        /// Path to the output image, or `.svg` for square and triangle maps; maps with frames
+        /// become an animated GIF, PNG or WebP
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Play frames at this rate instead of with their recorded delays
+        #[arg(long)]
+        fps: Option<f64>,
+
+        /// Even out ragged matrices, whose rows differ in width, instead of failing
+        #[arg(long, value_enum)]
+        repair: Option<Repair>,
+
+        #[command(flatten)]
+        style: StyleOptions,
+    },
+    /// Derive a collision grid from a map's colors
+    Collision {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Color IDs of solid cells
+        #[arg(long, value_delimiter = ',', required_unless_present = "solid_by", conflicts_with = "solid_by")]
+        solid_ids: Vec<u32>,
+
+        /// Pick solid cells by a property of their color instead of by ID
+        #[arg(long, value_enum)]
+        solid_by: Option<collision::SolidBy>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t)]
+        format: collision::CollisionFormat,
+    },
+    /// Answer JSON-RPC requests (map, reconstruct, preview) on stdin and stdout, for editor
+    /// plugins; decoded images are cached between requests
+    Daemon {
+        /// Keep map and reconstruct results in this directory, by input content and options,
+        /// so repeated requests are answered instantly, also in later runs
+        #[arg(long, value_name = "DIR")]
+        cache_dir: Option<PathBuf>,
+    },
+    /// Compare two maps cell by cell by color and list the changed cells
+    Diff {
+        /// Path to the earlier map (JSON) or image
+        #[arg(short, long)]
+        before: PathBuf,
+
+        /// Path to the later map (JSON) or image
+        #[arg(short, long)]
+        after: PathBuf,
+
+        /// Show both maps side by side in the terminal, with unchanged cells dimmed
+        #[arg(long)]
+        preview: bool,
+    },
+    /// Edit a map's cells in the terminal
+    Edit {
+        /// Path to the JSON file to edit and save
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Convert a JSON map for use in other tools
+    Export {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the main output file; companion files are written next to it
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Format to export to
+        #[arg(short, long, value_enum)]
+        format: export::ExportFormat,
+
+        /// Tile size in cells as N or WxH, for tile-based formats
+        #[arg(long, default_value = "16")]
+        tile_size: TileSize,
+
+        #[command(flatten)]
+        sizing: Sizing,
+
+        #[command(flatten)]
+        pagination: Pagination,
+
+        #[command(flatten)]
+        text: TextOptions,
+    },
+    /// List the threads, beads or bricks a map needs, with how many packs to buy
+    Materials {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output file; prints to stdout if not provided
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Catalog to match colors to
+        #[arg(short, long, value_enum)]
+        catalog: materials::Catalog,
+
+        /// Items per pack, instead of the catalog's usual pack size
+        #[arg(long)]
+        per_pack: Option<usize>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t)]
+        format: materials::ListFormat,
+    },
+    /// Overlay two frames of a map with frames, to check the motion between them
+    Onion {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// The two frames to overlay, counting from 0; the second is drawn on top
+        #[arg(long, required = true, value_name = "A,B", value_delimiter = ',')]
+        frames: Vec<usize>,
+
         /// Path to the output image
         #[arg(short, long)]
         output: PathBuf,
+
+        /// Tint of the first frame (RRGGBBAA); the alpha sets how strongly it is applied
+        #[arg(long, default_value = "ff000080", value_parser = palette::parse_color)]
+        before_tint: Rgba<u8>,
+
+        /// Tint of the second frame (RRGGBBAA)
+        #[arg(long, default_value = "0000ff80", value_parser = palette::parse_color)]
+        after_tint: Rgba<u8>,
+
+        /// Opacity of the second frame over the first (0.0 to 1.0)
+        #[arg(long, default_value_t = 0.5)]
+        opacity: f32,
+    },
+    /// Draw a hard drop shadow under the opaque cells of a map, growing the canvas where it
+    /// falls outside
+    Shadow {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output JSON file, or an image of the result (`.png`, `.gif`, ...)
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Cells to move the shadow right and down by; negative values move it left and up
+        #[arg(long, value_name = "DX,DY", default_value = "1,1", allow_hyphen_values = true)]
+        offset: Offset,
+
+        /// Shadow color (RRGGBB or RRGGBBAA)
+        #[arg(long, default_value = "000000", value_parser = palette::parse_color)]
+        color: Rgba<u8>,
+
+        /// Opacity of the shadow (0.0 to 1.0)
+        #[arg(long, default_value_t = 0.5)]
+        opacity: f32,
+    },
+    /// Render a map under alternative palettes side by side in one image, to compare recolors
+    Swaps {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Palette files (GIMP `.gpl` or one hex color per line), one render each; their
+        /// colors replace the map's opaque colors in ID order. The first render is the original
+        #[arg(short, long = "palette", required = true)]
+        palettes: Vec<PathBuf>,
+
+        /// Path to the output image
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Frame to render from a map with frames, counting from 0
+        #[arg(long, default_value_t = 0)]
+        frame: usize,
+
+        /// Renders per row [default: all in one row]
+        #[arg(long)]
+        cols: Option<u32>,
+
+        /// Transparent pixels between renders
+        #[arg(long, default_value_t = 4)]
+        spacing: u32,
+
+        #[command(flatten)]
+        style: StyleOptions,
+    },
+    /// Make a dark-to-light shading ramp for each color of a map's palette
+    Ramps {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output palette: GIMP `.gpl`, or one hex color per line for other names
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Shades per ramp, the base color included
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(2..))]
+        steps: u8,
+
+        /// Also draw the ramps into this image, one per row
+        #[arg(long, value_name = "FILE")]
+        swatch: Option<PathBuf>,
+
+        /// Pixels per side of each swatch square
+        #[arg(long, default_value_t = 16)]
+        scale: u32,
+    },
+    /// Insert in-between frames into a map with frames, using only colors already in its palette
+    Tween {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output JSON file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Number of frames to insert between each pair of frames
+        #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(..=tween::MAX_STEPS as i64))]
+        steps: u32,
+
+        /// Only tween from frame A to frame B (counting from 0), dropping the other frames
+        #[arg(long, value_name = "A,B", value_delimiter = ',')]
+        frames: Option<Vec<usize>>,
+    },
+    /// Animate a single map by rotating the colors of ranges of IDs, for classic palette-cycling
+    /// effects such as waterfalls and fire
+    Cycle {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output GIF, PNG (APNG) or WebP
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// IDs to rotate as A-B, moving each color one ID up per frame (down when A is above B);
+        /// A-B@N moves only every N frames. Repeat for several ranges
+        #[arg(short, long = "cycle", value_name = "A-B[@N]", required = true)]
+        cycles: Vec<Cycle>,
+
+        /// Frames per second
+        #[arg(long, default_value_t = 10.0)]
+        fps: f64,
+
+        /// Number of frames to write, at most 1000 [default: until every range is back at its
+        /// start]
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=cycle::MAX_FRAMES as i64))]
+        frames: Option<u32>,
+
+        #[command(flatten)]
+        style: StyleOptions,
+    },
+    /// Turn a map into a printable cross-stitch or diamond painting pattern matched to DMC colors
+    Stitch {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output PDF
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Kind of pattern to make
+        #[arg(long, value_enum, default_value_t)]
+        mode: stitch::StitchMode,
+
+        #[command(flatten)]
+        sizing: Sizing,
+
+        #[command(flatten)]
+        pagination: Pagination,
+    },
+    /// Show a map or image in the terminal
+    Preview {
+        /// Path to a JSON map or an image
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Maximum width in characters, or pixels for Sixel [default: the terminal's width]
+        #[arg(long)]
+        width: Option<u32>,
+
+        /// Kind of terminal graphics to output
+        #[arg(long, value_enum, default_value_t)]
+        terminal: preview::Terminal,
+
+        /// Use ordered dithering toward the palette of `xterm256` and `ansi16` terminals
+        #[arg(long)]
+        dither: bool,
+    },
+    /// Serve map, pixelate and reconstruct over HTTP
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        /// POST a JSON summary of each answered request (endpoint, status, size, error) to
+        /// this URL
+        #[arg(long, value_name = "URL", conflicts_with = "grpc")]
+        notify_url: Option<String>,
+
+        /// Serve the streaming gRPC API of `proto/pixel.proto` instead (needs the `grpc` feature)
+        #[arg(long)]
+        grpc: bool,
+    },
+    /// Cut a map into tiles, storing repeated tiles once in a tileset image
+    Tileset {
+        /// Path to the input JSON file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output JSON file with the tile index matrix; the tileset image is written
+        /// next to it as `<stem>-tiles.png`
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Tile size in cells as N or WxH
+        #[arg(long, default_value = "16")]
+        tile_size: TileSize,
+
+        /// Also match tiles that are mirrored horizontally or vertically
+        #[arg(long)]
+        flips: bool,
+    },
+    /// Try block sizes and tolerances on an image with a live preview, then print the command
+    Tune {
+        /// Path to the input image
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Block size to start from
+        #[arg(short, long, default_value_t = 10)]
+        block_size: u32,
+
+        /// Tolerance to start from
+        #[arg(short, long, default_value_t = 0.0)]
+        tolerance: f64,
+    },
+    /// Check a map file for problems, listing all of them
+    Validate {
+        /// Path to the JSON map to check
+        #[arg(required_unless_present = "schema")]
+        input: Option<PathBuf>,
+
+        /// Print the JSON Schema of map files instead
+        #[arg(long, conflicts_with = "input")]
+        schema: bool,
+    },
+    /// Re-map an image every time it is saved
+    Watch {
+        /// Path to the input image
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Pixel block size (1 maps every pixel)
+        #[arg(short, long, default_value_t = 1)]
+        block_size: u32,
+
+        /// Path to the output file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Color grouping tolerance (0.0 to ~510.0)
+        #[arg(short, long, default_value_t = 0.0)]
+        tolerance: f64,
+
+        #[command(flatten)]
+        options: ProcessOptions,
+    },
+    /// Run the steps of a pipeline file over its inputs
+    Run {
+        /// Path to the pipeline TOML file
+        pipeline: PathBuf,
+    },
+    /// Save, list or delete named presets of options
+    Preset {
+        #[command(subcommand)]
+        action: bundle::PresetAction,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page, or write one page per subcommand into a directory
+    Manpage {
+        /// Directory to write `pixel.1`, `pixel-map.1`, ... into
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Measure mapping throughput on synthetic images
+    Bench {
+        /// Image sizes (square side in pixels)
+        #[arg(long, value_delimiter = ',', default_values_t = [256, 1024, 2048])]
+        sizes: Vec<u32>,
+
+        /// Number of distinct colors in each image
+        #[arg(long, value_delimiter = ',', default_values_t = [16, 256, 4096])]
+        palettes: Vec<u32>,
+
+        /// Block sizes to pixelate with
+        #[arg(short, long, value_delimiter = ',', default_values_t = [1, 8])]
+        block_sizes: Vec<u32>,
+
+        /// Color grouping tolerance (0.0 to ~510.0)
+        #[arg(short, long, default_value_t = 0.0)]
+        tolerance: f64,
+
+        /// Runs per case; the fastest is reported
+        #[arg(short = 'n', long, default_value_t = 3)]
+        iterations: u32,
     },
 }
 
-#[derive(Serialize, Deserialize)]
-struct Output {
-    matrix: Vec<Vec<u32>>,
+/// Options shared by the commands that map images
+#[derive(Args, Clone, Debug)]
+struct ProcessOptions {
+    /// Decode the input in strips to keep memory low (PNG/TIFF)
+    #[arg(long)]
+    low_memory: bool,
+
+    /// Spill the matrix to a temporary file every N rows instead of keeping it in memory
+    #[arg(long, value_name = "N")]
+    tile_rows: Option<usize>,
+
+    /// Write one `<name>.json` per input into this directory
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    out_dir: Option<PathBuf>,
+
+    /// Walk input directories for images, mirroring their layout under --out-dir and
+    /// skipping files unchanged since the last run
+    #[arg(short, long, requires = "out_dir")]
+    recursive: bool,
+
+    /// Only report what would be produced (dimensions, matrix size, palette bound,
+    /// output size) from the image headers, without processing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Without --output, write `<input>_pixelated_<block size>.json` and `.png` next to each
+    /// input instead of printing the JSON
+    #[arg(long, conflicts_with_all = ["output", "out_dir"])]
+    save_default: bool,
+
+    /// Number of files to process at once in batch runs
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+
+    /// Directory for per-block hashes; unchanged blocks keep their IDs from the previous run
+    #[arg(long, value_name = "DIR")]
+    cache: Option<PathBuf>,
+
+    /// Carry color IDs from each input to the next, in input order, so a color keeps its ID
+    /// across a sequence of frames; each output lists the colors seen so far
+    #[arg(long, requires = "out_dir", conflicts_with_all = ["recursive", "jobs", "cache"])]
+    shared_palette: bool,
+
+    /// How to treat blocks at the edges when the image isn't a multiple of the block size;
+    /// recorded in the output as `edges` unless `average`
+    #[arg(long, value_enum, default_value_t)]
+    edges: Edges,
+
+    /// Shape of the cells, recorded in the output as `grid` unless square; image outputs of
+    /// hex and triangle maps draw cells as wide as the block size
+    #[arg(long, value_enum, default_value_t, conflicts_with_all = ["edges", "edge_aware", "depth", "tile_rows", "cache"])]
+    grid: Grid,
+
+    #[command(flatten)]
+    segment: SegmentOptions,
+
+    /// Bits per channel to average and write colors with; 16 keeps the precision of 16-bit PNG
+    /// and TIFF inputs, recorded in the output as `depth`
+    #[arg(long, value_enum, default_value_t, conflicts_with_all = ["low_memory", "tile_rows", "cache", "shared_palette", "cluster", "preset", "edge_aware"])]
+    depth: Depth,
+
+    /// Average blocks split by a strong edge over their larger side only, keeping silhouettes
+    /// crisp instead of giving border blocks a blend of both sides
+    #[arg(long)]
+    edge_aware: bool,
+
+    /// Collect every color first and merge those within --tolerance around frequency-weighted
+    /// centroids, instead of around the first color seen, so results don't depend on scan order
+    #[arg(long, conflicts_with_all = ["shared_palette", "cache"])]
+    cluster: bool,
+
+    /// Snap colors to a platform's fixed palette and check its size limits. The name of a
+    /// preset saved with `pixel preset save` sets its options instead, for any command
+    #[arg(long, value_enum)]
+    preset: Option<Preset>,
+
+    /// Also write the result into the `__gfx__` section of this PICO-8 cartridge (`.p8`),
+    /// creating it if needed
+    #[arg(long, value_name = "FILE", requires = "preset")]
+    cart: Option<PathBuf>,
+
+    /// Also write a shell script that prints the result with terminal escape codes, e.g. as a
+    /// MOTD banner; needs `--preset xterm256` or `ansi16`
+    #[arg(long, value_name = "FILE", requires = "preset")]
+    script: Option<PathBuf>,
+
+    /// Also lay the frames out in a sprite sheet, `<name>-sheet.png`, with a frame index
+    /// (position, size, duration) in `<name>-sheet.json`
+    #[arg(long, value_name = "cols=N")]
+    sheet: Option<SheetSpec>,
+
+    #[command(flatten)]
+    sizing: Sizing,
+
+    /// Store each frame after the first as the cells that changed since the previous one
+    #[arg(long)]
+    delta: bool,
+
+    /// POST a JSON summary of the batch (files processed, failures, outputs written) to this
+    /// URL once it finishes
+    #[arg(long, value_name = "URL", requires = "out_dir")]
+    notify_url: Option<String>,
+
+    /// Map photos as stored instead of turning them upright by their EXIF orientation
+    #[arg(long)]
+    no_auto_orient: bool,
+
+    /// Filter noise out of the input before mapping, so JPEG artifacts and dust don't turn into
+    /// one-off colors; `median:3` takes the median of each 3x3 window
+    #[arg(long, value_name = "median:SIZE")]
+    denoise: Option<Denoise>,
+
+    #[command(flatten)]
+    adjust: Adjustments,
+
+    /// Reduce each color channel to this many levels before mapping, as `pixel posterize` does
+    #[arg(long, value_name = "LEVELS", value_parser = clap::value_parser!(u8).range(2..))]
+    posterize: Option<u8>,
+
+    /// Frames per second to sample video inputs at
+    #[cfg(feature = "video")]
+    #[arg(long, default_value_t = 10.0)]
+    fps: f64,
+}
+
+impl Commands {
+    /// Files the command is asked to write, to check before overwriting them. Outputs of
+    /// `--out-dir` batches and `watch` are rewritten on purpose and left out, and dry runs
+    /// write nothing.
+    fn outputs(&self) -> Vec<PathBuf> {
+        match self {
+            Commands::Pixelate { output, options, .. } | Commands::Map { output, options, .. } | Commands::Posterize { output, options, .. } if !options.dry_run => {
+                let sheet = output.first().filter(|_| options.sheet.is_some());
+                output.iter().cloned().chain(sheet.into_iter().flat_map(|path| companions(path, &["-sheet.png", "-sheet.json"]))).collect()
+            }
+            Commands::Export { output, format, .. } => [output.clone()].into_iter().chain(companions(output, format.companions())).collect(),
+            Commands::Tileset { output, .. } => [output.clone()].into_iter().chain(companions(output, &["-tiles.png"])).collect(),
+            Commands::Reconstruct { output, .. }
+            | Commands::Collision { output, .. }
+            | Commands::Onion { output, .. }
+            | Commands::Shadow { output, .. }
+            | Commands::Swaps { output, .. }
+            | Commands::Tween { output, .. }
+            | Commands::Cycle { output, .. }
+            | Commands::Stitch { output, .. } => vec![output.clone()],
+            Commands::Materials { output, .. } | Commands::Redact { output, .. } => output.iter().cloned().collect(),
+            Commands::Ramps { output, swatch, .. } => [Some(output), swatch.as_ref()].into_iter().flatten().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Files written next to `output`, named after its stem followed by each of `endings`.
+fn companions<'a>(output: &'a Path, endings: &'a [&str]) -> impl Iterator<Item = PathBuf> + 'a {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    endings.iter().map(move |ending| output.with_file_name(format!("{}{}", stem, ending)))
+}
+
+/// Contents of a map file: a single matrix, or one per frame of an animation.
+#[derive(Deserialize)]
+struct MapFile {
+    matrix: Option<Vec<Vec<u32>>>,
+    frames: Option<Vec<animation::FrameMap>>,
     colors: HashMap<u32, String>,
+    #[serde(default)]
+    grid: Grid,
+    #[serde(default)]
+    segmentation: Option<Segmentation>,
 }
 
-fn color_distance(c1: &Rgba<u8>, c2: &Rgba<u8>) -> f64 {
-    let r_diff = c1[0] as f64 - c2[0] as f64;
-    let g_diff = c1[1] as f64 - c2[1] as f64;
-    let b_diff = c1[2] as f64 - c2[2] as f64;
-    let a_diff = c1[3] as f64 - c2[3] as f64;
-    (r_diff * r_diff + g_diff * g_diff + b_diff * b_diff + a_diff * a_diff).sqrt()
-}
-
-fn process_image(input_path: &PathBuf, block_size: u32, output_path: Option<&PathBuf>, tolerance: f64) -> Result<(), Box<dyn std::error::Error>> {
-    let img = image::open(input_path)?;
-    let (width, height) = img.dimensions();
-
-    let mut matrix: Vec<Vec<u32>> = Vec::new();
-    let mut color_to_id: HashMap<String, u32> = HashMap::new();
-    let mut id_to_color: HashMap<u32, String> = HashMap::new();
-    // Cache of canonical colors for fuzzy matching: (ID, RGBA)
-    let mut palette: Vec<(u32, Rgba<u8>)> = Vec::new();
-
-    // Reserve ID 0 for fully transparent
-    let transparent_hex = "#00000000".to_string();
-    color_to_id.insert(transparent_hex.clone(), 0);
-    id_to_color.insert(0, transparent_hex);
-
-    let mut next_id = 1;
-
-    for y in (0..height).step_by(block_size as usize) {
-        let mut row: Vec<u32> = Vec::new();
-        for x in (0..width).step_by(block_size as usize) {
-            let r: u8;
-            let g: u8;
-            let b: u8;
-            let a: u8;
-
-            if block_size > 1 {
-                let mut r_sum: u64 = 0;
-                let mut g_sum: u64 = 0;
-                let mut b_sum: u64 = 0;
-                let mut a_sum: u64 = 0;
-                let mut count: u64 = 0;
-
-                let x_end = (x + block_size).min(width);
-                let y_end = (y + block_size).min(height);
-
-                for by in y..y_end {
-                    for bx in x..x_end {
-                        let pixel = img.get_pixel(bx, by);
-                        let rgba = pixel.to_rgba();
-                        r_sum += rgba[0] as u64;
-                        g_sum += rgba[1] as u64;
-                        b_sum += rgba[2] as u64;
-                        a_sum += rgba[3] as u64;
-                        count += 1;
-                    }
-                }
-                
-                let avg_a = (a_sum / count) as u8;
-                if avg_a == 0 {
-                    r = 0;
-                    g = 0;
-                    b = 0;
-                    a = 0;
-                } else {
-                    r = (r_sum / count) as u8;
-                    g = (g_sum / count) as u8;
-                    b = (b_sum / count) as u8;
-                    a = avg_a;
-                }
-            } else {
-                let pixel = img.get_pixel(x, y);
-                let rgba = pixel.to_rgba();
-                if rgba[3] == 0 {
-                    r = 0;
-                    g = 0;
-                    b = 0;
-                    a = 0;
-                } else {
-                    r = rgba[0];
-                    g = rgba[1];
-                    b = rgba[2];
-                    a = rgba[3];
+/// How a map was made, as recorded in it, for commands that write a changed copy of a map.
+#[derive(Deserialize)]
+struct MapMeta {
+    edges: Option<serde_json::Value>,
+    depth: Option<serde_json::Value>,
+    grid: Option<serde_json::Value>,
+}
+
+impl MapMeta {
+    /// The recorded fields, in the order `pixelate` writes them.
+    fn fields(self) -> Vec<(&'static str, serde_json::Value)> {
+        [("edges", self.edges), ("depth", self.depth), ("grid", self.grid)].into_iter().filter_map(|(key, value)| Some((key, value?))).collect()
+    }
+}
+
+/// Frames of an animated or (with the `video` feature) video input; `None` for still images.
+#[cfg_attr(not(feature = "video"), allow(unused_variables))]
+fn open_frames(input_path: &Path, options: &ProcessOptions) -> Result<Option<animation::Frames>, Box<dyn std::error::Error>> {
+    #[cfg(feature = "video")]
+    if video::is_video(input_path) {
+        return video::frames(input_path, options.fps).map(Some);
+    }
+    animation::frames(input_path)
+}
+
+fn open_rows(input_path: &Path, low_memory: bool, auto_orient: bool) -> Result<Box<dyn RowSource>, Box<dyn std::error::Error>> {
+    // Images too large to decode at once are read in strips without asking
+    let (width, height) = image::ImageReader::open(input_path)?.with_guessed_format()?.into_dimensions()?;
+    let large = mapped::too_large(width, height);
+    if mapped::too_large(width, 1) {
+        return Err(failure::bad_input(format!("{} is {}x{}, too wide to read even a row at a time", input_path.display(), width, height)));
+    }
+    if low_memory || large {
+        // Strips come as stored; turning the image upright takes all of it
+        let turned = auto_orient && mapped::orientation(input_path)? != Orientation::NoTransforms;
+        match stream::open_streaming(input_path)? {
+            Some(source) if !turned => {
+                if !low_memory {
+                    info!(input = %input_path.display(), width, height, "too large to decode at once, reading it in strips");
                 }
+                return Ok(source);
             }
+            _ if low_memory => warn!(input = %input_path.display(), "can't be decoded in strips, loading it fully"),
+            _ => {}
+        }
+    }
+    Ok(Box::new(ImageRows::new(mapped::open_image(input_path, auto_orient)?)))
+}
 
-            let current_rgba = Rgba([r, g, b, a]);
-            let hex_color = format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a);
+// Largest block side; padded blocks this wide still sum 16-bit channels within a u64
+const MAX_BLOCK_SIZE: u32 = 65535;
 
-            // 1. Try exact match
-            let id = if let Some(&existing_id) = color_to_id.get(&hex_color) {
-                existing_id
-            } else {
-                // 2. Try fuzzy match (if tolerance > 0 and not transparent)
-                let mut found_id = None;
-                if tolerance > 0.0 && a > 0 {
-                    for (pid, p_color) in &palette {
-                        if color_distance(&current_rgba, p_color) <= tolerance {
-                            found_id = Some(*pid);
-                            break;
-                        }
-                    }
+/// Block size of a run: the same for every image, or fitted to each one.
+#[derive(Clone, Copy, Debug)]
+enum BlockSize {
+    Fixed(u32),
+    /// Percentage of the image's smaller side
+    Percent(f64),
+    /// Smallest block size that brings the image within this many cells across and down
+    Fit { width: Option<u32>, height: Option<u32> },
+}
+
+impl FromStr for BlockSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.parse::<f64>() {
+                Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(BlockSize::Percent(percent)),
+                _ => Err(format!("Expected a percentage above 0% and up to 100%, got {}", s)),
+            },
+            None => s.parse().map(BlockSize::Fixed).map_err(|_| format!("Expected a block size in pixels or a percentage, got {}", s)),
+        }
+    }
+}
+
+impl BlockSize {
+    /// Block size for `input`, read from its header unless fixed; `auto_orient` swaps the sides
+    /// of images turned a quarter by their EXIF orientation.
+    fn for_input(self, input: &Path, auto_orient: bool) -> Result<u32, Box<dyn std::error::Error>> {
+        if let BlockSize::Fixed(block_size) = self {
+            return Ok(block_size);
+        }
+        #[cfg(feature = "video")]
+        let (width, height) = if video::is_video(input) { video::dimensions(input)? } else { image::ImageReader::open(input)?.with_guessed_format()?.into_dimensions()? };
+        #[cfg(not(feature = "video"))]
+        let (width, height) = image::ImageReader::open(input)?.with_guessed_format()?.into_dimensions()?;
+        let quarter = [Orientation::Rotate90, Orientation::Rotate270, Orientation::Rotate90FlipH, Orientation::Rotate270FlipH];
+        #[cfg(feature = "video")]
+        let turned = auto_orient && !video::is_video(input) && quarter.contains(&mapped::orientation(input)?);
+        #[cfg(not(feature = "video"))]
+        let turned = auto_orient && quarter.contains(&mapped::orientation(input)?);
+        let (width, height) = if turned { (height, width) } else { (width, height) };
+        let fit = |size: u32, target: Option<u32>| target.map_or(1, |target| size.div_ceil(target));
+        let block_size = match self {
+            BlockSize::Fixed(block_size) => block_size,
+            BlockSize::Percent(percent) => (width.min(height) as f64 * percent / 100.0).round() as u32,
+            BlockSize::Fit { width: target_width, height: target_height } => fit(width, target_width).max(fit(height, target_height)),
+        };
+        Ok(block_size.max(1))
+    }
+}
+
+/// Identifies the options an output in a recursive batch was produced with; anything that
+/// changes the output must be part of it.
+fn manifest_key(blocks: Blocks, tolerance: f64, depth: Depth, denoise: Option<Denoise>, adjust: &Adjustments, posterize: Option<u8>) -> String {
+    let mut key = format!("block_size={} tolerance={}", blocks.size, tolerance);
+    if blocks.edges != Edges::Average {
+        key += &format!(" edges={}", blocks.edges.name());
+    }
+    if blocks.edge_aware {
+        key += " edge_aware";
+    }
+    if blocks.grid != Grid::Square {
+        key += &format!(" grid={}", blocks.grid.name());
+    }
+    if depth == Depth::Sixteen {
+        key += " depth=16";
+    }
+    if let Some(denoise) = denoise {
+        key += &format!(" denoise={}", denoise.key());
+    }
+    if !adjust.is_identity() {
+        key += &format!(" {}", adjust.key());
+    }
+    if let Some(levels) = posterize {
+        key += &format!(" posterize={}", levels);
+    }
+    key
+}
+
+/// Map `inputs` into `outputs`, or into their `--save-default` or `--out-dir` files;
+/// `overwrite` guards the `--save-default` files, which are only known once inputs are.
+fn process_inputs(inputs: &[PathBuf], block_size: BlockSize, outputs: &[PathBuf], tolerance: f64, options: &ProcessOptions, overwrite: &OverwriteOptions, quiet: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let inputs = batch::expand_inputs(&remote::resolve(inputs, quiet)?, options.recursive, options.out_dir.as_deref())?;
+    if options.dry_run {
+        return dry_run(inputs, block_size, outputs, tolerance, options);
+    }
+    let Some(out_dir) = &options.out_dir else {
+        if options.save_default {
+            // Every file is checked before any is written
+            let jobs = inputs.iter().map(|input| Ok((input, block_size.for_input(&input.path, !options.no_auto_orient)?))).collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+            let outputs: Vec<PathBuf> = jobs.iter().flat_map(|(input, block_size)| default_outputs(&input.path, *block_size)).collect();
+            return overwrite.guard(&outputs, || {
+                for (input, block_size) in &jobs {
+                    process_image(&input.path, *block_size, &default_outputs(&input.path, *block_size), tolerance, options, None, quiet)?;
                 }
+                Ok(())
+            });
+        }
+        return match inputs.as_slice() {
+            [input] => process_image(&input.path, block_size.for_input(&input.path, !options.no_auto_orient)?, outputs, tolerance, options, None, quiet),
+            _ => Err(failure::bad_input("Multiple inputs need --out-dir")),
+        };
+    };
+
+    std::fs::create_dir_all(out_dir).map_err(failure::write)?;
+    let manifest = options.recursive.then(|| Mutex::new(batch::Manifest::load(out_dir)));
+    let shared = options.shared_palette.then(|| Mutex::new(ColorMapper::new(tolerance)));
 
-                if let Some(fid) = found_id {
-                    // Map this specific slightly-different hex to the existing ID for future speed
-                    color_to_id.insert(hex_color.clone(), fid);
-                    fid
-                } else {
-                    // New color
-                    let id = next_id;
-                    if id != 0 { // Should always be true as we start at 1
-                         palette.push((id, current_rgba));
+    // Returns whether the file was processed (false: skipped as unchanged)
+    let run = |input: &batch::Input, output: &PathBuf, quiet: bool| -> Result<bool, Box<dyn std::error::Error>> {
+        let block_size = block_size.for_input(&input.path, !options.no_auto_orient)?;
+        let key = manifest_key(Blocks { size: block_size, edges: options.edges, edge_aware: options.edge_aware, grid: options.grid }, tolerance, options.depth, options.denoise, &options.adjust, options.posterize);
+        if let Some(manifest) = &manifest
+            && manifest.lock().unwrap().is_current(input, output, &key)?
+        {
+            return Ok(false);
+        }
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut shared = shared.as_ref().map(|mapper| mapper.lock().unwrap());
+        process_image(&input.path, block_size, std::slice::from_ref(output), tolerance, options, shared.as_deref_mut(), quiet)?;
+        if let Some(manifest) = &manifest {
+            let mut manifest = manifest.lock().unwrap();
+            manifest.record(input, &key)?;
+            manifest.save(out_dir).map_err(failure::write)?;
+        }
+        Ok(true)
+    };
+
+    let jobs = batch::plan_outputs(inputs, out_dir, "json")?;
+    let threads = options.jobs.clamp(1, jobs.len().max(1));
+    // Row progress per file only makes sense when files run one at a time
+    let bar = progress::files(jobs.len() as u64, quiet || threads == 1);
+    let next = AtomicUsize::new(0);
+    let processed = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let failed_kind = Mutex::new(None);
+    let (outputs, failures) = (Mutex::new(Vec::new()), Mutex::new(Vec::new()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Some((input, output)) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    match run(input, output, quiet || threads > 1) {
+                        Ok(true) => {
+                            processed.fetch_add(1, Ordering::Relaxed);
+                            outputs.lock().unwrap().push(output.display().to_string());
+                        }
+                        Ok(false) => {}
+                        Err(e) => {
+                            bar.suspend(|| error!(input = %input.path.display(), "{}", e));
+                            failed.fetch_add(1, Ordering::Relaxed);
+                            failures.lock().unwrap().push(notify::Failure { input: input.path.display().to_string(), error: e.to_string() });
+                            let kind = failure::classify(e.as_ref());
+                            let mut merged = failed_kind.lock().unwrap();
+                            *merged = Some(merged.map_or(kind, |k: Kind| k.merge(kind)));
+                        }
                     }
-                    color_to_id.insert(hex_color.clone(), id);
-                    id_to_color.insert(id, hex_color);
-                    next_id += 1;
-                    id
+                    bar.inc(1);
                 }
-            };
+            });
+        }
+    });
+    bar.finish_and_clear();
+
+    let (processed, failed) = (processed.into_inner(), failed.into_inner());
+    info!(files = jobs.len(), processed, unchanged = jobs.len() - processed - failed, failed, "batch finished");
+    if let Some(url) = &options.notify_url {
+        let (mut outputs, mut failures) = (outputs.into_inner().unwrap(), failures.into_inner().unwrap());
+        // Threads finish in any order
+        outputs.sort();
+        failures.sort_by(|a, b| a.input.cmp(&b.input));
+        notify::send(url, &notify::BatchSummary { files: jobs.len(), processed, unchanged: jobs.len() - processed - failed, failed, outputs, failures });
+    }
+    if let Some(kind) = failed_kind.into_inner().unwrap() {
+        return Err(failure::tag(kind, format!("{} of {} files failed", failed, jobs.len())));
+    }
+    Ok(())
+}
 
-            row.push(id);
+fn dry_run(inputs: Vec<batch::Input>, block_size: BlockSize, outputs: &[PathBuf], tolerance: f64, options: &ProcessOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(out_dir) = &options.out_dir else {
+        if options.save_default {
+            for input in &inputs {
+                let block_size = block_size.for_input(&input.path, !options.no_auto_orient)?;
+                println!("{} -> {}: {}", input.path.display(), destinations(&default_outputs(&input.path, block_size)), dryrun::report(&input.path, block_size)?);
+            }
+            return Ok(());
         }
-        matrix.push(row);
+        let [input] = inputs.as_slice() else {
+            return Err(failure::bad_input("Multiple inputs need --out-dir"));
+        };
+        let destination = destinations(outputs);
+        println!("{} -> {}: {}", input.path.display(), destination, dryrun::report(&input.path, block_size.for_input(&input.path, !options.no_auto_orient)?)?);
+        return Ok(());
+    };
+
+    let mut manifest = options.recursive.then(|| batch::Manifest::load(out_dir));
+    let (mut files, mut cells, mut bytes) = (0, 0, 0);
+    for (input, output) in batch::plan_outputs(inputs, out_dir, "json")? {
+        let block_size = block_size.for_input(&input.path, !options.no_auto_orient)?;
+        let key = manifest_key(Blocks { size: block_size, edges: options.edges, edge_aware: options.edge_aware, grid: options.grid }, tolerance, options.depth, options.denoise, &options.adjust, options.posterize);
+        if let Some(manifest) = &mut manifest
+            && manifest.is_current(&input, &output, &key)?
+        {
+            println!("{} -> {}: unchanged, would be skipped", input.path.display(), output.display());
+            continue;
+        }
+        let report = dryrun::report(&input.path, block_size)?;
+        println!("{} -> {}: {}", input.path.display(), output.display(), report);
+        files += 1;
+        cells += report.cells();
+        bytes += report.estimated_bytes;
+    }
+    println!("{} files to process, {} cells, ~{} of JSON", files, cells, dryrun::format_bytes(bytes));
+    Ok(())
+}
+
+/// Map one input and write its JSON; `shared` replaces the input's own mapper to carry IDs
+/// over from previous inputs.
+fn process_image(input_path: &Path, block_size: u32, outputs: &[PathBuf], tolerance: f64, options: &ProcessOptions, shared: Option<&mut ColorMapper>, quiet: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if options.sheet.is_some() && outputs.is_empty() {
+        return Err(failure::bad_input("--sheet needs --output or --out-dir"));
+    }
+    if block_size > MAX_BLOCK_SIZE {
+        return Err(failure::bad_input(format!("Block size of {} is over the largest, {}", block_size, MAX_BLOCK_SIZE)));
     }
 
-    let output = Output {
-        matrix,
-        colors: id_to_color,
+    let cache_path = options.cache.as_deref().map(|dir| cache::path_for(dir, input_path));
+    let mut cache = cache_path.as_deref().map(|path| cache::load(path, block_size, tolerance).unwrap_or_else(|| BlockCache::new(block_size, tolerance)));
+    let mut own_mapper;
+    let mapper = match (shared, &mut cache) {
+        (Some(shared), _) => shared,
+        (None, Some(cache)) => {
+            own_mapper = ColorMapper::restore(tolerance, std::mem::take(&mut cache.colors), std::mem::take(&mut cache.aliases));
+            &mut own_mapper
+        }
+        (None, None) => {
+            // Clustering merges colors afterwards; map them exactly first
+            own_mapper = ColorMapper::new(if options.cluster { 0.0 } else { tolerance });
+            &mut own_mapper
+        }
     };
 
-    // Custom JSON serialization to keep matrix rows on single lines
-    let mut json_output = String::new();
-    json_output.push_str("{\n  \"matrix\": [\n");
-    for (i, row) in output.matrix.iter().enumerate() {
-        let row_str = serde_json::to_string(row)?;
-        json_output.push_str("    ");
-        json_output.push_str(&row_str);
-        if i < output.matrix.len() - 1 {
-            json_output.push_str(",");
-        }
-        json_output.push_str("\n");
-    }
-    json_output.push_str("  ],\n  \"colors\": ");
-    let colors_json = serde_json::to_string_pretty(&output.colors)?;
-    json_output.push_str(&colors_json);
-    json_output.push_str("\n}");
-
-    if let Some(path) = output_path {
-        let mut file = File::create(path)?;
-        file.write_all(json_output.as_bytes())?;
+    let started = Instant::now();
+    if options.segment.segmentation.is_some() {
+        return process_segments(input_path, outputs, options, mapper, started, quiet);
+    }
+    // (delay in ms, matrix) per frame; still images have a single frame without a delay
+    let mut frames = Vec::new();
+    let mut size = (0, 0);
+    let bar = progress::rows(0, quiet);
+    let blocks = Blocks { size: block_size, edges: options.edges, edge_aware: options.edge_aware, grid: options.grid };
+    if options.depth == Depth::Sixteen {
+        if open_frames(input_path, options)?.is_some() {
+            return Err(failure::bad_input("--depth 16 takes still images"));
+        }
+        let mut image = mapped::open_image(input_path, !options.no_auto_orient)?.into_rgba16();
+        if let Some(denoise) = options.denoise {
+            image = denoise.image16(&image);
+        }
+        if !options.adjust.is_identity() {
+            options.adjust.image16(&mut image);
+        }
+        if let Some(levels) = options.posterize {
+            posterize::image16(&mut image, levels);
+        }
+        size = image.dimensions();
+        debug!(input = %input_path.display(), width = size.0, height = size.1, block_size, "mapping at 16 bits");
+        let (matrix, colors) = depth::map_image(&image, block_size, options.edges, tolerance);
+        frames.push((0, Matrix::Memory(matrix)));
+        mapper.id_to_color = colors;
+    } else if let Some(animated) = open_frames(input_path, options)? {
+        for frame in animated {
+            let (delay, mut buffer) = frame?;
+            if let Some(denoise) = options.denoise {
+                buffer = denoise.image(&buffer);
+            }
+            if !options.adjust.is_identity() {
+                options.adjust.pixels(&mut buffer);
+            }
+            if let Some(levels) = options.posterize {
+                posterize::image(&mut buffer, levels);
+            }
+            size = buffer.dimensions();
+            if blocks.grid != Grid::Square {
+                frames.push((delay, Matrix::Memory(blocks.grid.map_image(&buffer, block_size, mapper))));
+                continue;
+            }
+            let mut source = ImageRows::new(DynamicImage::ImageRgba8(buffer));
+            bar.inc_length(source.dimensions().1 as u64);
+            // Cached block hashes only describe the first frame
+            let cache = if frames.is_empty() { cache.as_mut() } else { None };
+            frames.push((delay, map_rows(&mut source, blocks, mapper, options.tile_rows, cache, &bar)?));
+        }
+        debug!(input = %input_path.display(), frames = frames.len(), "decoded animation");
     } else {
-        println!("{}", json_output);
+        let mut source = filtered_rows(input_path, options)?;
+        size = source.dimensions();
+        let (width, height) = size;
+        debug!(input = %input_path.display(), width, height, block_size, "mapping");
+        if blocks.grid != Grid::Square {
+            let image = read_image(source.as_mut(), &format!("--grid {}", blocks.grid.name()))?;
+            frames.push((0, Matrix::Memory(blocks.grid.map_image(&image, block_size, mapper))));
+        } else {
+            bar.inc_length(height as u64);
+            frames.push((0, map_rows(source.as_mut(), blocks, mapper, options.tile_rows, cache.as_mut(), &bar)?));
+        }
+    }
+    bar.finish_and_clear();
+    debug!(colors = mapper.id_to_color.len(), "mapped");
+    if options.cluster {
+        mapper.id_to_color = cluster_frames(&mut frames, &mapper.id_to_color, tolerance)?;
+        debug!(colors = mapper.id_to_color.len(), "clustered");
+    }
+
+    if let (Some(path), Some(cache)) = (&cache_path, &mut cache) {
+        cache.colors = mapper.id_to_color.clone();
+        cache.aliases = mapper.color_to_id.clone();
+        cache::save(path, cache).map_err(failure::write)?;
     }
 
+    let quantized;
+    let colors = match options.preset {
+        Some(preset) => {
+            quantized = preset::apply(preset, &mut frames, &mapper.id_to_color)?;
+            &quantized
+        }
+        None => &mapper.id_to_color,
+    };
+    let mut meta: Vec<(&str, serde_json::Value)> = Vec::new();
+    if options.edges != Edges::Average {
+        meta.push(("edges", options.edges.name().into()));
+    }
+    if options.depth == Depth::Sixteen {
+        meta.push(("depth", 16.into()));
+    }
+    if options.grid != Grid::Square {
+        meta.push(("grid", options.grid.name().into()));
+    }
+    let render = |w: &mut dyn Write| match frames.as_slice() {
+        [(_, matrix)] => write_json(matrix, colors, &meta, w),
+        _ => write_frames_json(&frames, colors, options.delta, &meta, w),
+    };
+    for path in outputs {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+            let [(_, matrix)] = frames.as_slice() else {
+                return Err(failure::bad_input("SVG outputs take a single frame"));
+            };
+            match options.grid {
+                Grid::Square => svg::write(matrix, colors, path),
+                Grid::Triangle => svg::write_triangles(matrix, colors, path),
+                Grid::Hex => return Err(failure::bad_input("SVG outputs take square and triangle grids")),
+            }
+            .map_err(failure::write)?;
+        } else if image::ImageFormat::from_path(path).is_ok() && options.grid != Grid::Square {
+            let mut images = Vec::with_capacity(frames.len());
+            for (delay, matrix) in &frames {
+                let mut rows = Vec::with_capacity(matrix.len());
+                matrix.for_each_row(|row| {
+                    rows.push(row.to_vec());
+                    Ok(())
+                })?;
+                images.push((*delay, render_cells(&rows, colors, options.grid, block_size, &ProgressBar::hidden())?));
+            }
+            save_images(path, &images)?;
+        } else if image::ImageFormat::from_path(path).is_ok() {
+            write_image(path, &frames, colors)?;
+        } else {
+            mapped::write_file(path, render).map_err(failure::write)?;
+        }
+    }
+    if outputs.is_empty() {
+        let mut stdout = std::io::stdout().lock();
+        render(&mut stdout).and_then(|()| writeln!(stdout)).map_err(failure::write)?;
+    }
+    if let (Some(spec), Some(path)) = (options.sheet, outputs.first()) {
+        sheet::write(&frames, colors, spec, path).map_err(failure::write)?;
+    }
+    if options.cart.is_some() || options.script.is_some() {
+        let [(_, matrix)] = frames.as_mut_slice() else {
+            return Err(failure::bad_input("--cart and --script take a single frame"));
+        };
+        let rows = std::mem::replace(matrix, Matrix::Memory(Vec::new())).into_rows()?;
+        if let Some(cart) = &options.cart {
+            if !matches!(options.preset, Some(Preset::Pico8)) {
+                return Err(failure::bad_input("--cart needs --preset pico8"));
+            }
+            pico8::write_cart(cart, &rows).map_err(failure::write)?;
+        }
+        if let (Some(script), Some(preset)) = (&options.script, options.preset) {
+            script::write(script, preset, &rows).map_err(failure::write)?;
+        }
+    }
+
+    // Worked out even when quiet, for the warning past --max-size
+    let (width, height) = size;
+    let (cols, rows) = options.grid.cells(width, height, block_size, options.edges);
+    let finished = options.sizing.finished(cols, rows);
+    if !quiet {
+        info!(
+            input = %input_path.display(),
+            size = %format_args!("{}x{}", width, height),
+            cells = %format_args!("{}x{}", cols, rows),
+            finished = finished.map(tracing::field::display),
+            frames = frames.len(),
+            colors = colors.len(),
+            elapsed = ?started.elapsed(),
+            output = %destinations(outputs),
+            "pixelated"
+        );
+    }
     Ok(())
 }
 
-fn hex_to_rgba(hex: &str) -> Result<Rgba<u8>, String> {
-    if hex.len() != 9 || !hex.starts_with('#') {
-        return Err(format!("Invalid hex color: {}", hex));
+/// Rest of [`process_image`] for `--segmentation`, which maps still images into a
+/// [`SegmentMap`] instead of a matrix.
+fn process_segments(input_path: &Path, outputs: &[PathBuf], options: &ProcessOptions, mapper: &mut ColorMapper, started: Instant, quiet: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if open_frames(input_path, options)?.is_some() {
+        return Err(failure::bad_input("--segmentation takes still images"));
+    }
+    let mut source = filtered_rows(input_path, options)?;
+    let (width, height) = source.dimensions();
+    debug!(input = %input_path.display(), width, height, "segmenting");
+    let image = read_image(source.as_mut(), "--segmentation")?;
+    let map = options.segment.map_image(&image, mapper)?;
+
+    for path in outputs {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+            map.write_svg(path)?;
+        } else if image::ImageFormat::from_path(path).is_ok() {
+            render_segments(&map, 1)?.save(path).map_err(failure::write)?;
+        } else {
+            mapped::write_file(path, |w| map.write_json(w)).map_err(failure::write)?;
+        }
+    }
+    if outputs.is_empty() {
+        let mut stdout = std::io::stdout().lock();
+        map.write_json(&mut stdout).and_then(|()| writeln!(stdout)).map_err(failure::write)?;
+    }
+
+    if !quiet {
+        info!(
+            input = %input_path.display(),
+            size = %format_args!("{}x{}", width, height),
+            segmentation = map.segmentation().name(),
+            cells = map.cells(),
+            colors = map.colors().len(),
+            elapsed = ?started.elapsed(),
+            output = %destinations(outputs),
+            "segmented"
+        );
     }
-    let r = u8::from_str_radix(&hex[1..3], 16).map_err(|e| e.to_string())?;
-    let g = u8::from_str_radix(&hex[3..5], 16).map_err(|e| e.to_string())?;
-    let b = u8::from_str_radix(&hex[5..7], 16).map_err(|e| e.to_string())?;
-    let a = u8::from_str_radix(&hex[7..9], 16).map_err(|e| e.to_string())?;
-    Ok(Rgba([r, g, b, a]))
+    Ok(())
 }
 
-fn reconstruct_image(input_path: &PathBuf, output_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    let mut file = File::open(input_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+/// The rows of the still image in `input_path` with the filters and adjustments of `options`
+/// applied as they're read.
+fn filtered_rows(input_path: &Path, options: &ProcessOptions) -> Result<Box<dyn RowSource>, Box<dyn std::error::Error>> {
+    let mut source = open_rows(input_path, options.low_memory, !options.no_auto_orient)?;
+    if let Some(denoise) = options.denoise {
+        source = Box::new(denoise::Rows::new(source, denoise));
+    }
+    if !options.adjust.is_identity() {
+        source = Box::new(adjust::Rows::new(source, options.adjust));
+    }
+    if let Some(levels) = options.posterize {
+        source = Box::new(posterize::Rows::new(source, levels));
+    }
+    Ok(source)
+}
 
-    let data: Output = serde_json::from_str(&contents)?;
+/// Read all of `source` into memory for `what`, which can't map it a row at a time.
+fn read_image(source: &mut dyn RowSource, what: &str) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let (width, height) = source.dimensions();
+    if mapped::too_large(width, height) {
+        return Err(failure::bad_input(format!("{} needs all of the image in memory, too much for {}x{} pixels", what, width, height)));
+    }
+    let mut image = RgbaImage::new(width, height);
+    for row in image.chunks_exact_mut(width as usize * 4) {
+        source.read_row(row)?;
+    }
+    Ok(image)
+}
+
+/// Outputs of `--save-default`: the map and its image, named after `input` and next to it.
+fn default_outputs(input: &Path, block_size: u32) -> Vec<PathBuf> {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    ["json", "png"].iter().map(|ext| input.with_file_name(format!("{}_pixelated_{}.{}", stem, block_size, ext))).collect()
+}
 
-    if data.matrix.is_empty() {
-        return Err("Matrix is empty".into());
+/// Merge the exact colors of `frames` with [`pixel::cluster_colors`], renumbering the cells so
+/// IDs go from the most used color down; returns the new colors.
+fn cluster_frames(frames: &mut [(u32, Matrix)], colors: &HashMap<u32, String>, tolerance: f64) -> Result<HashMap<u32, String>, Box<dyn std::error::Error>> {
+    let mut counts: HashMap<u32, u64> = HashMap::new();
+    for (_, matrix) in frames.iter() {
+        matrix.for_each_row(|row| {
+            for &id in row {
+                *counts.entry(id).or_default() += 1;
+            }
+            Ok(())
+        })?;
     }
+    let ids: Vec<u32> = counts.keys().copied().collect();
+    let weighted = ids.iter().map(|id| Ok((hex_to_rgba(&colors[id])?, counts[id]))).collect::<Result<Vec<_>, PixelError>>()?;
+    let (clusters, centroids) = pixel::cluster_colors(&weighted, tolerance);
 
-    let height = data.matrix.len() as u32;
-    let width = data.matrix[0].len() as u32;
+    // Cluster n becomes ID n + 1, leaving 0 for transparent
+    let remap: HashMap<u32, u32> = ids.iter().zip(&clusters).map(|(&id, cluster)| (id, cluster.map_or(0, |cluster| cluster as u32 + 1))).collect();
+    for (_, matrix) in frames.iter_mut() {
+        *matrix = matrix.map_ids(|id| remap[&id])?;
+    }
+    let mut clustered: HashMap<u32, String> = centroids.iter().enumerate().map(|(cluster, color)| (cluster as u32 + 1, rgba_to_hex(color))).collect();
+    clustered.insert(0, "#00000000".to_string());
+    Ok(clustered)
+}
 
-    let mut img: RgbaImage = ImageBuffer::new(width, height);
+// Where outputs go, for messages
+fn destinations(outputs: &[PathBuf]) -> String {
+    match outputs {
+        [] => "stdout".to_string(),
+        _ => outputs.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "),
+    }
+}
 
-    for (y, row) in data.matrix.iter().enumerate() {
-        for (x, &id) in row.iter().enumerate() {
-            if let Some(hex_color) = data.colors.get(&id) {
-                let rgba = hex_to_rgba(hex_color)?;
-                img.put_pixel(x as u32, y as u32, rgba);
+/// Render `frames` to the image at `path`, one pixel per cell; several frames become an
+/// animation.
+fn write_image(path: &Path, frames: &[(u32, Matrix)], colors: &HashMap<u32, String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut images = Vec::with_capacity(frames.len());
+    for (delay, matrix) in frames {
+        let mut rows = Vec::with_capacity(matrix.len());
+        matrix.for_each_row(|row| {
+            rows.push(row.to_vec());
+            Ok(())
+        })?;
+        images.push((*delay, render_map(&rows, colors, &ProgressBar::hidden())?));
+    }
+    save_images(path, &images)
+}
+
+/// Write rendered frames to the image at `path`; several frames become an animation.
+fn save_images(path: &Path, images: &[(u32, RgbaImage)]) -> Result<(), Box<dyn std::error::Error>> {
+    match images {
+        [(_, image)] => image.save(path).map_err(failure::write)?,
+        _ if animation::can_write(path) => animation::write(path, images).map_err(failure::write)?,
+        _ => return Err(failure::bad_input("Maps with frames can only be written as GIF, PNG (APNG) or WebP images")),
+    }
+    Ok(())
+}
+
+/// Average the rows of `source` over `block_size` blocks and assign each block a color ID.
+///
+/// With a `cache`, blocks whose pixels hash the same as in the cached run keep their cached
+/// ID without being re-matched, and the cache is updated with this run's blocks.
+fn map_rows(source: &mut dyn RowSource, blocks: Blocks, mapper: &mut ColorMapper, tile_rows: Option<usize>, mut cache: Option<&mut BlockCache>, bar: &ProgressBar) -> Result<Matrix, Box<dyn std::error::Error>> {
+    let Blocks { size: block_size, edges, edge_aware, .. } = blocks;
+    let (width, height) = source.dimensions();
+    let (x_spans, y_spans) = (edges.spans(width, block_size), edges.spans(height, block_size));
+    let columns = x_spans.len();
+    if edge_aware && mapped::too_large(width, block_size.min(height)) {
+        return Err(failure::bad_input(format!("--edge-aware keeps a row of blocks in memory, too much for {} pixels across at block size {}", width, block_size)));
+    }
+
+    let auto_rows = tile_rows.is_none().then(|| matrix::spill_rows(columns, y_spans.len())).flatten();
+    if let Some(rows) = auto_rows {
+        debug!(columns, rows = y_spans.len(), tile_rows = rows, "matrix too large for memory, spilling it to disk");
+    }
+    let mut matrix = Matrix::new(columns, tile_rows.or(auto_rows))?;
+
+    let previous = match cache.as_deref_mut() {
+        Some(cache) if cache.width == width && cache.height == height => std::mem::take(&mut cache.blocks),
+        _ => Vec::new(),
+    };
+    let mut blocks: Vec<Vec<(u64, u32)>> = Vec::new();
+    let mut hashes = vec![cache::HASH_SEED; columns];
+    let mut reused = 0u64;
+
+    // Only one block-row of channel sums is kept, so rows can be consumed as they are decoded
+    let mut row_buf = vec![0u8; width as usize * 4];
+    let mut sums = vec![[0u64; 4]; columns];
+    // Edge-aware blocks need their pixels, not just sums
+    let mut strip = Vec::new();
+    let pixels = |span: &edges::Span| span.start as usize * 4..span.end as usize * 4;
+
+    for (by, y_span) in y_spans.iter().enumerate() {
+        sums.fill([0; 4]);
+        hashes.fill(cache::HASH_SEED);
+        strip.clear();
+
+        for y in y_span.start..y_span.end {
+            source.read_row(&mut row_buf)?;
+            bar.inc(1);
+            if edge_aware {
+                strip.extend_from_slice(&row_buf);
             } else {
-                eprintln!("Warning: Color ID {} not found in map", id);
-                img.put_pixel(x as u32, y as u32, Rgba([0, 0, 0, 0])); // Default to transparent
+                // The last row of a padded block also stands in for the rows it's short of
+                let weight = if y + 1 == y_span.end { 1 + y_span.pad as u64 } else { 1 };
+                for (sum, x_span) in sums.iter_mut().zip(&x_spans) {
+                    let block = &row_buf[pixels(x_span)];
+                    let block_sum = sum_rgba(block);
+                    let last = &block[block.len() - 4..];
+                    for c in 0..4 {
+                        sum[c] += (block_sum[c] + last[c] as u64 * x_span.pad as u64) * weight;
+                    }
+                }
+            }
+            if cache.is_some() {
+                for (hash, x_span) in hashes.iter_mut().zip(&x_spans) {
+                    *hash = cache::hash_pixels(*hash, &row_buf[pixels(x_span)]);
+                }
+            }
+        }
+
+        let mut row: Vec<u32> = Vec::with_capacity(columns);
+        for (bx, (sum, x_span)) in sums.iter().zip(&x_spans).enumerate() {
+            // Padding changes the color of the same pixels
+            if x_span.pad > 0 || y_span.pad > 0 {
+                hashes[bx] = cache::hash_pixels(hashes[bx], &[x_span.pad.to_le_bytes(), y_span.pad.to_le_bytes()].concat());
+            }
+            // And so does averaging them edge-aware
+            if edge_aware {
+                hashes[bx] = cache::hash_pixels(hashes[bx], b"edge-aware");
+            }
+            let cached = previous.get(by).and_then(|r| r.get(bx)).filter(|(hash, _)| *hash == hashes[bx]);
+            if let Some(&(_, id)) = cached {
+                reused += 1;
+                row.push(id);
+                continue;
+            }
+
+            if edge_aware {
+                row.push(mapper.id_for(edge_aware::block_color(&strip, width, x_span, y_span)));
+                continue;
             }
+            let count = (x_span.end - x_span.start + x_span.pad) as u64 * (y_span.end - y_span.start + y_span.pad) as u64;
+            row.push(mapper.id_for(pixel::block_color(*sum, count)));
+        }
+        if cache.is_some() {
+            blocks.push(hashes.iter().copied().zip(row.iter().copied()).collect());
+        }
+        matrix.push_row(row)?;
+        bar.set_message(format!("{} colors", mapper.id_to_color.len()));
+    }
+
+    if let Some(cache) = cache {
+        debug!(reused, blocks = (columns * y_spans.len()) as u64, "cached blocks reused");
+        cache.width = width;
+        cache.height = height;
+        cache.blocks = blocks;
+    }
+
+    Ok(matrix)
+}
+
+fn write_rows(matrix: &Matrix, indent: &[u8], w: &mut dyn Write) -> std::io::Result<()> {
+    let mut i = 0;
+    matrix.for_each_row(|row| {
+        w.write_all(indent)?;
+        serde_json::to_writer(&mut *w, row)?;
+        i += 1;
+        if i < matrix.len() {
+            w.write_all(b",")?;
         }
+        w.write_all(b"\n")
+    })
+}
+
+// Top-level fields describing how a map was made, ahead of its matrix
+fn write_meta(meta: &[(&str, serde_json::Value)], w: &mut dyn Write) -> std::io::Result<()> {
+    for (key, value) in meta {
+        writeln!(w, "  {}: {},", serde_json::Value::from(*key), value)?;
     }
+    Ok(())
+}
+
+// Custom JSON serialization to keep matrix rows on single lines and colors in ID order
+fn write_json(matrix: &Matrix, colors: &HashMap<u32, String>, meta: &[(&str, serde_json::Value)], w: &mut dyn Write) -> std::io::Result<()> {
+    w.write_all(b"{\n")?;
+    write_meta(meta, w)?;
+    w.write_all(b"  \"matrix\": [\n")?;
+    write_rows(matrix, b"    ", w)?;
+    w.write_all(b"  ],\n  \"colors\": ")?;
+    serde_json::to_writer_pretty(&mut *w, &colors.iter().collect::<BTreeMap<_, _>>())?;
+    w.write_all(b"\n}")
+}
+
+// One `[x,y,id]` per line for every cell of `matrix` that differs from `previous`
+fn write_changes(previous: &Matrix, matrix: &Matrix, indent: &[u8], w: &mut dyn Write) -> std::io::Result<()> {
+    let mut before = Vec::with_capacity(previous.len());
+    previous.for_each_row(|row| {
+        before.push(row.to_vec());
+        Ok(())
+    })?;
+
+    let mut first = true;
+    let mut y = 0;
+    matrix.for_each_row(|row| {
+        for (x, &id) in row.iter().enumerate() {
+            if before.get(y).and_then(|r| r.get(x)) == Some(&id) {
+                continue;
+            }
+            w.write_all(if first { b"" } else { b",\n" })?;
+            w.write_all(indent)?;
+            write!(w, "[{},{},{}]", x, y, id)?;
+            first = false;
+        }
+        y += 1;
+        Ok(())
+    })?;
+    w.write_all(if first { b"" } else { b"\n" })
+}
+
+/// Like `write_json`, with one `{"delay_ms", "matrix"}` object per frame under `frames`
+/// and a single palette shared by all of them. With `delta`, frames after the first hold
+/// `"changes"` against the previous frame instead of a matrix.
+fn write_frames_json(frames: &[(u32, Matrix)], colors: &HashMap<u32, String>, delta: bool, meta: &[(&str, serde_json::Value)], w: &mut dyn Write) -> std::io::Result<()> {
+    w.write_all(b"{\n")?;
+    write_meta(meta, w)?;
+    w.write_all(b"  \"frames\": [\n")?;
+    for (i, (delay, matrix)) in frames.iter().enumerate() {
+        write!(w, "    {{\n      \"delay_ms\": {},\n", delay)?;
+        match i.checked_sub(1).filter(|_| delta) {
+            Some(previous) => {
+                w.write_all(b"      \"changes\": [\n")?;
+                write_changes(&frames[previous].1, matrix, b"        ", w)?;
+            }
+            None => {
+                w.write_all(b"      \"matrix\": [\n")?;
+                write_rows(matrix, b"        ", w)?;
+            }
+        }
+        w.write_all(if i + 1 < frames.len() { b"      ]\n    },\n" } else { b"      ]\n    }\n" })?;
+    }
+    w.write_all(b"  ],\n  \"colors\": ")?;
+    serde_json::to_writer_pretty(&mut *w, &colors.iter().collect::<BTreeMap<_, _>>())?;
+    w.write_all(b"\n}")
+}
+
+/// Grid a map was cut on, for commands that read maps without [`MapFile`].
+#[derive(Deserialize)]
+struct MapGrid {
+    #[serde(default)]
+    grid: Grid,
+}
+
+/// Read the map in `path`, which must have square cells.
+fn load_map(path: &Path) -> Result<Output, Box<dyn std::error::Error>> {
+    let contents = mapped::map_file(path)?;
+    serde_json::from_slice::<MapGrid>(&contents)?.grid.require_square()?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+/// Paint every cell of `matrix` with its color; IDs missing from `colors` become transparent.
+fn render_map(matrix: &[Vec<u32>], colors: &HashMap<u32, String>, bar: &ProgressBar) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let img = pixel::render(matrix, colors, |id| bar.suspend(|| warn!(id, "color ID not found in map"))).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    bar.inc(matrix.len() as u64);
+    Ok(img)
+}
+
+/// Paint every cell of `matrix`, a map of `grid`, `width` pixels across; IDs missing from
+/// `colors` become transparent.
+fn render_cells(matrix: &[Vec<u32>], colors: &HashMap<u32, String>, grid: Grid, width: u32, bar: &ProgressBar) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let img = grid.render(matrix, colors, width, |id| bar.suspend(|| warn!(id, "color ID not found in map"))).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    bar.inc(matrix.len() as u64);
+    Ok(img)
+}
+
+/// Paint a segmented map `scale` pixels per pixel; IDs missing from its colors become
+/// transparent.
+fn render_segments(map: &SegmentMap, scale: u32) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    map.render(scale, |id| warn!(id, "color ID not found in map")).map_err(|e| failure::tag(Kind::InvalidJson, e))
+}
+
+/// Render the map in `input_path` to `output_path`; maps with frames become an animation,
+/// played at `fps` when given. Ragged matrices are evened out with `repair`, or refused.
+fn reconstruct_image(input_path: &Path, output_path: &Path, fps: Option<f64>, repair: Option<Repair>, style: &StyleOptions, quiet: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(fps) = fps
+        && (!fps.is_finite() || fps <= 0.0)
+    {
+        return Err(failure::bad_input("--fps must be greater than 0"));
+    }
+    style.validate()?;
+
+    let contents = mapped::map_file(input_path)?;
+    let MapFile { matrix, frames, mut colors, grid, segmentation } = serde_json::from_slice(&contents)?;
+    if segmentation.is_some() {
+        let map: SegmentMap = serde_json::from_slice(&contents)?;
+        if output_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+            return map.write_svg(output_path);
+        }
+        let img = render_segments(&map, style.segment_scale()?)?;
+        img.save(output_path).map_err(failure::write)?;
+        return Ok(());
+    }
+    // Cells drawn in the style, or as hexagons
+    let draw = |matrix: &[Vec<u32>], colors: &HashMap<u32, String>, bar: &ProgressBar| match grid {
+        Grid::Square => render_map(matrix, colors, bar).and_then(|img| style.apply(img)),
+        Grid::Hex | Grid::Triangle => render_cells(matrix, colors, grid, style.cell_width()?, bar),
+    };
+    match (matrix, frames) {
+        (Some(mut matrix), None) => {
+            repair::check(&mut matrix, &mut colors, repair, "Matrix")?;
+            if output_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+                let matrix = Matrix::Memory(matrix);
+                return match grid {
+                    Grid::Square => svg::write(&matrix, &colors, output_path),
+                    Grid::Triangle => svg::write_triangles(&matrix, &colors, output_path),
+                    Grid::Hex => return Err(failure::bad_input("Hex maps can't be reconstructed as SVG")),
+                }
+                .map_err(failure::write);
+            }
+            let bar = progress::rows(matrix.len() as u64, quiet);
+            let img = draw(&matrix, &colors, &bar)?;
+            bar.finish_and_clear();
+
+            img.save(output_path).map_err(failure::write)?;
+        }
+        (None, Some(map_frames)) => {
+            if !animation::can_write(output_path) {
+                return Err(failure::bad_input("Maps with frames can only be reconstructed as GIF, PNG (APNG) or WebP"));
+            }
+            if map_frames.is_empty() {
+                return Err(failure::tag(Kind::InvalidJson, "Map has no frames"));
+            }
+            let mut map_frames = animation::resolve(map_frames).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+            for (i, (_, matrix)) in map_frames.iter_mut().enumerate() {
+                repair::check(matrix, &mut colors, repair, &format!("Frame {}", i))?;
+            }
+
+            let bar = progress::rows(map_frames.iter().map(|(_, matrix)| matrix.len() as u64).sum(), quiet);
+            let mut frames = Vec::with_capacity(map_frames.len());
+            for (delay, matrix) in &map_frames {
+                let delay = fps.map_or(*delay, |fps| (1000.0 / fps).round() as u32);
+                frames.push((delay, draw(matrix, &colors, &bar)?));
+            }
+            bar.finish_and_clear();
 
-    img.save(output_path)?;
+            if frames.iter().any(|(_, img)| img.dimensions() != frames[0].1.dimensions()) {
+                return Err(failure::tag(Kind::InvalidJson, "Frames differ in size"));
+            }
+            animation::write(output_path, &frames).map_err(failure::write)?;
+        }
+        _ => return Err(failure::tag(Kind::InvalidJson, "Map needs either a matrix or frames")),
+    }
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+fn main() {
+    // Errors before the command line is parsed are reported in the default format
+    fn exit<T>(e: Box<dyn std::error::Error>) -> T {
+        std::process::exit(failure::report(e.as_ref(), ErrorFormat::default()))
+    }
+
+    // Defaults come from the configuration files, then saved presets; `PIXEL_*` environment
+    // variables override both, and flags override everything
+    let (args, presets) = bundle::extract(std::env::args_os());
+    let mut command = config::apply(config::env(Cli::command()))
+        .and_then(|mut command| presets.iter().try_for_each(|name| bundle::apply(&mut command, name)).map(|()| command))
+        .unwrap_or_else(exit);
+    let matches = match command.try_get_matches_from_mut(&args) {
+        Ok(matches) => matches,
+        // Without the arguments of `pixelate`, ask for them on a terminal
+        Err(e) if matches!(e.kind(), ErrorKind::MissingRequiredArgument | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand) && wizard::wanted(&command, &args) => {
+            let args = wizard::ask(&command, args).unwrap_or_else(exit);
+            command.get_matches_from(args)
+        }
+        Err(e) => e.exit(),
+    };
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    logging::init(cli.verbose, cli.quiet, cli.log_format);
+
+    if let Err(e) = run(&cli) {
+        std::process::exit(failure::report(e.as_ref(), cli.error_format));
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    cli.overwrite.guard(&cli.command.outputs(), || run_command(cli))
+}
 
+fn run_command(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
     match &cli.command {
-        Commands::Pixelate { input, block_size, output, tolerance } => {
+        Commands::Pixelate { input, block_size, target_width, target_height, output, tolerance, options } => {
+            if matches!(block_size, BlockSize::Fixed(0)) {
+                return Err(failure::bad_input("Block size must be greater than 0"));
+            }
+            if *target_width == Some(0) || *target_height == Some(0) {
+                return Err(failure::bad_input("Target width and height must be greater than 0"));
+            }
+            let block_size = match (target_width, target_height) {
+                (None, None) => *block_size,
+                (&width, &height) => BlockSize::Fit { width, height },
+            };
+            process_inputs(input, block_size, output, *tolerance, options, &cli.overwrite, cli.quiet)
+        }
+        Commands::Map { input, output, tolerance, options } => process_inputs(input, BlockSize::Fixed(1), output, *tolerance, options, &cli.overwrite, cli.quiet),
+        Commands::Posterize { input, output, levels, block_size, tolerance, options } => {
+            if matches!(block_size, BlockSize::Fixed(0)) {
+                return Err(failure::bad_input("Block size must be greater than 0"));
+            }
+            let options = ProcessOptions { posterize: Some(*levels), ..options.clone() };
+            process_inputs(input, *block_size, output, *tolerance, &options, &cli.overwrite, cli.quiet)
+        }
+        Commands::Redact { input, output, out_dir, block_size, region, mask, detect_faces, face_model } => {
             if *block_size == 0 {
-                eprintln!("Error: Block size must be greater than 0");
-                std::process::exit(1);
+                return Err(failure::bad_input("Block size must be greater than 0"));
             }
-            process_image(input, *block_size, output.as_ref(), *tolerance)
+            let jobs: Vec<(PathBuf, PathBuf)> = match (output, out_dir) {
+                (_, Some(dir)) => {
+                    std::fs::create_dir_all(dir).map_err(failure::write)?;
+                    let named = |path: &PathBuf| path.file_name().map(|name| (path.clone(), dir.join(name))).ok_or_else(|| failure::bad_input(format!("{} has no file name", path.display())));
+                    input.iter().map(named).collect::<Result<_, _>>()?
+                }
+                (Some(output), None) if input.len() == 1 => vec![(input[0].clone(), output.clone())],
+                _ => return Err(failure::bad_input("Several inputs need --out-dir")),
+            };
+            redact::run(&jobs, *block_size, region, mask.as_deref(), face_model.as_deref().filter(|_| *detect_faces))
+        }
+        Commands::Reconstruct { input, output, fps, repair, style } => reconstruct_image(input, output, *fps, *repair, style, cli.quiet),
+        Commands::Collision { input, output, solid_ids, solid_by, format } => {
+            let solid = match solid_by {
+                Some(by) => collision::Solid::By(*by),
+                None => collision::Solid::Ids(solid_ids),
+            };
+            collision::run(input, output, solid, *format)
+        }
+        Commands::Daemon { cache_dir } => daemon::run(cache_dir.as_deref()),
+        Commands::Diff { before, after, preview } => diff::run(before, after, *preview),
+        Commands::Edit { input } => edit::run(input),
+        Commands::Export { input, output, format, tile_size, sizing, pagination, text } => {
+            export::run(input, output, *format, *tile_size, sizing, pagination, text)
         }
-        Commands::Map { input, output, tolerance } => process_image(input, 1, output.as_ref(), *tolerance),
-        Commands::Reconstruct { input, output } => reconstruct_image(input, output),
+        Commands::Materials { input, output, catalog, per_pack, format } => {
+            materials::run(input, output.as_deref(), *catalog, *per_pack, *format)
+        }
+        Commands::Onion { input, frames, output, before_tint, after_tint, opacity } => {
+            onion::run(input, frames, output, *before_tint, *after_tint, *opacity)
+        }
+        Commands::Shadow { input, output, offset, color, opacity } => shadow::run(input, output, *offset, *color, *opacity),
+        Commands::Swaps { input, palettes, output, frame, cols, spacing, style } => swaps::run(input, palettes, output, *frame, *cols, *spacing, style),
+        Commands::Ramps { input, output, steps, swatch, scale } => ramps::run(input, output, *steps as usize, swatch.as_deref(), *scale),
+        Commands::Tween { input, output, steps, frames } => tween::run(input, output, *steps, frames.as_deref()),
+        Commands::Cycle { input, output, cycles, fps, frames, style } => cycle::run(input, output, cycles, *fps, *frames, style),
+        Commands::Preview { input, width, terminal, dither } => preview::run(input, *width, *terminal, *dither),
+        Commands::Stitch { input, output, mode, sizing, pagination } => stitch::run(input, output, *mode, sizing, pagination),
+        Commands::Serve { host, port, notify_url, grpc: false } => serve::run(host, *port, notify_url.as_deref()),
+        #[cfg(feature = "grpc")]
+        Commands::Serve { host, port, grpc: true, .. } => grpc::run(host, *port),
+        #[cfg(not(feature = "grpc"))]
+        Commands::Serve { grpc: true, .. } => Err(failure::bad_input("This build has no gRPC support; rebuild with --features grpc")),
+        Commands::Tileset { input, output, tile_size, flips } => tileset::run(input, output, *tile_size, *flips),
+        Commands::Tune { input, block_size, tolerance } => {
+            if *block_size == 0 {
+                return Err(failure::bad_input("Block size must be greater than 0"));
+            }
+            tune::run(input, *block_size, *tolerance)
+        }
+        Commands::Validate { input, schema } => match input {
+            Some(input) if !schema => validate::run(input),
+            _ => {
+                print!("{}", validate::SCHEMA);
+                Ok(())
+            }
+        },
+        Commands::Watch { input, block_size, output, tolerance, options } => {
+            if *block_size == 0 {
+                return Err(failure::bad_input("Block size must be greater than 0"));
+            }
+            watch::run(input, || process_image(input, *block_size, std::slice::from_ref(output), *tolerance, options, None, cli.quiet))
+        }
+        Commands::Run { pipeline } => pipeline::run(pipeline),
+        Commands::Preset { action } => bundle::run(action, &Cli::command()),
+        Commands::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "pixel", &mut std::io::stdout());
+            Ok(())
+        }
+        Commands::Manpage { out_dir } => {
+            let cmd = Cli::command().name("pixel");
+            match out_dir {
+                Some(dir) => {
+                    std::fs::create_dir_all(dir)?;
+                    clap_mangen::generate_to(cmd, dir)?;
+                }
+                None => clap_mangen::Man::new(cmd).render(&mut std::io::stdout())?,
+            }
+            Ok(())
+        }
+        Commands::Bench { sizes, palettes, block_sizes, tolerance, iterations } => {
+            if block_sizes.contains(&0) {
+                return Err(failure::bad_input("Block size must be greater than 0"));
+            }
+            bench::run(sizes, palettes, block_sizes, *tolerance, *iterations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parses(args: &[&str]) -> bool {
+        Cli::try_parse_from(std::iter::once("pixel").chain(args.iter().copied())).is_ok()
+    }
+
+    #[test]
+    fn tween_steps_are_capped() {
+        assert!(parses(&["tween", "-i", "in.json", "-o", "out.json", "--steps", "1000"]));
+        assert!(!parses(&["tween", "-i", "in.json", "-o", "out.json", "--steps", "4294967295"]));
+    }
+
+    #[test]
+    fn cycle_frames_are_capped() {
+        assert!(parses(&["cycle", "-i", "in.json", "-o", "out.gif", "-c", "1-2", "--frames", "1000"]));
+        assert!(!parses(&["cycle", "-i", "in.json", "-o", "out.gif", "-c", "1-2", "--frames", "4294967295"]));
+        assert!(!parses(&["cycle", "-i", "in.json", "-o", "out.gif", "-c", "1-2", "--frames", "0"]));
+    }
+
+    #[test]
+    fn map_meta_keeps_how_the_map_was_made() {
+        let meta: MapMeta = serde_json::from_str(r#"{"grid": "hex", "depth": 16, "frames": [], "colors": {}}"#).unwrap();
+        assert_eq!(meta.fields(), vec![("depth", 16.into()), ("grid", "hex".into())]);
+    }
+
+    fn outputs(args: &[&str]) -> Vec<PathBuf> {
+        Cli::try_parse_from(std::iter::once("pixel").chain(args.iter().copied())).unwrap().command.outputs()
+    }
+
+    #[test]
+    fn outputs_cover_companion_files() {
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(outputs(&["export", "-i", "in.json", "-o", "out/bg.chr", "-f", "nes"]), paths(&["out/bg.chr", "out/bg.nam", "out/bg-palettes.json"]));
+        assert_eq!(outputs(&["export", "-i", "in.json", "-o", "level.tscn", "-f", "godot"]), paths(&["level.tscn", "level.tres", "level-tiles.png"]));
+        assert_eq!(outputs(&["tileset", "-i", "in.json", "-o", "level.json"]), paths(&["level.json", "level-tiles.png"]));
+        assert_eq!(outputs(&["pixelate", "-i", "walk.gif", "-o", "walk.json", "--sheet", "cols=4"]), paths(&["walk.json", "walk-sheet.png", "walk-sheet.json"]));
+    }
+
+    #[test]
+    fn save_default_does_not_overwrite_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("g.png");
+        RgbaImage::new(16, 16).save(&input).unwrap();
+        let existing = dir.path().join("g_pixelated_8.json");
+        std::fs::write(&existing, "keep").unwrap();
+        let pixelate = |force: &[&str]| {
+            let args = ["pixel", "--quiet"].into_iter().chain(force.iter().copied()).chain(["pixelate", "-i", input.to_str().unwrap(), "-b", "8", "--save-default"]);
+            run(&Cli::try_parse_from(args).unwrap())
+        };
+        assert!(pixelate(&[]).is_err());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "keep");
+        assert!(!dir.path().join("g_pixelated_8.png").exists());
+        pixelate(&["--force"]).unwrap();
+        assert_ne!(std::fs::read_to_string(&existing).unwrap(), "keep");
     }
 }
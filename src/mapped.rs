@@ -0,0 +1,90 @@
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use memmap2::{Mmap, MmapMut};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+
+use crate::failure;
+
+// Largest image decoded in one piece, in bytes of 8-bit RGBA pixels
+const MAX_DECODE_BYTES: u64 = 1 << 30;
+
+/// Whether an image of `width` by `height` pixels is too large to decode in one piece.
+pub fn too_large(width: u32, height: u32) -> bool {
+    width as u64 * height as u64 * 4 > MAX_DECODE_BYTES
+}
+
+/// Map a file read-only into memory.
+pub fn map_file(path: &Path) -> io::Result<Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: the map is only read, and we accept that a concurrent writer to the
+    // same file could change what we decode, as with any other read.
+    unsafe { Mmap::map(&file) }
+}
+
+/// Decode an image straight from a memory map instead of copying the file into a buffer; with
+/// `auto_orient`, photos are turned upright by their EXIF orientation.
+pub fn open_image(path: &Path, auto_orient: bool) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let map = map_file(path)?;
+    let mut reader = ImageReader::new(Cursor::new(&map[..]));
+    match ImageFormat::from_path(path) {
+        Ok(format) => reader.set_format(format),
+        Err(_) => reader = reader.with_guessed_format()?,
+    }
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    if too_large(width, height) {
+        return Err(failure::bad_input(format!("{} is {}x{}, too large to decode in memory", path.display(), width, height)));
+    }
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    if auto_orient {
+        image.apply_orientation(orientation);
+    }
+    Ok(image)
+}
+
+/// EXIF orientation of the image at `path`, from its header.
+pub fn orientation(path: &Path) -> Result<Orientation, Box<dyn std::error::Error>> {
+    Ok(ImageReader::open(path)?.with_guessed_format()?.into_decoder()?.orientation()?)
+}
+
+/// Counts bytes without storing them, to size the output file before mapping it.
+struct CountingWriter(u64);
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write the output of `render` to `path` through a writable memory map.
+///
+/// `render` is called twice: once to measure the output, once to fill the map, so the
+/// serialized document is never held in memory as a whole.
+pub fn write_file<F>(path: &Path, mut render: F) -> io::Result<()>
+where
+    F: FnMut(&mut dyn Write) -> io::Result<()>,
+{
+    let mut counter = CountingWriter(0);
+    render(&mut counter)?;
+
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    file.set_len(counter.0)?;
+    if counter.0 == 0 {
+        return Ok(());
+    }
+
+    // SAFETY: the file was just created and truncated by us and is not shared with
+    // anything else in this process.
+    let mut map = unsafe { MmapMut::map_mut(&file)? };
+    let mut cursor: &mut [u8] = &mut map[..];
+    render(&mut cursor)?;
+    map.flush()
+}
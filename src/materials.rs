@@ -0,0 +1,123 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+use crate::beads::{self, Catalog as BeadCatalog};
+use crate::failure::{self, Kind};
+use crate::{chart, dmc, lego, load_map, mapped, rgba_to_hex, stitch};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Catalog {
+    /// DMC floss, in skeins
+    Dmc,
+    /// Perler beads, in bags of 1000
+    Perler,
+    /// Hama beads, in bags of 1000
+    Hama,
+    /// Artkal beads, in bags of 1000
+    Artkal,
+    /// LEGO 1x1 plates by BrickLink color, sold individually
+    Lego,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum ListFormat {
+    #[default]
+    Csv,
+    Json,
+    Markdown,
+}
+
+#[derive(Serialize)]
+struct Item {
+    code: String,
+    name: &'static str,
+    color: String,
+    count: usize,
+    packs: usize,
+}
+
+const BEADS_PER_BAG: usize = 1000;
+
+fn bead_entries(catalog: &BeadCatalog) -> Vec<(String, &'static str, u32)> {
+    catalog.beads.iter().map(|&(code, name, rgb)| (code.to_string(), name, rgb)).collect()
+}
+
+impl Catalog {
+    /// Code, name and color of each entry.
+    fn entries(self) -> Vec<(String, &'static str, u32)> {
+        match self {
+            Catalog::Dmc => dmc::FLOSS.iter().map(|&(number, name, rgb)| (number.to_string(), name, rgb)).collect(),
+            Catalog::Perler => bead_entries(&beads::PERLER),
+            Catalog::Hama => bead_entries(&beads::HAMA),
+            Catalog::Artkal => bead_entries(&beads::ARTKAL),
+            Catalog::Lego => lego::COLORS.iter().map(|&(name, id, rgb)| (id.to_string(), name, rgb)).collect(),
+        }
+    }
+
+    fn per_pack(self) -> usize {
+        match self {
+            Catalog::Dmc => stitch::STITCHES_PER_SKEIN,
+            Catalog::Perler | Catalog::Hama | Catalog::Artkal => BEADS_PER_BAG,
+            Catalog::Lego => 1,
+        }
+    }
+}
+
+// Quote fields that would break a CSV row
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) { format!("\"{}\"", text.replace('"', "\"\"")) } else { text.to_string() }
+}
+
+/// List how much of each `catalog` color the map in `input` needs, with the number of packs of
+/// `per_pack` (or the catalog's usual pack size) to buy, to `output` or stdout.
+pub fn run(input: &Path, output: Option<&Path>, catalog: Catalog, per_pack: Option<usize>, format: ListFormat) -> Result<(), Box<dyn std::error::Error>> {
+    if per_pack == Some(0) {
+        return Err(failure::bad_input("Pack size must be greater than 0"));
+    }
+    let map = load_map(input)?;
+    let entries = catalog.entries();
+    let colors: Vec<u32> = entries.iter().map(|&(_, _, rgb)| rgb).collect();
+    let matched = chart::match_colors(&map, &colors).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    let per_pack = per_pack.unwrap_or(catalog.per_pack());
+
+    let mut items: Vec<Item> = matched
+        .used
+        .iter()
+        .map(|&(index, count)| {
+            let (code, name, rgb) = &entries[index];
+            Item { code: code.clone(), name, color: rgba_to_hex(&chart::opaque(*rgb)), count, packs: count.div_ceil(per_pack) }
+        })
+        .collect();
+    items.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.code.cmp(&b.code)));
+
+    let render = |w: &mut dyn Write| -> std::io::Result<()> {
+        match format {
+            ListFormat::Csv => {
+                writeln!(w, "code,name,color,count,packs")?;
+                for item in &items {
+                    writeln!(w, "{},{},{},{},{}", csv_field(&item.code), csv_field(item.name), item.color, item.count, item.packs)?;
+                }
+            }
+            ListFormat::Json => {
+                serde_json::to_writer_pretty(&mut *w, &items)?;
+                writeln!(w)?;
+            }
+            ListFormat::Markdown => {
+                writeln!(w, "| Code | Name | Color | Count | Packs |")?;
+                writeln!(w, "|------|------|-------|------:|------:|")?;
+                for item in &items {
+                    writeln!(w, "| {} | {} | `{}` | {} | {} |", item.code, item.name, item.color, item.count, item.packs)?;
+                }
+            }
+        }
+        Ok(())
+    };
+    match output {
+        Some(path) => mapped::write_file(path, render),
+        None => render(&mut std::io::stdout().lock()),
+    }
+    .map_err(failure::write)?;
+    Ok(())
+}
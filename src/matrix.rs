@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+// Matrices that would take more memory than this are spilled to disk even without --tile-rows
+const MEMORY_LIMIT: u64 = 256 << 20;
+// Size of the tiles they're spilled in
+const TILE_BYTES: usize = 4 << 20;
+
+/// Rows per tile to spill a matrix of `width` by `height` IDs in when it would take too much
+/// memory whole, as very tall images do; `None` when it fits.
+pub fn spill_rows(width: usize, height: usize) -> Option<usize> {
+    // Every row in memory is a `Vec` of its own, with its own allocation
+    let row_bytes = size_of::<Vec<u32>>() + (width * 4).max(16);
+    (height as u64 * row_bytes as u64 > MEMORY_LIMIT).then(|| (TILE_BYTES / (width * 4).max(1)).max(1))
+}
+
+/// ID matrix under construction, either fully in memory or spilled to disk in tiles.
+pub enum Matrix {
+    Memory(Vec<Vec<u32>>),
+    Spilled(SpillFile),
+}
+
+impl Matrix {
+    /// Keep the whole matrix in memory, or spill it every `tile_rows` rows when given.
+    pub fn new(width: usize, tile_rows: Option<usize>) -> io::Result<Self> {
+        Ok(match tile_rows {
+            Some(tile_rows) => Matrix::Spilled(SpillFile::new(width, tile_rows)?),
+            None => Matrix::Memory(Vec::new()),
+        })
+    }
+
+    pub fn push_row(&mut self, row: Vec<u32>) -> io::Result<()> {
+        match self {
+            Matrix::Memory(rows) => rows.push(row),
+            Matrix::Spilled(spill) => spill.push_row(&row)?,
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Matrix::Memory(rows) => rows.len(),
+            Matrix::Spilled(spill) => spill.rows,
+        }
+    }
+
+    pub fn into_rows(self) -> io::Result<Vec<Vec<u32>>> {
+        match self {
+            Matrix::Memory(rows) => Ok(rows),
+            Matrix::Spilled(spill) => {
+                let mut rows = Vec::with_capacity(spill.rows);
+                spill.for_each_row(|row| {
+                    rows.push(row.to_vec());
+                    Ok(())
+                })?;
+                Ok(rows)
+            }
+        }
+    }
+
+    /// A copy with every ID replaced by `f(id)`, held in memory or spilled like this one.
+    pub fn map_ids(&self, f: impl Fn(u32) -> u32) -> io::Result<Matrix> {
+        let mut mapped = match self {
+            Matrix::Memory(_) => Matrix::Memory(Vec::with_capacity(self.len())),
+            Matrix::Spilled(spill) => Matrix::Spilled(SpillFile::new(spill.width, spill.tile_rows)?),
+        };
+        self.for_each_row(|row| mapped.push_row(row.iter().map(|&id| f(id)).collect()))?;
+        Ok(mapped)
+    }
+
+    /// Call `f` with every row, top to bottom.
+    pub fn for_each_row(&self, mut f: impl FnMut(&[u32]) -> io::Result<()>) -> io::Result<()> {
+        match self {
+            Matrix::Memory(rows) => rows.iter().try_for_each(|row| f(row)),
+            Matrix::Spilled(spill) => spill.for_each_row(f),
+        }
+    }
+}
+
+/// Matrix rows buffered one tile at a time and flushed to an anonymous temporary file.
+pub struct SpillFile {
+    file: BufWriter<File>,
+    width: usize,
+    rows: usize,
+    tile_rows: usize,
+    tile: Vec<u32>,
+}
+
+impl SpillFile {
+    fn new(width: usize, tile_rows: usize) -> io::Result<Self> {
+        Ok(SpillFile {
+            file: BufWriter::new(tempfile::tempfile()?),
+            width,
+            rows: 0,
+            tile_rows: tile_rows.max(1),
+            tile: Vec::new(),
+        })
+    }
+
+    fn push_row(&mut self, row: &[u32]) -> io::Result<()> {
+        self.tile.extend_from_slice(row);
+        self.rows += 1;
+        if self.tile.len() >= self.tile_rows * self.width {
+            self.flush_tile()?;
+        }
+        Ok(())
+    }
+
+    fn flush_tile(&mut self) -> io::Result<()> {
+        for id in self.tile.drain(..) {
+            self.file.write_all(&id.to_le_bytes())?;
+        }
+        self.file.flush()
+    }
+
+    fn for_each_row(&self, mut f: impl FnMut(&[u32]) -> io::Result<()>) -> io::Result<()> {
+        let mut file = self.file.get_ref().try_clone()?;
+        file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(file);
+
+        let spilled_rows = self.rows - self.tile.len() / self.width.max(1);
+        let mut bytes = vec![0u8; self.width * 4];
+        let mut row = vec![0u32; self.width];
+        for _ in 0..spilled_rows {
+            reader.read_exact(&mut bytes)?;
+            for (id, b) in row.iter_mut().zip(bytes.chunks_exact(4)) {
+                *id = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            }
+            f(&row)?;
+        }
+        // Rows of the last, partially filled tile are still in memory
+        for row in self.tile.chunks(self.width.max(1)) {
+            f(row)?;
+        }
+        Ok(())
+    }
+}
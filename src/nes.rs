@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::{Output, failure, hex_to_rgba};
+
+const TILE_SIZE: usize = 8;
+// Attribute table entries pick one palette per 16x16 area
+const AREA_SIZE: usize = 16;
+const NAMETABLE_COLUMNS: usize = 32;
+const NAMETABLE_ROWS: usize = 30;
+const PATTERN_TABLE_BYTES: usize = 4096;
+
+#[derive(Serialize)]
+struct Palettes {
+    /// Shared background color, index 0 of every palette
+    background: String,
+    /// Colors 1-3 of each background palette
+    palettes: Vec<Vec<String>>,
+}
+
+/// Write the map for the NES background: `output` as a CHR pattern table (up to 256 distinct
+/// 8x8 tiles, tile 0 blank), `<stem>.nam` with the 32x30 nametable followed by its attribute
+/// table, and `<stem>-palettes.json` with the colors each palette index stands for.
+///
+/// Transparent cells use the shared background color; every 16x16 area may add at most three
+/// colors to it, and the whole map at most four such palettes.
+pub fn write(map: &Output, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let stem = output.file_stem().ok_or("Output has no file name")?.to_string_lossy().into_owned();
+    let nam_path = output.with_file_name(format!("{}.nam", stem));
+    let palettes_path = output.with_file_name(format!("{}-palettes.json", stem));
+    if nam_path == output || palettes_path == output {
+        return Err(failure::bad_input("Output must not end in .nam or -palettes.json; those are written next to it"));
+    }
+
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let height = map.matrix.len();
+    if width > NAMETABLE_COLUMNS * TILE_SIZE || height > NAMETABLE_ROWS * TILE_SIZE {
+        return Err(failure::bad_input(format!("A nametable covers at most 256x240 cells, the map is {}x{}", width, height)));
+    }
+
+    let mut background = HashMap::new();
+    for (&id, hex) in &map.colors {
+        background.insert(id, id == 0 || hex_to_rgba(hex)?[3] == 0);
+    }
+    // Unknown IDs render transparent, so they take the background color too
+    let color_at = |x: usize, y: usize| map.matrix.get(y).and_then(|row| row.get(x)).copied().filter(|id| background.get(id) == Some(&false));
+
+    let (area_columns, area_rows) = (NAMETABLE_COLUMNS * TILE_SIZE / AREA_SIZE, NAMETABLE_ROWS * TILE_SIZE / AREA_SIZE);
+    let mut area_colors = vec![vec![BTreeSet::new(); area_columns]; area_rows];
+    let mut violations = Vec::new();
+    for (ay, row) in area_colors.iter_mut().enumerate() {
+        for (ax, colors) in row.iter_mut().enumerate() {
+            for y in ay * AREA_SIZE..(ay + 1) * AREA_SIZE {
+                colors.extend((ax * AREA_SIZE..(ax + 1) * AREA_SIZE).filter_map(|x| color_at(x, y)));
+            }
+            if colors.len() > 3 {
+                violations.push(format!("({}, {}) with {}", ax * AREA_SIZE, ay * AREA_SIZE, colors.len() + 1));
+            }
+        }
+    }
+    if !violations.is_empty() {
+        return Err(failure::bad_input(format!(
+            "{} 16x16 area(s) use more than 4 colors counting the background, by top-left cell: {}",
+            violations.len(),
+            violations.join(", ")
+        )));
+    }
+
+    // Fill the largest color sets first so smaller ones can share their palette
+    let mut sets: Vec<&BTreeSet<u32>> = area_colors.iter().flatten().filter(|set| !set.is_empty()).collect();
+    sets.sort_by_key(|set| std::cmp::Reverse(set.len()));
+    let mut palettes: Vec<BTreeSet<u32>> = Vec::new();
+    for set in sets {
+        match palettes.iter_mut().find(|palette| palette.union(set).count() <= 3) {
+            Some(palette) => palette.extend(set),
+            None => palettes.push(set.clone()),
+        }
+    }
+    if palettes.len() > 4 {
+        return Err(failure::bad_input(format!("The map needs {} background palettes of 3 colors; the NES has 4", palettes.len())));
+    }
+    let area_palette: Vec<Vec<usize>> = area_colors
+        .iter()
+        .map(|row| row.iter().map(|set| palettes.iter().position(|palette| set.is_subset(palette)).unwrap_or(0)).collect())
+        .collect();
+
+    let mut tiles: Vec<Vec<u8>> = vec![vec![0; TILE_SIZE * TILE_SIZE]];
+    let mut seen: HashMap<Vec<u8>, usize> = HashMap::from([(tiles[0].clone(), 0)]);
+    let mut nametable = Vec::with_capacity(NAMETABLE_COLUMNS * NAMETABLE_ROWS + 64);
+    for ty in 0..NAMETABLE_ROWS {
+        for tx in 0..NAMETABLE_COLUMNS {
+            let palette = palettes.get(area_palette[ty / 2][tx / 2]);
+            let mut cells = Vec::with_capacity(TILE_SIZE * TILE_SIZE);
+            for y in ty * TILE_SIZE..(ty + 1) * TILE_SIZE {
+                cells.extend((tx * TILE_SIZE..(tx + 1) * TILE_SIZE).map(|x| match (color_at(x, y), palette) {
+                    (Some(id), Some(palette)) => palette.iter().position(|&c| c == id).map_or(0, |i| i as u8 + 1),
+                    _ => 0,
+                }));
+            }
+            let index = *seen.entry(cells).or_insert_with_key(|cells| {
+                tiles.push(cells.clone());
+                tiles.len() - 1
+            });
+            nametable.push(index);
+        }
+    }
+    if tiles.len() > 256 {
+        return Err(failure::bad_input(format!("The map has {} distinct tiles including the blank one; a pattern table holds 256", tiles.len())));
+    }
+
+    // Each attribute byte covers 32x32 cells: bits 0-1 top left, 2-3 top right, 4-5 bottom left, 6-7 bottom right
+    let mut nam: Vec<u8> = nametable.iter().map(|&index| index as u8).collect();
+    for by in 0..area_rows.div_ceil(2) {
+        for bx in 0..area_columns / 2 {
+            let mut byte = 0;
+            for (shift, (dx, dy)) in [(0, 0), (1, 0), (0, 1), (1, 1)].into_iter().enumerate() {
+                let palette = area_palette.get(by * 2 + dy).map_or(0, |row| row[bx * 2 + dx]);
+                byte |= (palette as u8) << (shift * 2);
+            }
+            nam.push(byte);
+        }
+    }
+    fs::write(&nam_path, nam)?;
+
+    // Each tile is its low bit plane (8 bytes, one per row, leftmost pixel in the high bit), then its high bit plane
+    let mut chr = Vec::with_capacity(PATTERN_TABLE_BYTES);
+    for tile in &tiles {
+        for plane in 0..2 {
+            chr.extend(tile.chunks(TILE_SIZE).map(|row| row.iter().fold(0u8, |byte, &pixel| byte << 1 | (pixel >> plane & 1))));
+        }
+    }
+    chr.resize(PATTERN_TABLE_BYTES, 0);
+    fs::write(output, chr)?;
+
+    let background_hex = map.colors.iter().filter(|(id, _)| background[id]).min_by_key(|(id, _)| **id).map_or("#00000000", |(_, hex)| hex);
+    let palettes = Palettes {
+        background: background_hex.to_string(),
+        palettes: palettes.iter().map(|palette| palette.iter().map(|id| map.colors[id].clone()).collect()).collect(),
+    };
+    serde_json::to_writer_pretty(BufWriter::new(File::create(palettes_path)?), &palettes)?;
+    Ok(())
+}
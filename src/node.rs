@@ -0,0 +1,64 @@
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::{Env, Error, Result, Task};
+use napi_derive::napi;
+
+use crate::{Output, map_encoded, render_png};
+
+/// Options of `map`, as for `pixel pixelate`.
+#[napi(object)]
+pub struct MapOptions {
+    /// Defaults to 1, mapping every pixel
+    pub block_size: Option<u32>,
+    pub tolerance: Option<f64>,
+}
+
+pub struct MapTask {
+    bytes: Vec<u8>,
+    block_size: u32,
+    tolerance: f64,
+}
+
+impl Task for MapTask {
+    type Output = String;
+    type JsValue = String;
+
+    fn compute(&mut self) -> Result<String> {
+        let map = map_encoded(&self.bytes, self.block_size, self.tolerance).map_err(|e| Error::from_reason(e.to_string()))?;
+        serde_json::to_string(&map).map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    fn resolve(&mut self, _env: Env, json: String) -> Result<String> {
+        Ok(json)
+    }
+}
+
+pub struct ReconstructTask {
+    json: String,
+}
+
+impl Task for ReconstructTask {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> Result<Vec<u8>> {
+        let map: Output = serde_json::from_str(&self.json).map_err(|e| Error::from_reason(e.to_string()))?;
+        render_png(&map).map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    fn resolve(&mut self, _env: Env, png: Vec<u8>) -> Result<Buffer> {
+        Ok(png.into())
+    }
+}
+
+/// Map an encoded image (PNG, JPEG, GIF, ...) on the libuv thread pool; resolves to the JSON map.
+#[napi(ts_return_type = "Promise<string>")]
+pub fn map(bytes: Buffer, options: Option<MapOptions>) -> AsyncTask<MapTask> {
+    let (block_size, tolerance) = options.map_or((1, 0.0), |options| (options.block_size.unwrap_or(1), options.tolerance.unwrap_or(0.0)));
+    AsyncTask::new(MapTask { bytes: bytes.to_vec(), block_size, tolerance })
+}
+
+/// Paint a JSON map with a single matrix into a PNG on the libuv thread pool.
+#[napi(ts_return_type = "Promise<Buffer>")]
+pub fn reconstruct(json: String) -> AsyncTask<ReconstructTask> {
+    AsyncTask::new(ReconstructTask { json })
+}
@@ -0,0 +1,41 @@
+use serde::Serialize;
+use tracing::{debug, warn};
+
+/// A file of a batch that failed, and why.
+#[derive(Serialize)]
+pub struct Failure {
+    pub input: String,
+    pub error: String,
+}
+
+/// What a finished batch did, as posted to `--notify-url`.
+#[derive(Serialize)]
+pub struct BatchSummary {
+    pub files: usize,
+    pub processed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    /// Outputs written by this run, leaving out the unchanged ones
+    pub outputs: Vec<String>,
+    pub failures: Vec<Failure>,
+}
+
+/// A request the server answered, as posted to `serve --notify-url`.
+#[derive(Serialize)]
+pub struct RequestSummary<'a> {
+    pub endpoint: &'a str,
+    pub status: u16,
+    /// Size of the response body, in bytes
+    pub bytes: usize,
+    pub error: Option<String>,
+}
+
+/// POST `summary` as JSON to `url`. A webhook that can't be reached only gets a warning: the
+/// job it reports on is done either way.
+pub fn send(url: &str, summary: &impl Serialize) {
+    let body = serde_json::to_vec(summary).expect("summaries serialize");
+    match ureq::post(url).header("Content-Type", "application/json").send(&body[..]) {
+        Ok(_) => debug!(url, "notified"),
+        Err(e) => warn!(url, "notification failed: {}", e),
+    }
+}
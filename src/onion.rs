@@ -0,0 +1,62 @@
+use image::{Rgba, RgbaImage};
+use indicatif::ProgressBar;
+use std::path::Path;
+
+use crate::failure::{self, Kind};
+use crate::{MapFile, animation, mapped, render_map};
+
+// Mix `tint` into the color channels by the tint's alpha, keeping the cell's own alpha
+fn tint(color: Rgba<u8>, tint: Rgba<u8>) -> [f32; 4] {
+    let strength = tint[3] as f32 / 255.0;
+    let mut out = [0.0; 4];
+    for c in 0..3 {
+        out[c] = color[c] as f32 * (1.0 - strength) + tint[c] as f32 * strength;
+    }
+    out[3] = color[3] as f32;
+    out
+}
+
+/// Draw `after` at `opacity` over `before`, each tinted first.
+fn overlay(before: &RgbaImage, after: &RgbaImage, before_tint: Rgba<u8>, after_tint: Rgba<u8>, opacity: f32) -> RgbaImage {
+    RgbaImage::from_fn(before.width().max(after.width()), before.height().max(after.height()), |x, y| {
+        let transparent = Rgba([0, 0, 0, 0]);
+        let below = tint(*before.get_pixel_checked(x, y).unwrap_or(&transparent), before_tint);
+        let above = tint(*after.get_pixel_checked(x, y).unwrap_or(&transparent), after_tint);
+
+        // Porter-Duff "over" on straight alpha
+        let a_above = above[3] / 255.0 * opacity;
+        let a_below = below[3] / 255.0;
+        let a_out = a_above + a_below * (1.0 - a_above);
+        if a_out == 0.0 {
+            return transparent;
+        }
+        let mut out = [0u8; 4];
+        for c in 0..3 {
+            out[c] = ((above[c] * a_above + below[c] * a_below * (1.0 - a_above)) / a_out).round() as u8;
+        }
+        out[3] = (a_out * 255.0).round() as u8;
+        Rgba(out)
+    })
+}
+
+/// Render the two `frames` (0-based) of the map in `input` on top of each other into `output`.
+pub fn run(input: &Path, frames: &[usize], output: &Path, before_tint: Rgba<u8>, after_tint: Rgba<u8>, opacity: f32) -> Result<(), Box<dyn std::error::Error>> {
+    let &[first, second] = frames else {
+        return Err(failure::bad_input("--frames takes two frames, like 3,4"));
+    };
+    if !(0.0..=1.0).contains(&opacity) {
+        return Err(failure::bad_input("--opacity must be between 0 and 1"));
+    }
+    let data: MapFile = serde_json::from_slice(&mapped::map_file(input)?)?;
+    data.grid.require_square()?;
+    let map_frames = data.frames.ok_or_else(|| failure::tag(Kind::InvalidJson, "Map has no frames"))?;
+    let frames = animation::resolve(map_frames).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+
+    let frame = |i: usize| {
+        let (_, matrix) = frames.get(i).ok_or_else(|| failure::bad_input(format!("No frame {}; the map has {} (counting from 0)", i, frames.len())))?;
+        render_map(matrix, &data.colors, &ProgressBar::hidden())
+    };
+    let image = overlay(&frame(first)?, &frame(second)?, before_tint, after_tint, opacity);
+    image.save(output).map_err(failure::write)?;
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use clap::Args;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::failure;
+
+/// What to do about output files that already exist.
+#[derive(Args, Debug)]
+pub struct OverwriteOptions {
+    /// Overwrite existing output files
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// Keep a copy of existing output files as `<name>.bak` before writing them; refuses to
+    /// replace an earlier `.bak`
+    #[arg(long, global = true)]
+    backup: bool,
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+// Size and modification time of the file at `path`, to tell whether it was written since
+fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+impl OverwriteOptions {
+    /// Refuse to go on if any of `outputs` exists, unless forced or backing it up to a
+    /// `<name>.bak` that doesn't exist yet.
+    pub fn check(&self, outputs: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+        for path in outputs.iter().filter(|path| path.is_file()) {
+            if self.backup {
+                let backup = backup_path(path);
+                if backup.exists() {
+                    return Err(failure::bad_input(format!("{} exists from an earlier backup; move it away to back up {} again", backup.display(), path.display())));
+                }
+            } else if !self.force {
+                return Err(failure::bad_input(format!("{} exists; pass --force to overwrite it or --backup to keep a copy", path.display())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `write`, which writes `outputs`, once they pass [`check`](Self::check). With
+    /// `--backup`, existing outputs are copied aside first; if `write` fails, the copies of
+    /// outputs it didn't get to are removed again, leaving them as they were.
+    pub fn guard<T>(&self, outputs: &[PathBuf], write: impl FnOnce() -> Result<T, Box<dyn std::error::Error>>) -> Result<T, Box<dyn std::error::Error>> {
+        self.check(outputs)?;
+        let mut backups = Vec::new();
+        if self.backup {
+            for path in outputs.iter().filter(|path| path.is_file()) {
+                let backup = backup_path(path);
+                let before = stamp(path);
+                fs::copy(path, &backup).map_err(failure::write)?;
+                backups.push((path, backup, before));
+            }
+        }
+        let result = write();
+        if result.is_err() {
+            for (path, backup, before) in backups {
+                if before.is_some() && stamp(path) == before {
+                    let _ = fs::remove_file(backup);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKUP: OverwriteOptions = OverwriteOptions { force: false, backup: true };
+
+    #[test]
+    fn failed_commands_leave_outputs_and_no_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("map.json");
+        fs::write(&output, "old").unwrap();
+        assert!(BACKUP.guard(std::slice::from_ref(&output), || Err::<(), _>(failure::bad_input("no input"))).is_err());
+        assert_eq!(fs::read_to_string(&output).unwrap(), "old");
+        assert!(!backup_path(&output).exists());
+    }
+
+    #[test]
+    fn backups_keep_what_was_overwritten_and_are_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("map.json");
+        fs::write(&output, "old").unwrap();
+        BACKUP.guard(std::slice::from_ref(&output), || fs::write(&output, "new").map_err(Into::into)).unwrap();
+        assert_eq!(fs::read_to_string(backup_path(&output)).unwrap(), "old");
+        assert!(BACKUP.guard(std::slice::from_ref(&output), || Ok(())).is_err());
+        assert_eq!(fs::read_to_string(backup_path(&output)).unwrap(), "old");
+    }
+}
@@ -0,0 +1,66 @@
+use image::Rgba;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Parse a hex color as [`pixel::hex_to_rgba`] does, ignoring surrounding whitespace.
+pub fn parse_color(text: &str) -> Result<Rgba<u8>, String> {
+    pixel::hex_to_rgba(text.trim()).map_err(|e| e.to_string())
+}
+
+/// Load a palette file: GIMP `.gpl`, or one hex color per line (Lospec `.hex` and similar).
+pub fn load(path: &Path) -> Result<Vec<Rgba<u8>>, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)?;
+    let colors = if text.starts_with("GIMP Palette") { parse_gpl(&text)? } else { parse_hex_lines(&text)? };
+    if colors.is_empty() {
+        return Err(format!("{} contains no colors", path.display()).into());
+    }
+    Ok(colors)
+}
+
+/// Write `colors` as a palette file: GIMP `.gpl` with `columns` colors per row when the path
+/// ends in `.gpl`, one hex color per line otherwise. Alpha is only kept in hex lines.
+pub fn write(path: &Path, name: &str, colors: &[Rgba<u8>], columns: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let mut text = Vec::new();
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gpl")) {
+        writeln!(text, "GIMP Palette\nName: {}\nColumns: {}\n#", name, columns)?;
+        for color in colors {
+            writeln!(text, "{:3} {:3} {:3}\t{}", color[0], color[1], color[2], &pixel::rgba_to_hex(color)[1..7])?;
+        }
+    } else {
+        for color in colors {
+            let hex = pixel::rgba_to_hex(color);
+            writeln!(text, "{}", if color[3] == 255 { &hex[1..7] } else { &hex[1..] })?;
+        }
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+fn parse_hex_lines(text: &str) -> Result<Vec<Rgba<u8>>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with("//"))
+        .map(parse_color)
+        .collect()
+}
+
+fn parse_gpl(text: &str) -> Result<Vec<Rgba<u8>>, String> {
+    let mut colors = Vec::new();
+    for line in text.lines().skip(1).map(str::trim) {
+        // Header fields ("Name: ...", "Columns: ...") and comments
+        if line.is_empty() || line.starts_with('#') || (line.contains(':') && !line.starts_with(|c: char| c.is_ascii_digit())) {
+            continue;
+        }
+        let channels: Vec<u8> = line
+            .split_whitespace()
+            .take(3)
+            .map(|v| v.parse().map_err(|_| format!("Invalid palette line: {}", line)))
+            .collect::<Result<_, _>>()?;
+        if channels.len() != 3 {
+            return Err(format!("Invalid palette line: {}", line));
+        }
+        colors.push(Rgba([channels[0], channels[1], channels[2], 255]));
+    }
+    Ok(colors)
+}
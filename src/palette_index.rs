@@ -0,0 +1,61 @@
+use image::Rgba;
+use std::collections::HashMap;
+
+use crate::color_distance_sq;
+
+/// Grid-bucketed index of palette colors for tolerance lookups.
+///
+/// Colors are bucketed into RGBA cells whose side is the tolerance, so every color within
+/// tolerance of a query lives in the query's cell or one of its 80 neighbours.
+pub struct PaletteIndex {
+    tolerance_sq: f64,
+    cell: f64,
+    buckets: HashMap<[i32; 4], Vec<(u32, Rgba<u8>)>>,
+}
+
+impl PaletteIndex {
+    pub fn new(tolerance: f64) -> Self {
+        PaletteIndex {
+            tolerance_sq: tolerance * tolerance,
+            // Integer colors closer than 1.0 are identical, so smaller cells buy nothing
+            cell: tolerance.max(1.0),
+            buckets: HashMap::new(),
+        }
+    }
+
+    fn key(&self, color: &Rgba<u8>) -> [i32; 4] {
+        color.0.map(|c| (c as f64 / self.cell).floor() as i32)
+    }
+
+    pub fn insert(&mut self, id: u32, color: Rgba<u8>) {
+        let key = self.key(&color);
+        self.buckets.entry(key).or_default().push((id, color));
+    }
+
+    /// Lowest ID within tolerance of `color`, i.e. the first one inserted, matching the
+    /// result of scanning the palette in order.
+    pub fn find(&self, color: &Rgba<u8>) -> Option<u32> {
+        let base = self.key(color);
+        let mut found: Option<u32> = None;
+        for n in 0..81 {
+            let mut key = base;
+            let mut rest = n;
+            for k in key.iter_mut() {
+                *k += rest % 3 - 1;
+                rest /= 3;
+            }
+            let Some(bucket) = self.buckets.get(&key) else {
+                continue;
+            };
+            for (id, p_color) in bucket {
+                if found.is_some_and(|f| f <= *id) {
+                    continue;
+                }
+                if color_distance_sq(color, p_color) as f64 <= self.tolerance_sq {
+                    found = Some(*id);
+                }
+            }
+        }
+        found
+    }
+}
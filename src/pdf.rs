@@ -0,0 +1,176 @@
+use clap::ValueEnum;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use image::Rgba;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+pub const MARGIN: f32 = 36.0;
+
+/// Portrait paper sizes.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Paper {
+    #[default]
+    A4,
+    A3,
+    Letter,
+}
+
+impl Paper {
+    /// Width and height in points.
+    pub fn size(self) -> (f32, f32) {
+        match self {
+            Paper::A4 => (595.0, 842.0),
+            Paper::A3 => (842.0, 1191.0),
+            Paper::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Font {
+    Regular,
+    Bold,
+    /// Monospaced, for symbols that must line up in cells
+    Mono,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+}
+
+const FONTS: [&str; 3] = ["Helvetica", "Helvetica-Bold", "Courier-Bold"];
+
+fn rgb(color: Rgba<u8>) -> String {
+    let [r, g, b, _] = color.0.map(|c| c as f32 / 255.0);
+    format!("{:.3} {:.3} {:.3}", r, g, b)
+}
+
+// Only printable ASCII is safe with the standard fonts' encoding
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            ' '..='~' => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+/// Drawing commands for one page, positioned in points from the top-left corner.
+pub struct Page {
+    height: f32,
+    content: String,
+}
+
+impl Page {
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgba<u8>) {
+        let _ = writeln!(self.content, "{} rg {:.2} {:.2} {:.2} {:.2} re f", rgb(color), x, self.height - y - height, width, height);
+    }
+
+    pub fn stroke_rect(&mut self, x: f32, y: f32, width: f32, height: f32, line_width: f32) {
+        let _ = writeln!(self.content, "0 G {:.2} w {:.2} {:.2} {:.2} {:.2} re S", line_width, x, self.height - y - height, width, height);
+    }
+
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), line_width: f32, color: Rgba<u8>) {
+        let _ = writeln!(
+            self.content,
+            "{} RG {:.2} w {:.2} {:.2} m {:.2} {:.2} l S",
+            rgb(color),
+            line_width,
+            from.0,
+            self.height - from.1,
+            to.0,
+            self.height - to.1
+        );
+    }
+
+    /// Draw `text` with its baseline starting at (`x`, `y`).
+    pub fn text(&mut self, x: f32, y: f32, font: Font, size: f32, color: Rgba<u8>, text: &str) {
+        let _ = writeln!(self.content, "BT /{} {} Tf {} rg {:.2} {:.2} Td ({}) Tj ET", font.resource(), size, rgb(color), x, self.height - y, escape(text));
+    }
+
+    /// Draw `text` in the monospaced font, centered on (`x`, `y`).
+    pub fn mono_centered(&mut self, x: f32, y: f32, size: f32, color: Rgba<u8>, text: &str) {
+        // Courier glyphs are 0.6 em wide, capitals about 0.57 em high
+        let width = 0.6 * size * text.chars().count() as f32;
+        self.text(x - width / 2.0, y + 0.285 * size, Font::Mono, size, color, text);
+    }
+}
+
+/// A PDF using the standard Helvetica and Courier fonts.
+pub struct Document {
+    pub width: f32,
+    pub height: f32,
+    pages: Vec<Page>,
+}
+
+impl Document {
+    pub fn new(paper: Paper) -> Self {
+        let (width, height) = paper.size();
+        Document { width, height, pages: Vec::new() }
+    }
+
+    pub fn add_page(&mut self) -> &mut Page {
+        self.pages.push(Page { height: self.height, content: String::new() });
+        self.pages.last_mut().unwrap()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        // Objects: 1 catalog, 2 page tree, 3-5 fonts, then a page and its content per page
+        let first_page = 3 + FONTS.len();
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        let kids: Vec<String> = (0..self.pages.len()).map(|i| format!("{} 0 R", first_page + i * 2)).collect();
+        objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), self.pages.len()).into_bytes());
+        for font in FONTS {
+            objects.push(format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", font).into_bytes());
+        }
+        let fonts: Vec<String> = (0..FONTS.len()).map(|i| format!("/F{} {} 0 R", i + 1, 3 + i)).collect();
+        for (i, page) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {} >> >> /Contents {} 0 R >>",
+                    self.width,
+                    self.height,
+                    fonts.join(" "),
+                    first_page + i * 2 + 1
+                )
+                .into_bytes(),
+            );
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(page.content.as_bytes())?;
+            let data = encoder.finish()?;
+            let mut stream = format!("<< /Length {} /Filter /FlateDecode >>\nstream\n", data.len()).into_bytes();
+            stream.extend_from_slice(&data);
+            stream.extend_from_slice(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut file = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(file.len());
+            file.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            file.extend_from_slice(object);
+            file.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = file.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(table, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref);
+        file.extend_from_slice(table.as_bytes());
+        fs::write(path, file)?;
+        Ok(())
+    }
+}
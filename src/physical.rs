@@ -0,0 +1,93 @@
+use clap::Args;
+use std::str::FromStr;
+use tracing::warn;
+
+const MM_PER_INCH: f32 = 25.4;
+
+/// A physical length, from a number with a `mm`, `cm` or `in` unit.
+#[derive(Clone, Copy, Debug)]
+pub struct Length {
+    pub mm: f32,
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, scale) = if let Some(number) = s.strip_suffix("mm") {
+            (number, 1.0)
+        } else if let Some(number) = s.strip_suffix("cm") {
+            (number, 10.0)
+        } else if let Some(number) = s.strip_suffix("in") {
+            (number, MM_PER_INCH)
+        } else {
+            return Err(format!("Expected a length in mm, cm or in, got {}", s));
+        };
+        match number.trim().parse::<f32>() {
+            Ok(value) if value.is_finite() && value > 0.0 => Ok(Length { mm: value * scale }),
+            _ => Err(format!("Invalid length: {}", s)),
+        }
+    }
+}
+
+/// Width and height, from `WxH` with one unit at the end (`30x20cm`), or a single length for a
+/// square or round hoop.
+#[derive(Clone, Copy, Debug)]
+pub struct Extent {
+    pub width: Length,
+    pub height: Length,
+}
+
+impl FromStr for Extent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((width, height)) = s.split_once('x') else {
+            let side = s.parse()?;
+            return Ok(Extent { width: side, height: side });
+        };
+        let unit = height.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        let width = if width.ends_with(char::is_alphabetic) { width.parse()? } else { format!("{}{}", width, unit).parse()? };
+        Ok(Extent { width, height: height.parse()? })
+    }
+}
+
+/// Physical size of printed patterns.
+#[derive(Args, Clone, Copy, Debug, Default)]
+pub struct Sizing {
+    /// Size of one bead, stitch, drill or stud (e.g. 2.6mm); defaults to the pattern's usual one
+    #[arg(long, value_name = "LENGTH")]
+    cell_size: Option<Length>,
+
+    /// Warn when the finished design won't fit a board or hoop of this size (e.g. 30x20cm)
+    #[arg(long, value_name = "WxH")]
+    max_size: Option<Extent>,
+}
+
+impl Sizing {
+    /// Finished size of `columns` x `rows` cells, in cm and inches, warning when it exceeds
+    /// `--max-size` in both orientations.
+    pub fn describe(&self, columns: usize, rows: usize, default_cell: Length) -> String {
+        let cell = self.cell_size.unwrap_or(default_cell).mm;
+        let (width, height) = (columns as f32 * cell, rows as f32 * cell);
+        if let Some(max) = self.max_size {
+            let (max_width, max_height) = (max.width.mm, max.height.mm);
+            if !(width <= max_width && height <= max_height || width <= max_height && height <= max_width) {
+                warn!(
+                    "finished design is {:.1} x {:.1} cm, larger than the {:.1} x {:.1} cm maximum",
+                    width / 10.0,
+                    height / 10.0,
+                    max_width / 10.0,
+                    max_height / 10.0
+                );
+            }
+        }
+        format!("{:.1} x {:.1} cm, {:.1} x {:.1} in", width / 10.0, height / 10.0, width / MM_PER_INCH, height / MM_PER_INCH)
+    }
+
+    /// Finished size of `columns` x `rows` cells as [`describe`](Self::describe) gives it, for
+    /// maps, which have no usual cell size; `None` without `--cell-size`.
+    pub fn finished(&self, columns: usize, rows: usize) -> Option<String> {
+        self.cell_size.map(|cell| self.describe(columns, rows, cell))
+    }
+}
@@ -0,0 +1,62 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const GFX_HEADER: &str = "__gfx__";
+const SIZE: usize = 128;
+
+fn is_section_header(line: &str) -> bool {
+    line.len() > 4 && line.starts_with("__") && line.ends_with("__")
+}
+
+/// Put `matrix` (IDs from a PICO-8 preset map: palette position + 1, 0 transparent) at the
+/// top-left of the `__gfx__` section of the cartridge at `path`, keeping the rest of the sheet
+/// and cartridge. The cartridge is created if it doesn't exist.
+pub fn write_cart(path: &Path, matrix: &[Vec<u32>]) -> Result<(), Box<dyn std::error::Error>> {
+    let cart = match fs::read_to_string(path) {
+        Ok(cart) => cart,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => "pico-8 cartridge // http://www.pico-8.com\nversion 42\n__lua__\n".to_string(),
+        Err(e) => return Err(e.into()),
+    };
+    let lines: Vec<&str> = cart.lines().collect();
+
+    let start = lines.iter().position(|&line| line == GFX_HEADER);
+    let (before, existing, after) = match start {
+        Some(start) => {
+            let end = lines[start + 1..].iter().position(|line| is_section_header(line)).map_or(lines.len(), |i| start + 1 + i);
+            (&lines[..start], &lines[start + 1..end], &lines[end..])
+        }
+        None => (&lines[..], &[][..], &[][..]),
+    };
+
+    let mut sheet: Vec<Vec<u8>> = (0..SIZE).map(|y| {
+        let mut row = existing.get(y).map_or(Vec::new(), |line| line.as_bytes().to_vec());
+        row.resize(SIZE, b'0');
+        row
+    }).collect();
+    for (row, ids) in sheet.iter_mut().zip(matrix) {
+        for (cell, &id) in row.iter_mut().zip(ids) {
+            // Transparent cells take color 0, which PICO-8 draws as transparent by default
+            let color = id.saturating_sub(1).min(15);
+            *cell = b"0123456789abcdef"[color as usize];
+        }
+    }
+
+    let mut out = String::new();
+    for line in before {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(GFX_HEADER);
+    out.push('\n');
+    for row in &sheet {
+        out.push_str(std::str::from_utf8(row)?);
+        out.push('\n');
+    }
+    for line in after {
+        out.push_str(line);
+        out.push('\n');
+    }
+    fs::write(path, out)?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use indicatif::ProgressBar;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+use crate::matrix::Matrix;
+use crate::edges::Blocks;
+use crate::failure::{self, Kind};
+use crate::{ColorMapper, Output, batch, load_map, map_rows, mapped, open_rows, palette, render_map, transform, write_json};
+
+/// A reproducible sequence of operations, read from a TOML file.
+///
+/// ```toml
+/// inputs = ["sprites/*.png"]
+/// out_dir = "build"
+///
+/// [[steps]]
+/// op = "pixelate"
+/// block_size = 8
+///
+/// [[steps]]
+/// op = "quantize"
+/// palette = "palettes/pico8.hex"
+///
+/// [[steps]]
+/// op = "trim"
+///
+/// [[steps]]
+/// op = "export"
+/// formats = ["png", "json"]
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Pipeline {
+    /// Images or JSON maps, relative to the pipeline file; glob patterns allowed
+    inputs: Vec<PathBuf>,
+    #[serde(default = "default_out_dir")]
+    out_dir: PathBuf,
+    steps: Vec<Step>,
+}
+
+fn default_out_dir() -> PathBuf {
+    PathBuf::from("out")
+}
+
+fn default_block_size() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase", deny_unknown_fields)]
+enum Step {
+    /// Map an image input; must come first and only for image inputs
+    Pixelate {
+        #[serde(default = "default_block_size")]
+        block_size: u32,
+        #[serde(default)]
+        tolerance: f64,
+    },
+    /// Snap colors to a palette file and/or inline hex colors
+    Quantize {
+        palette: Option<PathBuf>,
+        #[serde(default)]
+        colors: Vec<String>,
+    },
+    Trim {},
+    /// Write the current map as `<out_dir>/<input stem><suffix>.<format>`
+    Export {
+        formats: Vec<ExportFormat>,
+        #[serde(default)]
+        suffix: String,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    Png,
+    Json,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+fn is_map_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+fn require(map: &mut Option<Output>) -> Result<&mut Output, &'static str> {
+    map.as_mut().ok_or("The first step for an image input must be pixelate")
+}
+
+fn export(map: &mut Output, path: &Path, format: ExportFormat) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        ExportFormat::Png => render_map(&map.matrix, &map.colors, &ProgressBar::hidden())?.save(path)?,
+        ExportFormat::Json => {
+            let matrix = Matrix::Memory(std::mem::take(&mut map.matrix));
+            let written = mapped::write_file(path, |w| write_json(&matrix, &map.colors, &[], w));
+            if let Matrix::Memory(rows) = matrix {
+                map.matrix = rows;
+            }
+            written?;
+        }
+    }
+    Ok(())
+}
+
+fn run_steps(pipeline: &Pipeline, base: &Path, input: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut map: Option<Output> = if is_map_file(input) { Some(load_map(input)?) } else { None };
+    let stem = input.file_stem().ok_or("Input has no file name")?.to_string_lossy().into_owned();
+
+    for step in &pipeline.steps {
+        match step {
+            Step::Pixelate { block_size, tolerance } => {
+                if map.is_some() {
+                    return Err("pixelate must be the first step and needs an image input".into());
+                }
+                if *block_size == 0 {
+                    return Err(failure::bad_input("Block size must be greater than 0"));
+                }
+                let mut source = open_rows(input, false, true)?;
+                let mut mapper = ColorMapper::new(*tolerance);
+                let matrix = map_rows(source.as_mut(), Blocks::plain(*block_size), &mut mapper, None, None, &ProgressBar::hidden())?;
+                map = Some(Output { matrix: matrix.into_rows()?, colors: mapper.id_to_color });
+            }
+            Step::Quantize { palette: file, colors } => {
+                let mut entries = match file {
+                    Some(file) => palette::load(&base.join(file))?,
+                    None => Vec::new(),
+                };
+                for color in colors {
+                    entries.push(palette::parse_color(color)?);
+                }
+                transform::quantize(require(&mut map)?, &entries)?;
+            }
+            Step::Trim {} => transform::trim(require(&mut map)?)?,
+            Step::Export { formats, suffix } => {
+                let out_dir = base.join(&pipeline.out_dir);
+                fs::create_dir_all(&out_dir).map_err(failure::write)?;
+                for &format in formats {
+                    let path = out_dir.join(format!("{}{}.{}", stem, suffix, format.extension()));
+                    export(require(&mut map)?, &path, format).map_err(failure::write)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the pipeline in `path` over all of its inputs, reporting failures per input.
+pub fn run(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let pipeline: Pipeline = toml::from_str(&fs::read_to_string(path)?)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    let patterns: Vec<PathBuf> = pipeline.inputs.iter().map(|input| base.join(input)).collect();
+    let inputs = batch::expand_inputs(&patterns, false, None)?;
+
+    let mut failed = 0;
+    let mut failed_kind: Option<Kind> = None;
+    for input in &inputs {
+        if let Err(e) = run_steps(&pipeline, base, &input.path) {
+            error!(input = %input.path.display(), "{}", e);
+            failed += 1;
+            let kind = failure::classify(e.as_ref());
+            failed_kind = Some(failed_kind.map_or(kind, |k| k.merge(kind)));
+        }
+    }
+    info!(inputs = inputs.len(), done = inputs.len() - failed, failed, "pipeline finished");
+    if let Some(kind) = failed_kind {
+        return Err(failure::tag(kind, format!("{} of {} inputs failed", failed, inputs.len())));
+    }
+    Ok(())
+}
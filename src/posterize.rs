@@ -0,0 +1,63 @@
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+use crate::stream::RowSource;
+
+/// `value` on a channel from 0 to `max`, moved to the nearest of `levels` evenly spaced levels
+/// that include 0 and `max`.
+pub fn channel(value: u32, levels: u8, max: u32) -> u32 {
+    let steps = levels as u32 - 1;
+    let level = (value * steps + max / 2) / max;
+    (level * max + steps / 2) / steps
+}
+
+// Posterized value of every 8-bit channel value
+fn table(levels: u8) -> [u8; 256] {
+    std::array::from_fn(|value| channel(value as u32, levels, 255) as u8)
+}
+
+/// Reduce the color channels of `image` to `levels` each; alpha is kept.
+pub fn image(image: &mut RgbaImage, levels: u8) {
+    let table = table(levels);
+    for pixel in image.pixels_mut() {
+        for c in &mut pixel.0[..3] {
+            *c = table[*c as usize];
+        }
+    }
+}
+
+/// [`image`] for 16-bit images.
+pub fn image16(image: &mut ImageBuffer<Rgba<u16>, Vec<u16>>, levels: u8) {
+    for pixel in image.pixels_mut() {
+        for c in &mut pixel.0[..3] {
+            *c = channel(*c as u32, levels, u16::MAX as u32) as u16;
+        }
+    }
+}
+
+/// Rows of `source` with their color channels reduced to a few levels as they're read.
+pub struct Rows {
+    source: Box<dyn RowSource>,
+    table: [u8; 256],
+}
+
+impl Rows {
+    pub fn new(source: Box<dyn RowSource>, levels: u8) -> Self {
+        Rows { source, table: table(levels) }
+    }
+}
+
+impl RowSource for Rows {
+    fn dimensions(&self) -> (u32, u32) {
+        self.source.dimensions()
+    }
+
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.source.read_row(buf)?;
+        for pixel in buf.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = self.table[*c as usize];
+            }
+        }
+        Ok(())
+    }
+}
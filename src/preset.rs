@@ -0,0 +1,99 @@
+use clap::ValueEnum;
+use image::Rgba;
+use std::collections::HashMap;
+
+use crate::failure;
+use crate::matrix::Matrix;
+use crate::{Output, transform};
+
+/// Fixed palettes and size limits of target platforms.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Preset {
+    /// PICO-8: the 16-color palette on a 128x128 sprite sheet
+    Pico8,
+    /// The 256 colors of xterm-compatible terminals: 16 system colors, a 6x6x6 cube and 24 grays
+    Xterm256,
+    /// The 16 standard ANSI terminal colors, as xterm shows them
+    Ansi16,
+}
+
+const PICO8_PALETTE: [u32; 16] = [
+    0x000000, 0x1d2b53, 0x7e2553, 0x008751, 0xab5236, 0x5f574f, 0xc2c3c7, 0xfff1e8, 0xff004d, 0xffa300, 0xffec27, 0x00e436, 0x29adff, 0x83769c, 0xff77a8,
+    0xffccaa,
+];
+
+const ANSI16_PALETTE: [u32; 16] = [
+    0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5, 0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff,
+    0xffffff,
+];
+
+// Channel levels of the xterm color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn rgba(rgb: u32) -> Rgba<u8> {
+    Rgba([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255])
+}
+
+impl Preset {
+    pub fn palette(self) -> Vec<Rgba<u8>> {
+        match self {
+            Preset::Pico8 => PICO8_PALETTE.iter().map(|&rgb| rgba(rgb)).collect(),
+            Preset::Ansi16 => ANSI16_PALETTE.iter().map(|&rgb| rgba(rgb)).collect(),
+            Preset::Xterm256 => {
+                let system = ANSI16_PALETTE.iter().map(|&rgb| rgba(rgb));
+                let cube = (0..216).map(|i| Rgba([CUBE_LEVELS[i / 36], CUBE_LEVELS[i / 6 % 6], CUBE_LEVELS[i % 6], 255]));
+                let grays = (0..24).map(|i| Rgba([8 + i * 10, 8 + i * 10, 8 + i * 10, 255]));
+                system.chain(cube).chain(grays).collect()
+            }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Pico8 => "PICO-8",
+            Preset::Xterm256 => "xterm 256-color",
+            Preset::Ansi16 => "ANSI 16-color",
+        }
+    }
+
+    /// Largest matrix, in cells, the platform can hold, if it has a limit.
+    pub fn max_size(self) -> Option<(usize, usize)> {
+        match self {
+            Preset::Pico8 => Some((128, 128)),
+            Preset::Xterm256 | Preset::Ansi16 => None,
+        }
+    }
+
+    /// SGR parameters selecting palette color `index` as the terminal's foreground or
+    /// background; `None` for presets that aren't terminal palettes.
+    pub fn sgr(self, index: usize, background: bool) -> Option<String> {
+        let base = if background { 40 } else { 30 };
+        match self {
+            Preset::Pico8 => None,
+            Preset::Ansi16 if index < 8 => Some((base + index).to_string()),
+            Preset::Ansi16 => Some((base + 60 + index - 8).to_string()),
+            Preset::Xterm256 => Some(format!("{};5;{}", base + 8, index)),
+        }
+    }
+}
+
+/// Snap every frame to the preset's palette, so IDs become palette positions from 1 (0 stays
+/// transparent), and return the colors now in use.
+pub fn apply(preset: Preset, frames: &mut [(u32, Matrix)], colors: &HashMap<u32, String>) -> Result<HashMap<u32, String>, Box<dyn std::error::Error>> {
+    let palette = preset.palette();
+    let mut used = HashMap::new();
+    for (_, matrix) in frames.iter_mut() {
+        let rows = std::mem::replace(matrix, Matrix::Memory(Vec::new())).into_rows()?;
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if let Some((max_width, max_height)) = preset.max_size()
+            && (width > max_width || rows.len() > max_height)
+        {
+            return Err(failure::bad_input(format!("{} allows at most {}x{} cells, got {}x{}; use a larger block size", preset.name(), max_width, max_height, width, rows.len())));
+        }
+        let mut map = Output { matrix: rows, colors: colors.clone() };
+        transform::quantize(&mut map, &palette)?;
+        used.extend(map.colors);
+        *matrix = Matrix::Memory(map.matrix);
+    }
+    Ok(used)
+}
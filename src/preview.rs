@@ -0,0 +1,216 @@
+use clap::ValueEnum;
+use image::{Rgba, RgbaImage, imageops};
+use indicatif::ProgressBar;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::failure::{self, Kind};
+use crate::preset::Preset;
+use crate::{MapFile, animation, mapped, render_map};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Terminal {
+    /// 24-bit ANSI colors, two pixels per character
+    #[default]
+    Ansi,
+    /// Sixel graphics (xterm, mlterm, foot and others), one pixel per pixel
+    Sixel,
+    /// xterm's 256 colors, two pixels per character
+    Xterm256,
+    /// The 16 standard ANSI colors, two pixels per character
+    Ansi16,
+}
+
+// Default Sixel width to scale small maps up to, in pixels
+const SIXEL_WIDTH: u32 = 640;
+// Color registers most Sixel terminals provide
+const SIXEL_REGISTERS: usize = 256;
+// 4x4 Bayer matrix for ordered dithering, thresholds out of 16
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Load `input` as an image: a JSON map rendered at one pixel per cell (the first frame of maps
+/// with frames), or any image file as is.
+pub fn load(input: &Path) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    if !input.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+        return Ok(image::open(input)?.to_rgba8());
+    }
+    let data: MapFile = serde_json::from_slice(&mapped::map_file(input)?)?;
+    data.grid.require_square()?;
+    let matrix = match (data.matrix, data.frames) {
+        (Some(matrix), None) => matrix,
+        (None, Some(frames)) => {
+            let resolved = animation::resolve(frames).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+            resolved.into_iter().next().ok_or_else(|| failure::tag(Kind::InvalidJson, "Map has no frames"))?.1
+        }
+        _ => return Err(failure::tag(Kind::InvalidJson, "Map needs either a matrix or frames")),
+    };
+    render_map(&matrix, &data.colors, &ProgressBar::hidden())
+}
+
+/// Terminal width in columns, as shells export it.
+pub fn terminal_columns() -> u32 {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).filter(|&columns| columns > 0).unwrap_or(80)
+}
+
+/// Shrink `image` to at most `width` pixels across, keeping cells square.
+pub fn fit(image: RgbaImage, width: u32) -> RgbaImage {
+    if image.width() <= width {
+        return image;
+    }
+    let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+    imageops::resize(&image, width, height, imageops::FilterType::Nearest)
+}
+
+/// Print `image` with 24-bit colors, two pixels per character: the upper half block in the top
+/// pixel's color over the bottom one's.
+pub fn write_ansi(image: &RgbaImage, w: &mut dyn Write) -> std::io::Result<()> {
+    for y in (0..image.height()).step_by(2) {
+        for x in 0..image.width() {
+            let top = image.get_pixel(x, y);
+            let bottom = (y + 1 < image.height()).then(|| image.get_pixel(x, y + 1)).filter(|pixel| pixel[3] > 0);
+            match (top[3] > 0, bottom) {
+                (true, Some(bottom)) => write!(w, "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}", top[0], top[1], top[2], bottom[0], bottom[1], bottom[2])?,
+                (true, None) => write!(w, "\x1b[49m\x1b[38;2;{};{};{}m\u{2580}", top[0], top[1], top[2])?,
+                (false, Some(bottom)) => write!(w, "\x1b[49m\x1b[38;2;{};{};{}m\u{2584}", bottom[0], bottom[1], bottom[2])?,
+                (false, None) => write!(w, "\x1b[0m ")?,
+            }
+        }
+        writeln!(w, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+// Position in `palette` of the color closest to `pixel`
+fn nearest(palette: &[Rgba<u8>], pixel: [i32; 3]) -> usize {
+    let distance = |color: &Rgba<u8>| (0..3).map(|c| (color[c] as i32 - pixel[c]).pow(2)).sum::<i32>();
+    (0..palette.len()).min_by_key(|&i| distance(&palette[i])).unwrap_or(0)
+}
+
+/// Print `image` in the colors of a terminal palette, two pixels per character like
+/// [`write_ansi`]. With `dither`, pixels are offset by a 4x4 Bayer matrix before picking the
+/// closest color, so gradients come out as patterns instead of bands.
+pub fn write_palette(image: &RgbaImage, preset: Preset, dither: bool, w: &mut dyn Write) -> std::io::Result<()> {
+    let palette = preset.palette();
+    // About the distance between neighboring palette levels
+    let spread = match preset {
+        Preset::Ansi16 => 128,
+        _ => 48,
+    };
+    let index = |x: u32, y: u32| -> Option<usize> {
+        let pixel = image.get_pixel(x, y);
+        let offset = if dither { (BAYER[y as usize % 4][x as usize % 4] as i32 * 2 - 15) * spread / 32 } else { 0 };
+        (pixel[3] > 0).then(|| nearest(&palette, [0, 1, 2].map(|c| pixel[c] as i32 + offset)))
+    };
+    let sgr = |index: usize, background: bool| preset.sgr(index, background).unwrap_or_default();
+    for y in (0..image.height()).step_by(2) {
+        for x in 0..image.width() {
+            let top = index(x, y);
+            let bottom = (y + 1 < image.height()).then(|| index(x, y + 1)).flatten();
+            match (top, bottom) {
+                (Some(top), Some(bottom)) => write!(w, "\x1b[{};{}m\u{2580}", sgr(top, false), sgr(bottom, true))?,
+                (Some(top), None) => write!(w, "\x1b[49;{}m\u{2580}", sgr(top, false))?,
+                (None, Some(bottom)) => write!(w, "\x1b[49;{}m\u{2584}", sgr(bottom, false))?,
+                (None, None) => write!(w, "\x1b[0m ")?,
+            }
+        }
+        writeln!(w, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+// Sixel levels are percentages
+fn percent(channel: u8) -> u32 {
+    (channel as u32 * 100 + 127) / 255
+}
+
+// Exact colors when they fit the registers, otherwise 6x7x6 levels of red, green and blue
+fn sixel_color(pixel: &Rgba<u8>, exact: bool) -> [u8; 3] {
+    if exact {
+        return [pixel[0], pixel[1], pixel[2]];
+    }
+    let level = |c: u8, levels: u32| ((c as u32 * (levels - 1) + 127) / 255 * 255 / (levels - 1)) as u8;
+    [level(pixel[0], 6), level(pixel[1], 7), level(pixel[2], 6)]
+}
+
+fn write_sixel(image: &RgbaImage, w: &mut dyn Write) -> std::io::Result<()> {
+    let distinct = image.pixels().filter(|pixel| pixel[3] > 0).map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect::<HashSet<_>>();
+    let exact = distinct.len() <= SIXEL_REGISTERS;
+
+    let mut registers: HashMap<[u8; 3], usize> = HashMap::new();
+    let mut indices = Vec::with_capacity((image.width() * image.height()) as usize);
+    for pixel in image.pixels() {
+        indices.push((pixel[3] > 0).then(|| {
+            let color = sixel_color(pixel, exact);
+            let next = registers.len();
+            *registers.entry(color).or_insert(next)
+        }));
+    }
+
+    // Transparent pixels (P2 = 1) leave the terminal's background showing
+    write!(w, "\x1bP0;1q\"1;1;{};{}", image.width(), image.height())?;
+    let mut colors: Vec<(&[u8; 3], &usize)> = registers.iter().collect();
+    colors.sort_by_key(|&(_, &register)| register);
+    for (color, register) in colors {
+        write!(w, "#{};2;{};{};{}", register, percent(color[0]), percent(color[1]), percent(color[2]))?;
+    }
+
+    let width = image.width() as usize;
+    for band in (0..image.height() as usize).step_by(6) {
+        let rows = (image.height() as usize - band).min(6);
+        let mut used: Vec<usize> = (band * width..(band + rows) * width).filter_map(|i| indices[i]).collect();
+        used.sort_unstable();
+        used.dedup();
+        for (n, &register) in used.iter().enumerate() {
+            write!(w, "#{}", register)?;
+            let sixels: Vec<u8> = (0..width)
+                .map(|x| (0..rows).filter(|&dy| indices[(band + dy) * width + x] == Some(register)).fold(0, |bits, dy| bits | 1 << dy) + 63)
+                .collect();
+            // Runs of the same sixel are written as `!count` and the sixel
+            let mut x = 0;
+            while x < sixels.len() {
+                let run = sixels[x..].iter().take_while(|&&sixel| sixel == sixels[x]).count();
+                if run > 3 {
+                    write!(w, "!{}{}", run, sixels[x] as char)?;
+                } else {
+                    w.write_all(&sixels[x..x + run])?;
+                }
+                x += run;
+            }
+            w.write_all(if n + 1 < used.len() { b"$" } else { b"" })?;
+        }
+        w.write_all(b"-")?;
+    }
+    writeln!(w, "\x1b\\")
+}
+
+/// Print `input` to the terminal, at most `width` characters (ANSI) or pixels (Sixel) wide.
+/// ANSI fills the terminal's width by default; Sixel scales small images up by whole steps.
+/// `dither` applies to the palette terminals.
+pub fn run(input: &Path, width: Option<u32>, terminal: Terminal, dither: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if width == Some(0) {
+        return Err(failure::bad_input("Width must be greater than 0"));
+    }
+    if dither && matches!(terminal, Terminal::Ansi | Terminal::Sixel) {
+        return Err(failure::bad_input("--dither needs --terminal xterm256 or ansi16"));
+    }
+    let image = load(input)?;
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let written = match terminal {
+        Terminal::Ansi => write_ansi(&fit(image, width.unwrap_or_else(terminal_columns)), &mut out),
+        Terminal::Sixel => {
+            let image = match width {
+                Some(width) => fit(image, width),
+                None => {
+                    let scale = (SIXEL_WIDTH / image.width().max(1)).max(1);
+                    imageops::resize(&image, image.width() * scale, image.height() * scale, imageops::FilterType::Nearest)
+                }
+            };
+            write_sixel(&image, &mut out)
+        }
+        Terminal::Xterm256 => write_palette(&fit(image, width.unwrap_or_else(terminal_columns)), Preset::Xterm256, dither, &mut out),
+        Terminal::Ansi16 => write_palette(&fit(image, width.unwrap_or_else(terminal_columns)), Preset::Ansi16, dither, &mut out),
+    };
+    written.and_then(|()| out.flush()).map_err(failure::write)?;
+    Ok(())
+}
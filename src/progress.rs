@@ -0,0 +1,45 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Progress bar over `len` rows, drawn on stderr; hidden when `quiet` is set.
+pub fn rows(len: u64, quiet: bool) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {bar:40} {pos}/{len} rows (ETA {eta}) {msg}")
+            .expect("valid progress template"),
+    );
+    bar
+}
+
+/// Progress bar over a download of `len` bytes, when the server tells; hidden when `quiet` is set.
+pub fn bytes(len: Option<u64>, quiet: bool) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    let Some(len) = len else {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {spinner} {bytes} {msg}").expect("valid progress template"));
+        return bar;
+    };
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {bar:40} {bytes}/{total_bytes} (ETA {eta}) {msg}")
+            .expect("valid progress template"),
+    );
+    bar
+}
+
+/// Progress bar over `len` files of a batch; hidden when `quiet` is set.
+pub fn files(len: u64, quiet: bool) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {bar:40} {pos}/{len} files (ETA {eta})")
+            .expect("valid progress template"),
+    );
+    bar
+}
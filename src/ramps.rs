@@ -0,0 +1,91 @@
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+use tracing::info;
+
+use crate::failure::{self, Kind};
+use crate::{MapFile, hex_to_rgba, mapped, palette};
+
+// D65 white point of sRGB, for CIELAB
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+
+// How far the ends of a ramp go from its base: the darkest shade keeps this much of the base's
+// lightness, the lightest closes this much of the gap to white, and both keep this much chroma
+const DARKEST: f64 = 0.2;
+const LIGHTEST: f64 = 0.85;
+const END_CHROMA: f64 = 0.5;
+
+pub fn to_lab(color: Rgba<u8>) -> [f64; 3] {
+    let [r, g, b] = [color[0], color[1], color[2]].map(|c| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    });
+    let xyz = [0.4124 * r + 0.3576 * g + 0.1805 * b, 0.2126 * r + 0.7152 * g + 0.0722 * b, 0.0193 * r + 0.1192 * g + 0.9505 * b];
+    let [x, y, z] = [0, 1, 2].map(|i| {
+        let t = xyz[i] / WHITE[i];
+        if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 }
+    });
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+fn from_lab([l, a, b]: [f64; 3], alpha: u8) -> Rgba<u8> {
+    let y = (l + 16.0) / 116.0;
+    let xyz = [y + a / 500.0, y, y - b / 200.0].map(|t| if t.powi(3) > 216.0 / 24389.0 { t.powi(3) } else { (116.0 * t - 16.0) * 27.0 / 24389.0 });
+    let [x, y, z] = [0, 1, 2].map(|i| xyz[i] * WHITE[i]);
+    let rgb = [3.2406 * x - 1.5372 * y - 0.4986 * z, -0.9689 * x + 1.8758 * y + 0.0415 * z, 0.0557 * x - 0.2040 * y + 1.0570 * z].map(|c| {
+        let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (c.clamp(0.0, 1.0) * 255.0).round() as u8
+    });
+    Rgba([rgb[0], rgb[1], rgb[2], alpha])
+}
+
+/// `steps` shades of `base` from dark to light, made in CIELAB so they step evenly in perceived
+/// lightness; `base` itself sits in the middle (just past it for an even count).
+fn ramp(base: Rgba<u8>, steps: usize) -> Vec<Rgba<u8>> {
+    let [l, a, b] = to_lab(base);
+    let darkest = [l * DARKEST, a * END_CHROMA, b * END_CHROMA];
+    let lightest = [l + (100.0 - l) * LIGHTEST, a * END_CHROMA, b * END_CHROMA];
+    let middle = steps / 2;
+    (0..steps)
+        .map(|i| {
+            let (end, t) = if i < middle { (darkest, (middle - i) as f64 / middle as f64) } else if i > middle { (lightest, (i - middle) as f64 / (steps - 1 - middle) as f64) } else { (darkest, 0.0) };
+            from_lab([0, 1, 2].map(|c| [l, a, b][c] + (end[c] - [l, a, b][c]) * t), base[3])
+        })
+        .collect()
+}
+
+/// Make a ramp of `steps` shades for each color of the map in `input` that isn't fully
+/// transparent, in ID order, and write them one ramp after another to the palette file `output`
+/// and, with `swatch`, as rows of `scale`-pixel squares to that image.
+pub fn run(input: &Path, output: &Path, steps: usize, swatch: Option<&Path>, scale: u32) -> Result<(), Box<dyn std::error::Error>> {
+    if scale == 0 {
+        return Err(failure::bad_input("Scale must be greater than 0"));
+    }
+    let MapFile { colors, .. } = serde_json::from_slice(&mapped::map_file(input)?)?;
+    let mut ids: Vec<_> = colors.iter().collect();
+    ids.sort_by_key(|&(id, _)| id);
+    let mut bases: Vec<Rgba<u8>> = Vec::new();
+    for (_, hex) in ids {
+        let color = hex_to_rgba(hex).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+        if color[3] > 0 && !bases.contains(&color) {
+            bases.push(color);
+        }
+    }
+    if bases.is_empty() {
+        return Err(failure::bad_input("Map has no opaque colors to make ramps from"));
+    }
+
+    let ramps: Vec<Vec<Rgba<u8>>> = bases.iter().map(|&base| ramp(base, steps)).collect();
+    let name = input.file_stem().unwrap_or_default().to_string_lossy();
+    palette::write(output, &format!("{} ramps", name), &ramps.concat(), steps).map_err(failure::write)?;
+    info!(ramps = ramps.len(), steps, output = %output.display(), "wrote ramps");
+
+    if let Some(swatch) = swatch {
+        let (width, height) = (steps as u64 * scale as u64, ramps.len() as u64 * scale as u64);
+        if width > u32::MAX as u64 || height > u32::MAX as u64 || mapped::too_large(width as u32, height as u32) {
+            return Err(failure::bad_input(format!("The swatch would be {}x{} pixels, too large to hold; use a smaller --scale", width, height)));
+        }
+        let image = RgbaImage::from_fn(width as u32, height as u32, |x, y| ramps[(y / scale) as usize][(x / scale) as usize]);
+        image.save(swatch).map_err(failure::write)?;
+    }
+    Ok(())
+}
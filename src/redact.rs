@@ -0,0 +1,115 @@
+use image::{DynamicImage, GrayImage, Luma, RgbaImage};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::info;
+
+#[cfg(feature = "faces")]
+use crate::faces::Faces;
+use crate::{failure, mapped};
+
+/// Rectangle of an image in pixels, from `x,y,w,h`.
+#[derive(Clone, Copy, Debug)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Result<Vec<u32>, _> = s.split(',').map(|part| part.trim().parse()).collect();
+        match parts.as_deref() {
+            Ok(&[x, y, width, height]) if width > 0 && height > 0 => Ok(Region { x, y, width, height }),
+            _ => Err(format!("Expected a region as x,y,w,h with a width and height above 0, got {}", s)),
+        }
+    }
+}
+
+/// Pixels to pixelate: inside any of `regions`, or light and opaque in the image at `mask`.
+fn selection(width: u32, height: u32, regions: &[Region], mask: Option<&Path>) -> Result<GrayImage, Box<dyn std::error::Error>> {
+    let mut selected = match mask {
+        Some(path) => {
+            let mask = mapped::open_image(path, false)?.into_luma_alpha8();
+            if mask.dimensions() != (width, height) {
+                return Err(failure::bad_input(format!("Mask {} is {}x{}, the image is {}x{}", path.display(), mask.width(), mask.height(), width, height)));
+            }
+            GrayImage::from_fn(width, height, |x, y| {
+                let [luma, alpha] = mask.get_pixel(x, y).0;
+                Luma([if luma >= 128 && alpha >= 128 { 255 } else { 0 }])
+            })
+        }
+        None => GrayImage::new(width, height),
+    };
+    for region in regions {
+        for y in region.y.min(height)..region.y.saturating_add(region.height).min(height) {
+            for x in region.x.min(width)..region.x.saturating_add(region.width).min(width) {
+                selected.put_pixel(x, y, Luma([255]));
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Pixelate the `selected` pixels of `image` in blocks of `block_size` on the image's grid,
+/// each filled with the average of its selected pixels so nothing from outside leaks in.
+fn pixelate(image: &mut RgbaImage, selected: &GrayImage, block_size: u32) {
+    let (width, height) = image.dimensions();
+    for top in (0..height).step_by(block_size as usize) {
+        for left in (0..width).step_by(block_size as usize) {
+            let block = || (top..(top + block_size).min(height)).flat_map(move |y| (left..(left + block_size).min(width)).map(move |x| (x, y))).filter(|&(x, y)| selected.get_pixel(x, y)[0] > 0);
+            let mut sum = [0u64; 4];
+            let mut count = 0;
+            for (x, y) in block() {
+                for (total, &c) in sum.iter_mut().zip(&image.get_pixel(x, y).0) {
+                    *total += c as u64;
+                }
+                count += 1;
+            }
+            if count == 0 {
+                continue;
+            }
+            let color = pixel::block_color(sum, count);
+            for (x, y) in block() {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Pixelate only the `regions` and the light parts of `mask` of each image in `jobs` in blocks
+/// of `block_size`, along with the faces found with the SeetaFace model at `face_model`, and
+/// write the rest of it untouched to the output paired with it.
+pub fn run(jobs: &[(PathBuf, PathBuf)], block_size: u32, regions: &[Region], mask: Option<&Path>, face_model: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "faces")]
+    let mut faces = face_model.map(Faces::new).transpose()?;
+    #[cfg(not(feature = "faces"))]
+    if face_model.is_some() {
+        return Err(failure::bad_input("This build has no face detection; rebuild with --features faces"));
+    }
+
+    for (input, output) in jobs {
+        let original = mapped::open_image(input, true)?;
+        let mut image = original.to_rgba8();
+        #[cfg(feature = "faces")]
+        let regions = &match &mut faces {
+            Some(faces) => {
+                let found = faces.find(&original);
+                info!(input = %input.display(), faces = found.len(), "found faces");
+                [regions, &found].concat()
+            }
+            None => regions.to_vec(),
+        };
+        let (width, height) = image.dimensions();
+        let selected = selection(width, height, regions, mask)?;
+        pixelate(&mut image, &selected, block_size);
+
+        // Keep images without alpha that way, for formats such as JPEG that can't store it
+        let redacted = if original.color().has_alpha() { DynamicImage::ImageRgba8(image) } else { DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()) };
+        redacted.save(output).map_err(failure::write)?;
+        info!(input = %input.display(), output = %output.display(), "redacted");
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::io;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::{cache, failure, progress};
+
+fn is_url(input: &Path) -> bool {
+    let input = input.to_string_lossy();
+    ["http://", "https://", "s3://"].iter().any(|scheme| input.starts_with(scheme))
+}
+
+// Public S3 objects are plain HTTPS downloads; private ones would need signed requests
+fn http_url(url: &str) -> String {
+    match url.strip_prefix("s3://").and_then(|rest| rest.split_once('/')) {
+        Some((bucket, key)) => format!("https://{}.s3.amazonaws.com/{}", bucket, key),
+        None => url.to_string(),
+    }
+}
+
+fn downloads_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("pixel").join("downloads")
+}
+
+/// What the server said identifies a download, stored next to it to ask whether it changed.
+#[derive(Serialize, Deserialize, Default)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Download `url` into the cache and return its path there. A cached copy is used if the
+/// server answers that it hasn't changed since, by its ETag or Last-Modified date, or can't be
+/// reached; without either it is downloaded again. The file keeps the URL's file name, so
+/// formats are still recognized and outputs named after it.
+fn fetch(url: &str, quiet: bool) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let name = url.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or("download");
+    let dir = downloads_dir().join(format!("{:016x}", cache::hash_pixels(cache::HASH_SEED, url.as_bytes())));
+    let path = dir.join(name);
+    let validators_path = dir.join(".validators.json");
+    let cached = path.is_file().then(|| fs::read(&validators_path).ok().and_then(|json| serde_json::from_slice::<Validators>(&json).ok()).unwrap_or_default());
+
+    let mut request = ureq::get(&http_url(url));
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header("If-None-Match", etag);
+        }
+        if let Some(date) = &cached.last_modified {
+            request = request.header("If-Modified-Since", date);
+        }
+    }
+    let response = match request.call() {
+        Ok(response) if response.status() == 304 && cached.is_some() => {
+            debug!(url, path = %path.display(), "using cached download");
+            return Ok(path);
+        }
+        Ok(response) => response,
+        Err(e) if cached.is_some() => {
+            warn!(url, error = %e, "can't check for a newer download, using the cached one");
+            return Ok(path);
+        }
+        Err(e) => return Err(failure::bad_input(format!("Can't download {}: {}", url, e))),
+    };
+    let header = |name: &str| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let validators = Validators { etag: header("etag"), last_modified: header("last-modified") };
+    let len = response.headers().get("content-length").and_then(|len| len.to_str().ok()?.parse().ok());
+    let bar = progress::bytes(len, quiet);
+    bar.set_message(name.to_string());
+
+    fs::create_dir_all(&dir).map_err(failure::write)?;
+    let mut file = tempfile::NamedTempFile::new_in(&dir).map_err(failure::write)?;
+    io::copy(&mut bar.wrap_read(response.into_body().into_reader()), &mut file).map_err(|e| failure::bad_input(format!("Can't download {}: {}", url, e)))?;
+    bar.finish_and_clear();
+    file.persist(&path).map_err(failure::write)?;
+    if validators.etag.is_some() || validators.last_modified.is_some() {
+        fs::write(&validators_path, serde_json::to_vec(&validators)?).map_err(failure::write)?;
+    } else {
+        let _ = fs::remove_file(&validators_path);
+    }
+    Ok(path)
+}
+
+/// Replace `http(s)://` and `s3://` URLs among `inputs` with downloaded copies, cached by URL
+/// in `$XDG_CACHE_HOME/pixel/downloads` and downloaded again when they change.
+pub fn resolve(inputs: &[PathBuf], quiet: bool) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    inputs.iter().map(|input| if is_url(input) { fetch(&input.to_string_lossy(), quiet) } else { Ok(input.clone()) }).collect()
+}
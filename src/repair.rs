@@ -0,0 +1,67 @@
+use clap::ValueEnum;
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::failure::{self, Kind};
+
+// Offending rows listed in the error before the rest are only counted
+const SHOWN_ROWS: usize = 10;
+
+/// How to even out a ragged matrix, whose rows differ in width.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Repair {
+    /// Fill short rows up to the widest with transparent cells
+    Pad,
+    /// Cut long rows down to the narrowest
+    Truncate,
+}
+
+/// ID of a fully transparent color in `colors`, added under a new ID when there's none.
+pub fn transparent_id(colors: &mut HashMap<u32, String>) -> u32 {
+    if let Some(id) = colors.iter().filter(|(_, hex)| pixel::hex_to_rgba(hex).is_ok_and(|color| color[3] == 0)).map(|(&id, _)| id).min() {
+        return id;
+    }
+    let id = colors.keys().max().map_or(0, |id| id + 1);
+    colors.insert(id, "#00000000".to_string());
+    id
+}
+
+/// Check that every row of `matrix` is as wide as the first. Ragged matrices are evened out
+/// with `repair` to their widest or narrowest row, or refused with the offending rows. `name`
+/// says which matrix in messages, as in "Frame 3".
+pub fn check(matrix: &mut [Vec<u32>], colors: &mut HashMap<u32, String>, repair: Option<Repair>, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let ragged = pixel::ragged_rows(matrix);
+    if ragged.is_empty() {
+        return Ok(());
+    }
+    let Some(repair) = repair else {
+        let mut rows: Vec<String> = ragged.iter().take(SHOWN_ROWS).map(usize::to_string).collect();
+        if ragged.len() > SHOWN_ROWS {
+            rows.push(format!("and {} more", ragged.len() - SHOWN_ROWS));
+        }
+        let (noun, verb) = if ragged.len() == 1 { ("row", "differs") } else { ("rows", "differ") };
+        return Err(failure::tag(
+            Kind::InvalidJson,
+            format!("{} is ragged: {} {} {} in width from row 0 ({} cells); --repair pad or --repair truncate evens them out", name, noun, rows.join(", "), verb, matrix[0].len()),
+        ));
+    };
+
+    let widths = matrix.iter().map(Vec::len);
+    match repair {
+        Repair::Pad => {
+            let width = widths.max().unwrap_or(0);
+            let fill = transparent_id(colors);
+            for row in matrix.iter_mut() {
+                row.resize(width, fill);
+            }
+        }
+        Repair::Truncate => {
+            let width = widths.min().unwrap_or(0);
+            for row in matrix.iter_mut() {
+                row.truncate(width);
+            }
+        }
+    }
+    warn!(matrix = name, rows = ragged.len(), width = matrix[0].len(), "repaired ragged rows");
+    Ok(())
+}
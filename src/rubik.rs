@@ -0,0 +1,84 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::Output;
+use crate::chart;
+use crate::failure::{self, Kind};
+
+const FACE: usize = 3;
+
+/// Standard sticker colors: letter, name and color as `0xRRGGBB`.
+const STICKERS: [(char, &str, u32); 6] = [
+    ('W', "White", 0xffffff),
+    ('Y', "Yellow", 0xffd500),
+    ('R', "Red", 0xb71234),
+    ('O', "Orange", 0xff5800),
+    ('B', "Blue", 0x0046ad),
+    ('G', "Green", 0x009b48),
+];
+
+#[derive(Serialize)]
+struct Face {
+    /// Position in the mosaic, in cubes from the top-left
+    column: usize,
+    row: usize,
+    /// Sticker letters, top row first
+    stickers: [String; FACE],
+}
+
+#[derive(Serialize)]
+struct Plan {
+    cubes_across: usize,
+    cubes_down: usize,
+    cube_count: usize,
+    /// Letter to color name
+    colors: BTreeMap<char, &'static str>,
+    /// Stickers of each color showing across the mosaic
+    counts: BTreeMap<char, usize>,
+    faces: Vec<Face>,
+}
+
+/// Plan a Rubik's cube mosaic: the map matched to the six sticker colors and cut into 3x3
+/// faces, one per cube, written to `output` as JSON. Transparent cells and the padding that
+/// rounds the map up to whole cubes are white.
+pub fn write(map: &Output, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let catalog = STICKERS.map(|(_, _, rgb)| rgb);
+    let matched = chart::match_colors(map, &catalog).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    let letter = |x: usize, y: usize| {
+        let cell = matched.cells.get(y).and_then(|row| row.get(x)).copied().flatten();
+        cell.map_or('W', |i| STICKERS[matched.used[i].0].0)
+    };
+
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let (across, down) = (width.div_ceil(FACE), map.matrix.len().div_ceil(FACE));
+    let mut counts = BTreeMap::new();
+    let mut faces = Vec::with_capacity(across * down);
+    for row in 0..down {
+        for column in 0..across {
+            let stickers = std::array::from_fn(|dy| {
+                (0..FACE)
+                    .map(|dx| {
+                        let sticker = letter(column * FACE + dx, row * FACE + dy);
+                        *counts.entry(sticker).or_insert(0) += 1;
+                        sticker
+                    })
+                    .collect()
+            });
+            faces.push(Face { column, row, stickers });
+        }
+    }
+
+    let plan = Plan {
+        cubes_across: across,
+        cubes_down: down,
+        cube_count: across * down,
+        colors: STICKERS.iter().map(|&(letter, name, _)| (letter, name)).collect(),
+        counts,
+        faces,
+    };
+    serde_json::to_writer_pretty(BufWriter::new(File::create(output)?), &plan)?;
+    Ok(())
+}
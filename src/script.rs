@@ -0,0 +1,52 @@
+use std::fs;
+use std::path::Path;
+
+use crate::failure;
+use crate::preset::Preset;
+
+// SGR parameters of a cell, `None` for transparent cells, which keep the terminal's colors
+fn sgr(preset: Preset, id: Option<&u32>, background: bool) -> Option<String> {
+    match id {
+        Some(&id) if id > 0 => preset.sgr(id as usize - 1, background),
+        _ => None,
+    }
+}
+
+/// Write a POSIX shell script printing `matrix` (IDs from a terminal preset map: palette
+/// position + 1, 0 transparent) with escape codes, two rows per line in half blocks, e.g. for a
+/// MOTD banner.
+pub fn write(path: &Path, preset: Preset, matrix: &[Vec<u32>]) -> Result<(), Box<dyn std::error::Error>> {
+    if preset.sgr(0, false).is_none() {
+        return Err(failure::bad_input(format!("{} is not a terminal palette; use --preset xterm256 or ansi16", preset.name())));
+    }
+    let width = matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let mut script = String::from("#!/bin/sh\n");
+    for rows in matrix.chunks(2) {
+        let mut line = String::new();
+        let mut current = None;
+        for x in 0..width {
+            let top = sgr(preset, rows[0].get(x), false);
+            let bottom = rows.get(1).and_then(|row| row.get(x));
+            // The lower half block takes the foreground when only the bottom cell has a color
+            let (glyph, style) = match (top, sgr(preset, bottom, true)) {
+                (Some(top), Some(bottom)) => ('▀', format!("0;{};{}", top, bottom)),
+                (Some(top), None) => ('▀', format!("0;{}", top)),
+                (None, Some(_)) => ('▄', format!("0;{}", sgr(preset, bottom, false).unwrap_or_default())),
+                (None, None) => (' ', "0".to_string()),
+            };
+            if current.as_ref() != Some(&style) {
+                line.push_str(&format!("\\033[{}m", style));
+                current = Some(style);
+            }
+            line.push(glyph);
+        }
+        script.push_str(&format!("printf '{}\\033[0m\\n'\n", line));
+    }
+    fs::write(path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
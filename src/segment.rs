@@ -0,0 +1,196 @@
+use clap::{Args, ValueEnum};
+use image::RgbaImage;
+use pixel::{ColorMapper, PixelError};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::failure;
+use crate::matrix::Matrix;
+use crate::voronoi::{Seeding, Sites};
+use crate::{grid, mapped, slic, svg};
+
+/// Irregular cells an image is split into instead of a grid; recorded in maps as `segmentation`.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Segmentation {
+    /// Voronoi cells around scattered sites, each taking the pixels nearer its site than any
+    /// other; the map lists the sites as `[x, y]` in pixels
+    Voronoi,
+    /// SLIC superpixels, compact cells that follow edges in the image; the map holds the cell
+    /// of each pixel in `labels`, a row per line
+    Slic,
+}
+
+impl Segmentation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Segmentation::Voronoi => "voronoi",
+            Segmentation::Slic => "slic",
+        }
+    }
+}
+
+/// Options of the commands that map images into segments.
+#[derive(Args, Clone, Debug)]
+pub struct SegmentOptions {
+    /// Split the image into irregular cells instead of blocks, ignoring the block size; the map
+    /// holds the cells and a color ID for each in `cells` instead of a matrix
+    #[arg(long, value_enum, conflicts_with_all = ["grid", "edges", "edge_aware", "depth", "tile_rows", "cache", "cluster", "preset", "sheet", "delta", "dry_run"])]
+    pub segmentation: Option<Segmentation>,
+
+    /// Number of cells to split the image into; SLIC ends up with about as many
+    #[arg(long, default_value_t = 500, value_parser = clap::value_parser!(u32).range(1..))]
+    segments: u32,
+
+    /// How the sites of Voronoi cells are placed
+    #[arg(long, value_enum, default_value_t)]
+    seeding: Seeding,
+
+    /// Seed for placing sites; the same seed places them the same way
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// How much SLIC favors compact, regular cells over following colors closely (1 to 40
+    /// are typical)
+    #[arg(long, default_value_t = 10.0)]
+    compactness: f64,
+}
+
+impl SegmentOptions {
+    /// Split `image` into cells and assign each the color ID of its average color; cells left
+    /// without pixels are transparent.
+    pub fn map_image(&self, image: &RgbaImage, mapper: &mut ColorMapper) -> Result<SegmentMap, Box<dyn std::error::Error>> {
+        let Some(segmentation) = self.segmentation else {
+            return Err(failure::bad_input("No segmentation to map with"));
+        };
+        let (width, height) = image.dimensions();
+        let count = self.segments as usize;
+        if count as u64 > width as u64 * height as u64 {
+            return Err(failure::bad_input(format!("{} segments are more than the {}x{} pixels of the image", count, width, height)));
+        }
+        let mut map = SegmentMap { segmentation, width, height, sites: Vec::new(), labels: Vec::new(), cells: Vec::new(), colors: HashMap::new() };
+        match segmentation {
+            Segmentation::Voronoi => {
+                let sites = Sites::seed(width, height, count, self.seeding, self.seed);
+                map.cells = grid::average_cells(image, (count, 1), |x, y| (0, sites.nearest([x, y]).unwrap_or_default() as i64), mapper).swap_remove(0);
+                map.sites = sites.points().to_vec();
+            }
+            Segmentation::Slic => {
+                if !self.compactness.is_finite() || self.compactness <= 0.0 {
+                    return Err(failure::bad_input("--compactness must be greater than 0"));
+                }
+                (map.labels, map.cells) = slic::segment(image, count, self.compactness, mapper);
+            }
+        }
+        map.colors = mapper.id_to_color.clone();
+        Ok(map)
+    }
+}
+
+/// A segmented map: the cells of a `width` x `height` image and the color ID of each.
+#[derive(Deserialize)]
+pub struct SegmentMap {
+    segmentation: Segmentation,
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    sites: Vec<[f64; 2]>,
+    #[serde(default)]
+    labels: Vec<Vec<u32>>,
+    cells: Vec<u32>,
+    colors: HashMap<u32, String>,
+}
+
+impl SegmentMap {
+    pub fn segmentation(&self) -> Segmentation {
+        self.segmentation
+    }
+
+    pub fn cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn colors(&self) -> &HashMap<u32, String> {
+        &self.colors
+    }
+
+    fn check(&self) -> Result<(), PixelError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(PixelError::Parse("Segmented map has no pixels".to_string()));
+        }
+        match self.segmentation {
+            Segmentation::Voronoi if self.cells.is_empty() || self.sites.len() != self.cells.len() => Err(PixelError::Parse(format!("Voronoi map has {} sites for {} cells", self.sites.len(), self.cells.len()))),
+            Segmentation::Slic if self.labels.len() != self.height as usize || self.labels.iter().any(|row| row.len() != self.width as usize) => Err(PixelError::Parse(format!("SLIC map needs {} rows of {} labels", self.height, self.width))),
+            Segmentation::Slic if self.labels.iter().flatten().any(|&label| label as usize >= self.cells.len()) => Err(PixelError::Parse(format!("SLIC map has labels past its {} cells", self.cells.len()))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the map as JSON: its segmentation and size, then a site per line or a row of
+    /// labels per line, and the color ID of each cell.
+    pub fn write_json(&self, w: &mut dyn Write) -> io::Result<()> {
+        writeln!(w, "{{\n  \"segmentation\": \"{}\",\n  \"width\": {},\n  \"height\": {},", self.segmentation.name(), self.width, self.height)?;
+        match self.segmentation {
+            Segmentation::Voronoi => {
+                w.write_all(b"  \"sites\": [\n")?;
+                for (i, [x, y]) in self.sites.iter().enumerate() {
+                    write!(w, "{}    [{},{}]", if i == 0 { "" } else { ",\n" }, x, y)?;
+                }
+            }
+            Segmentation::Slic => {
+                w.write_all(b"  \"labels\": [\n")?;
+                for (i, row) in self.labels.iter().enumerate() {
+                    write!(w, "{}    ", if i == 0 { "" } else { ",\n" })?;
+                    serde_json::to_writer(&mut *w, row)?;
+                }
+            }
+        }
+        w.write_all(b"\n  ],\n  \"cells\": [")?;
+        for (i, id) in self.cells.iter().enumerate() {
+            write!(w, "{}{}", if i == 0 { "" } else { "," }, id)?;
+        }
+        w.write_all(b"],\n  \"colors\": ")?;
+        serde_json::to_writer_pretty(&mut *w, &self.colors.iter().collect::<BTreeMap<_, _>>())?;
+        w.write_all(b"\n}")
+    }
+
+    /// Paint the map `scale` pixels per pixel of the image it was made from. IDs missing from
+    /// the colors become transparent and are passed to `missing`.
+    pub fn render(&self, scale: u32, missing: impl FnMut(u32)) -> Result<RgbaImage, PixelError> {
+        self.check()?;
+        let (width, height) = (self.width as u64 * scale as u64, self.height as u64 * scale as u64);
+        if width > u32::MAX as u64 || height > u32::MAX as u64 || mapped::too_large(width as u32, height as u32) {
+            return Err(PixelError::InvalidArgument(format!("Map of {}x{} pixels is too large for an image at scale {}", self.width, self.height, scale)));
+        }
+        let size = (width as f64, height as f64);
+        let scale = scale as f64;
+        match self.segmentation {
+            Segmentation::Voronoi => {
+                let sites = Sites::new(&self.sites, self.width, self.height);
+                grid::paint_cells(std::slice::from_ref(&self.cells), &self.colors, size, |x, y| (0, sites.nearest([x / scale, y / scale]).unwrap_or_default() as i64), missing)
+            }
+            Segmentation::Slic => grid::paint_cells(std::slice::from_ref(&self.cells), &self.colors, size, |x, y| (0, self.labels[(y / scale) as usize][(x / scale) as usize] as i64), missing),
+        }
+    }
+
+    /// Write the map as an SVG a unit per pixel of the image: one polygon per Voronoi cell, or
+    /// runs of pixels as for square maps.
+    pub fn write_svg(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.check().map_err(|e| failure::tag(failure::Kind::InvalidJson, e))?;
+        match self.segmentation {
+            Segmentation::Voronoi => {
+                let sites = Sites::new(&self.sites, self.width, self.height);
+                let polygons: Vec<(u32, Vec<[f64; 2]>)> = self.cells.iter().enumerate().map(|(i, &id)| (id, sites.cell(i, self.width, self.height))).collect();
+                svg::write_polygons(&polygons, (self.width, self.height), &self.colors, path)
+            }
+            Segmentation::Slic => {
+                let matrix = self.labels.iter().map(|row| row.iter().map(|&label| self.cells[label as usize]).collect()).collect();
+                svg::write(&Matrix::Memory(matrix), &self.colors, path)
+            }
+        }
+        .map_err(failure::write)?;
+        Ok(())
+    }
+}
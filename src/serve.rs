@@ -0,0 +1,137 @@
+use image::ImageFormat;
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+
+use crate::edges::Blocks;
+use crate::failure::{self, Kind};
+use crate::notify::{self, RequestSummary};
+use crate::stream::ImageRows;
+use crate::{ColorMapper, MapFile, animation, map_rows, render_map, write_json};
+
+// Largest upload accepted, in bytes
+const MAX_BODY: u64 = 256 * 1024 * 1024;
+
+type Reply = (Vec<u8>, &'static str);
+
+fn query(url: &str) -> HashMap<&str, &str> {
+    url.split_once('?').map_or("", |(_, query)| query).split('&').filter_map(|pair| pair.split_once('=')).collect()
+}
+
+fn param<T: std::str::FromStr>(params: &HashMap<&str, &str>, name: &str, default: T) -> Result<T, Box<dyn std::error::Error>> {
+    match params.get(name) {
+        Some(value) => value.parse().map_err(|_| failure::bad_input(format!("Invalid {}: {}", name, value))),
+        None => Ok(default),
+    }
+}
+
+fn map(body: &[u8], block_size: u32, tolerance: f64) -> Result<Reply, Box<dyn std::error::Error>> {
+    if block_size == 0 {
+        return Err(failure::bad_input("Block size must be greater than 0"));
+    }
+    let image = image::load_from_memory(body)?;
+    let mut mapper = ColorMapper::new(tolerance);
+    let matrix = map_rows(&mut ImageRows::new(image), Blocks::plain(block_size), &mut mapper, None, None, &ProgressBar::hidden())?;
+    let mut json = Vec::new();
+    write_json(&matrix, &mapper.id_to_color, &[], &mut json)?;
+    Ok((json, "application/json"))
+}
+
+// Single maps come back as PNG, maps with frames as APNG
+fn reconstruct(body: &[u8]) -> Result<Reply, Box<dyn std::error::Error>> {
+    let data: MapFile = serde_json::from_slice(body)?;
+    data.grid.require_square()?;
+    match (data.matrix, data.frames) {
+        (Some(matrix), None) => {
+            let mut png = Cursor::new(Vec::new());
+            render_map(&matrix, &data.colors, &ProgressBar::hidden())?.write_to(&mut png, ImageFormat::Png).map_err(failure::write)?;
+            Ok((png.into_inner(), "image/png"))
+        }
+        (None, Some(frames)) => {
+            if frames.is_empty() {
+                return Err(failure::tag(Kind::InvalidJson, "Map has no frames"));
+            }
+            let frames = animation::resolve(frames).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+            let mut images = Vec::with_capacity(frames.len());
+            for (delay, matrix) in &frames {
+                images.push((*delay, render_map(matrix, &data.colors, &ProgressBar::hidden())?));
+            }
+            if images.iter().any(|(_, image)| image.dimensions() != images[0].1.dimensions()) {
+                return Err(failure::tag(Kind::InvalidJson, "Frames differ in size"));
+            }
+            let file = tempfile::Builder::new().suffix(".png").tempfile()?;
+            animation::write(file.path(), &images).map_err(failure::write)?;
+            Ok((std::fs::read(file.path())?, "image/apng"))
+        }
+        _ => Err(failure::tag(Kind::InvalidJson, "Map needs either a matrix or frames")),
+    }
+}
+
+fn route(request: &mut Request, path: &str) -> Result<Reply, Box<dyn std::error::Error>> {
+    let url = request.url().to_string();
+    let mut body = Vec::new();
+    request.as_reader().take(MAX_BODY + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(failure::bad_input(format!("Uploads are limited to {} MiB", MAX_BODY / 1024 / 1024)));
+    }
+
+    let params = query(&url);
+    match path {
+        "/map" => map(&body, 1, param(&params, "tolerance", 0.0)?),
+        "/pixelate" => map(&body, param(&params, "block_size", 10)?, param(&params, "tolerance", 0.0)?),
+        _ => reconstruct(&body),
+    }
+}
+
+// Problems with the request are the client's, anything else the server's
+fn status(error: &(dyn std::error::Error + 'static)) -> u16 {
+    match failure::classify(error) {
+        Kind::BadInput | Kind::Decode | Kind::InvalidJson => 400,
+        Kind::Other | Kind::Write => 500,
+    }
+}
+
+fn handle(mut request: Request, notify_url: Option<&str>) {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    let result = match (request.method(), path.as_str()) {
+        (Method::Post, "/map" | "/pixelate" | "/reconstruct") => route(&mut request, &path).map_err(|e| (status(e.as_ref()), e)),
+        (_, "/map" | "/pixelate" | "/reconstruct") => Err((405, failure::bad_input(format!("{} takes POST", path)))),
+        _ => Err((404, failure::bad_input(format!("No endpoint {}", path)))),
+    };
+    let (status, body, content_type, error) = match result {
+        Ok((body, content_type)) => (200, body, content_type, None),
+        Err((status, e)) => {
+            warn!(method = %request.method(), url = request.url(), status, "{}", e);
+            (status, failure::to_json(e.as_ref()).to_string().into_bytes(), "application/json", Some(e.to_string()))
+        }
+    };
+    let summary = RequestSummary { endpoint: &path, status, bytes: body.len(), error };
+    let header = Header::from_bytes("Content-Type", content_type).expect("static header is valid");
+    if let Err(e) = request.respond(Response::from_data(body).with_status_code(status).with_header(header)) {
+        warn!("failed to send response: {}", e);
+    }
+    if let Some(url) = notify_url {
+        notify::send(url, &summary);
+    }
+}
+
+/// Serve the mapper over HTTP on `host:port` until interrupted, one thread per request:
+///
+/// - `POST /map?tolerance=T` and `POST /pixelate?block_size=N&tolerance=T` take an image and
+///   answer with its JSON map
+/// - `POST /reconstruct` takes a JSON map and answers with a PNG, or an APNG for maps with frames
+///
+/// Errors are answered with the JSON of `--error-format json` and status 400 for bad requests, or 500.
+/// With `notify_url`, each answered request is reported there once its response is sent.
+pub fn run(host: &str, port: u16, notify_url: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::http((host, port)).map_err(|e| failure::bad_input(format!("Can't listen on {}:{}: {}", host, port, e)))?;
+    info!(host, port, "listening");
+    for request in server.incoming_requests() {
+        let notify_url = notify_url.map(str::to_string);
+        thread::spawn(move || handle(request, notify_url.as_deref()));
+    }
+    Ok(())
+}
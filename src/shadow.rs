@@ -0,0 +1,101 @@
+use image::Rgba;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::failure::{self, Kind};
+use crate::matrix::Matrix;
+use crate::{MapFile, animation, hex_to_rgba, mapped, repair, rgba_to_hex, write_frames_json, write_image, write_json};
+
+/// Cells to move the shadow right and down by, from `DX,DY`; negative values move it left and up.
+#[derive(Clone, Copy, Debug)]
+pub struct Offset {
+    x: i32,
+    y: i32,
+}
+
+impl FromStr for Offset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(',').map(|(x, y)| (x.trim().parse(), y.trim().parse())) {
+            Some((Ok(x), Ok(y))) => Ok(Offset { x, y }),
+            _ => Err(format!("Expected an offset in cells as DX,DY, got {}", s)),
+        }
+    }
+}
+
+/// Draw a shadow in `color` at `opacity` under the cells of the map in `input` that aren't fully
+/// transparent, `offset` cells away, and write the result to `output`: a map, or an image when
+/// the extension names one. The canvas grows where the shadow falls outside it.
+pub fn run(input: &Path, output: &Path, offset: Offset, color: Rgba<u8>, opacity: f32) -> Result<(), Box<dyn std::error::Error>> {
+    if !(0.0..=1.0).contains(&opacity) {
+        return Err(failure::bad_input("--opacity must be between 0 and 1"));
+    }
+    let MapFile { matrix, frames, mut colors, grid, .. } = serde_json::from_slice(&mapped::map_file(input)?)?;
+    grid.require_square()?;
+    let animated = frames.is_some();
+    let mut frames = match (matrix, frames) {
+        (Some(matrix), None) => vec![(0, matrix)],
+        (None, Some(frames)) if !frames.is_empty() => animation::resolve(frames).map_err(|e| failure::tag(Kind::InvalidJson, e))?,
+        _ => return Err(failure::tag(Kind::InvalidJson, "Map needs either a matrix or frames")),
+    };
+    for (i, (_, matrix)) in frames.iter_mut().enumerate() {
+        repair::check(matrix, &mut colors, None, &format!("Frame {}", i))?;
+    }
+
+    let mut solid = HashMap::new();
+    for (&id, hex) in &colors {
+        solid.insert(id, hex_to_rgba(hex).map_err(|e| failure::tag(Kind::InvalidJson, e))?[3] > 0);
+    }
+    let is_solid = |id: &u32| solid.get(id).copied().unwrap_or(false);
+
+    // Canvas covering the map and every shadowed cell of every frame, in the map's cells
+    let (width, height) = frames.iter().map(|(_, matrix)| (matrix.first().map_or(0, Vec::len), matrix.len())).max().unwrap_or((0, 0));
+    let (mut left, mut top, mut right, mut bottom) = (0i64, 0i64, width as i64, height as i64);
+    for (_, matrix) in &frames {
+        for (y, row) in matrix.iter().enumerate() {
+            for (x, id) in row.iter().enumerate() {
+                if is_solid(id) {
+                    let (x, y) = (x as i64 + offset.x as i64, y as i64 + offset.y as i64);
+                    (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1));
+                }
+            }
+        }
+    }
+
+    let (canvas_width, canvas_height) = (right - left, bottom - top);
+    if canvas_width > u32::MAX as i64 || canvas_height > u32::MAX as i64 || mapped::too_large(canvas_width as u32, canvas_height as u32) {
+        return Err(failure::bad_input(format!("The shadow makes the map {}x{} cells, too large to hold", canvas_width, canvas_height)));
+    }
+
+    let clear = repair::transparent_id(&mut colors);
+    let shadow = colors.keys().max().map_or(0, |id| id + 1);
+    let alpha = (color[3] as f32 * opacity).round() as u8;
+    colors.insert(shadow, rgba_to_hex(&Rgba([color[0], color[1], color[2], alpha])));
+
+    let shadowed: Vec<(u32, Matrix)> = frames
+        .iter()
+        .map(|(delay, matrix)| {
+            let mut canvas = vec![vec![clear; canvas_width as usize]; canvas_height as usize];
+            for (dx, dy, over) in [(offset.x as i64, offset.y as i64, false), (0, 0, true)] {
+                for (y, row) in matrix.iter().enumerate() {
+                    for (x, id) in row.iter().enumerate().filter(|(_, id)| is_solid(id)) {
+                        canvas[(y as i64 + dy - top) as usize][(x as i64 + dx - left) as usize] = if over { *id } else { shadow };
+                    }
+                }
+            }
+            (*delay, Matrix::Memory(canvas))
+        })
+        .collect();
+
+    if image::ImageFormat::from_path(output).is_ok() {
+        return write_image(output, &shadowed, &colors);
+    }
+    mapped::write_file(output, |w| match shadowed.as_slice() {
+        [(_, matrix)] if !animated => write_json(matrix, &colors, &[], w),
+        _ => write_frames_json(&shadowed, &colors, false, &[], w),
+    })
+    .map_err(failure::write)?;
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::{PixelError, hex_to_rgba};
+use crate::matrix::Matrix;
+
+/// Grid to lay frames out in, from `--sheet cols=N`.
+#[derive(Clone, Copy, Debug)]
+pub struct SheetSpec {
+    pub cols: u32,
+}
+
+impl FromStr for SheetSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cols = s.strip_prefix("cols=").ok_or_else(|| format!("Expected cols=N, got {}", s))?;
+        match cols.parse() {
+            Ok(cols) if cols > 0 => Ok(SheetSpec { cols }),
+            _ => Err(format!("Invalid column count: {}", cols)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Serialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+// Same shape as the JSON (array) export of Aseprite and TexturePacker
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    filename: String,
+    frame: Rect,
+    rotated: bool,
+    trimmed: bool,
+    sprite_source_size: Rect,
+    source_size: Size,
+    duration: u32,
+}
+
+#[derive(Serialize)]
+struct Meta {
+    app: &'static str,
+    image: String,
+    format: &'static str,
+    size: Size,
+    scale: &'static str,
+}
+
+#[derive(Serialize)]
+struct Index {
+    frames: Vec<Entry>,
+    meta: Meta,
+}
+
+/// `<stem>-sheet.png` and `<stem>-sheet.json` next to the map file at `map_path`.
+fn paths_for(map_path: &Path) -> (PathBuf, PathBuf) {
+    let stem = map_path.file_stem().unwrap_or_default().to_string_lossy();
+    (map_path.with_file_name(format!("{}-sheet.png", stem)), map_path.with_file_name(format!("{}-sheet.json", stem)))
+}
+
+/// Render every frame at one pixel per cell into a grid of `spec.cols` columns, and write the
+/// sheet and its frame index next to `map_path`.
+pub fn write(frames: &[(u32, Matrix)], colors: &HashMap<u32, String>, spec: SheetSpec, map_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let palette: HashMap<u32, Rgba<u8>> = colors.iter().map(|(&id, hex)| Ok((id, hex_to_rgba(hex)?))).collect::<Result<_, PixelError>>()?;
+
+    let mut width = 0;
+    let mut height = 0;
+    for (_, matrix) in frames {
+        height = height.max(matrix.len() as u32);
+        matrix.for_each_row(|row| {
+            width = width.max(row.len() as u32);
+            Ok(())
+        })?;
+    }
+    let cols = spec.cols.min(frames.len().max(1) as u32);
+    let rows = (frames.len() as u32).div_ceil(cols);
+    let mut sheet = RgbaImage::new(cols * width, rows * height);
+
+    let (image_path, index_path) = paths_for(map_path);
+    let stem = map_path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut entries = Vec::with_capacity(frames.len());
+    for (i, (delay, matrix)) in frames.iter().enumerate() {
+        let (x0, y0) = (i as u32 % cols * width, i as u32 / cols * height);
+        let mut y = y0;
+        matrix.for_each_row(|row| {
+            for (x, id) in (x0..).zip(row) {
+                // Unknown IDs stay transparent, as in reconstruct
+                if let Some(&color) = palette.get(id) {
+                    sheet.put_pixel(x, y, color);
+                }
+            }
+            y += 1;
+            Ok(())
+        })?;
+        entries.push(Entry {
+            filename: format!("{} {}", stem, i),
+            frame: Rect { x: x0, y: y0, w: width, h: height },
+            rotated: false,
+            trimmed: false,
+            sprite_source_size: Rect { x: 0, y: 0, w: width, h: height },
+            source_size: Size { w: width, h: height },
+            duration: *delay,
+        });
+    }
+    sheet.save(&image_path)?;
+
+    let index = Index {
+        frames: entries,
+        meta: Meta {
+            app: env!("CARGO_PKG_NAME"),
+            image: image_path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            format: "RGBA8888",
+            size: Size { w: sheet.width(), h: sheet.height() },
+            scale: "1",
+        },
+    };
+    serde_json::to_writer_pretty(BufWriter::new(File::create(index_path)?), &index)?;
+    Ok(())
+}
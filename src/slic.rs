@@ -0,0 +1,165 @@
+use image::RgbaImage;
+use pixel::ColorMapper;
+use std::collections::VecDeque;
+
+use crate::ramps;
+
+// Rounds of assigning pixels and moving centers; SLIC settles in about this many
+const ITERATIONS: usize = 10;
+
+// Features of a pixel or center: CIELAB, opacity on the same 0-100 scale as lightness, then x
+// and y
+type Point = [f64; 6];
+
+fn features(image: &RgbaImage) -> Vec<[f64; 4]> {
+    image.pixels().map(|&pixel| {
+        let [l, a, b] = ramps::to_lab(pixel);
+        [l, a, b, pixel[3] as f64 / 255.0 * 100.0]
+    }).collect()
+}
+
+fn color_distance_sq(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+/// Split `image` into about `count` superpixels that follow its edges, with SLIC: centers start
+/// on a grid and gather the pixels nearest them in color and position, `compactness` weighing
+/// position against color. Returns the superpixel of each pixel, row by row, and the color ID
+/// of the average of each superpixel.
+pub fn segment(image: &RgbaImage, count: usize, compactness: f64, mapper: &mut ColorMapper) -> (Vec<Vec<u32>>, Vec<u32>) {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let colors = features(image);
+    let step = ((width * height) as f64 / count.max(1) as f64).sqrt();
+    let (cols, rows) = (((width as f64 / step).round() as usize).max(1), ((height as f64 / step).round() as usize).max(1));
+
+    // Start each center at the smoothest pixel around its grid point, off edges and noise
+    let gradient = |x: usize, y: usize| {
+        let at = |x: usize, y: usize| &colors[y.min(height - 1) * width + x.min(width - 1)];
+        color_distance_sq(at(x + 1, y), at(x.saturating_sub(1), y)) + color_distance_sq(at(x, y + 1), at(x, y.saturating_sub(1)))
+    };
+    let mut centers: Vec<Point> = Vec::with_capacity(cols * rows);
+    for row in 0..rows {
+        for col in 0..cols {
+            let (x, y) = (((col as f64 + 0.5) * width as f64 / cols as f64) as usize, ((row as f64 + 0.5) * height as f64 / rows as f64) as usize);
+            let around = (y.saturating_sub(1)..=(y + 1).min(height - 1)).flat_map(|y| (x.saturating_sub(1)..=(x + 1).min(width - 1)).map(move |x| (x, y)));
+            let (x, y) = around.min_by(|&a, &b| gradient(a.0, a.1).total_cmp(&gradient(b.0, b.1))).unwrap_or((x, y));
+            let [l, a, b, alpha] = colors[y * width + x];
+            centers.push([l, a, b, alpha, x as f64, y as f64]);
+        }
+    }
+
+    let weight = (compactness / step).powi(2);
+    let mut labels = vec![u32::MAX; width * height];
+    let mut distances = vec![f64::INFINITY; width * height];
+    for _ in 0..ITERATIONS {
+        distances.fill(f64::INFINITY);
+        for (k, center) in centers.iter().enumerate() {
+            let (xs, ys) = (((center[4] - step).max(0.0) as usize)..((center[4] + step).ceil() as usize).min(width), ((center[5] - step).max(0.0) as usize)..((center[5] + step).ceil() as usize).min(height));
+            for y in ys {
+                for x in xs.clone() {
+                    let i = y * width + x;
+                    let distance = color_distance_sq(&colors[i], &center[..4]) + ((x as f64 - center[4]).powi(2) + (y as f64 - center[5]).powi(2)) * weight;
+                    if distance < distances[i] {
+                        distances[i] = distance;
+                        labels[i] = k as u32;
+                    }
+                }
+            }
+        }
+        let mut sums = vec![([0.0; 6], 0usize); centers.len()];
+        for (i, &label) in labels.iter().enumerate() {
+            let Some((sum, n)) = sums.get_mut(label as usize) else {
+                continue;
+            };
+            for c in 0..4 {
+                sum[c] += colors[i][c];
+            }
+            sum[4] += (i % width) as f64;
+            sum[5] += (i / width) as f64;
+            *n += 1;
+        }
+        for (center, (sum, n)) in centers.iter_mut().zip(sums) {
+            if n > 0 {
+                *center = sum.map(|s| s / n as f64);
+            }
+        }
+    }
+
+    let labels = connect(&labels, width, (step * step / 4.0) as usize);
+    let superpixels = labels.iter().max().map_or(0, |&max| max as usize + 1);
+    let mut sums = vec![([0u64; 4], 0u64); superpixels];
+    for (pixel, &label) in image.pixels().zip(&labels) {
+        let (sum, n) = &mut sums[label as usize];
+        for c in 0..4 {
+            sum[c] += pixel[c] as u64;
+        }
+        *n += 1;
+    }
+    let cells = sums.into_iter().map(|(sum, n)| mapper.id_for(pixel::block_color(sum, n))).collect();
+    (labels.chunks(width).map(<[u32]>::to_vec).collect(), cells)
+}
+
+// Number the connected pieces of `labels` from 0 in scan order, folding pieces smaller than
+// `min_size` into the piece before them, so every superpixel is in one piece
+fn connect(labels: &[u32], width: usize, min_size: usize) -> Vec<u32> {
+    let height = labels.len() / width;
+    let mut connected = vec![u32::MAX; labels.len()];
+    let mut next = 0;
+    let mut piece = Vec::new();
+    let mut queue = VecDeque::new();
+    for start in 0..labels.len() {
+        if connected[start] != u32::MAX {
+            continue;
+        }
+        let (x, y) = (start % width, start / width);
+        // Pixels are numbered in scan order, so the one left or above is in an earlier piece
+        let before = if x > 0 { Some(connected[start - 1]) } else if y > 0 { Some(connected[start - width]) } else { None };
+
+        piece.clear();
+        queue.push_back(start);
+        connected[start] = next;
+        while let Some(i) = queue.pop_front() {
+            piece.push(i);
+            let (x, y) = (i % width, i / width);
+            let neighbours = [(x > 0).then(|| i - 1), (x + 1 < width).then(|| i + 1), (y > 0).then(|| i - width), (y + 1 < height).then(|| i + width)];
+            for j in neighbours.into_iter().flatten() {
+                if connected[j] == u32::MAX && labels[j] == labels[start] {
+                    connected[j] = next;
+                    queue.push_back(j);
+                }
+            }
+        }
+        if let Some(label) = before
+            && piece.len() < min_size
+        {
+            for &i in &piece {
+                connected[i] = label;
+            }
+        } else {
+            next += 1;
+        }
+    }
+    connected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Label 0 in two pieces, the right one two pixels, around label 1
+    const LABELS: [u32; 12] = [
+        0, 0, 1, 1,
+        0, 1, 1, 0,
+        0, 0, 1, 0,
+    ];
+
+    #[test]
+    fn pieces_are_numbered_in_scan_order() {
+        assert_eq!(connect(&LABELS, 4, 0), [0, 0, 1, 1, 0, 1, 1, 2, 0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn small_pieces_fold_into_the_one_before() {
+        assert_eq!(connect(&LABELS, 4, 3), [0, 0, 1, 1, 0, 1, 1, 1, 0, 0, 1, 1]);
+    }
+}
@@ -0,0 +1,75 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::chart::{self, Key, Pagination};
+use crate::dmc;
+use crate::failure::{self, Kind};
+use crate::load_map;
+use crate::pdf::Document;
+use crate::physical::{Length, Sizing};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum StitchMode {
+    /// Cross-stitch: a symbol per floss color, skeins per color
+    #[default]
+    Cross,
+    /// Diamond painting: an alphanumeric code per drill color, bags of drills per color
+    Diamond,
+}
+
+// Full crosses on 14-count Aida with two strands, allowing for waste; a skein is 8 m of floss
+pub const STITCHES_PER_SKEIN: usize = 1800;
+// One stitch on 14-count Aida
+const STITCH: Length = Length { mm: 25.4 / 14.0 };
+const DRILL: Length = Length { mm: 2.5 };
+// Drills are sold in bags of 200; 10% more covers the ones lost while working
+const DRILLS_PER_BAG: usize = 200;
+const BOLD_EVERY: usize = 10;
+// Chart cell size in points
+const CELL: f32 = 10.0;
+
+/// Write the map in `input` as a pattern PDF: one symbol per DMC color on a grid with bold lines
+/// every 10 cells, followed by a legend with how much of each color to buy.
+pub fn run(input: &Path, output: &Path, mode: StitchMode, sizing: &Sizing, pagination: &Pagination) -> Result<(), Box<dyn std::error::Error>> {
+    let map = load_map(input)?;
+    let catalog: Vec<u32> = dmc::FLOSS.iter().map(|&(_, _, rgb)| rgb).collect();
+    let matched = chart::match_colors(&map, &catalog).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+
+    let symbols = match mode {
+        StitchMode::Cross => chart::symbols(matched.used.len()),
+        StitchMode::Diamond => chart::letter_codes(matched.used.len()),
+    };
+    let keys: Vec<Key> = matched.used.iter().zip(symbols).map(|(&(index, _), symbol)| Key { symbol, color: chart::opaque(catalog[index]) }).collect();
+
+    let name = input.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let (unit, cell) = match mode {
+        StitchMode::Cross => ("stitches", STITCH),
+        StitchMode::Diamond => ("drills", DRILL),
+    };
+    let title = format!("{}: {}x{} {} ({}), {} colors", name, width, map.matrix.len(), unit, sizing.describe(width, map.matrix.len(), cell), keys.len());
+
+    // Whole multiples of BOLD_EVERY per page, so page edges fall on bold lines
+    let section = chart::fit(pagination.paper(), CELL, BOLD_EVERY);
+    let mut doc = Document::new(pagination.paper());
+    chart::draw(&mut doc, &title, &matched.cells, &keys, section, BOLD_EVERY, pagination).map_err(failure::bad_input)?;
+    let rows: Vec<(&Key, Vec<String>)> = keys
+        .iter()
+        .zip(&matched.used)
+        .map(|(key, &(index, count))| {
+            let (number, floss_name, _) = dmc::FLOSS[index];
+            let packs = match mode {
+                StitchMode::Cross => count.div_ceil(STITCHES_PER_SKEIN),
+                StitchMode::Diamond => (count * 11 / 10).div_ceil(DRILLS_PER_BAG),
+            };
+            (key, vec![number.to_string(), floss_name.to_string(), count.to_string(), packs.to_string()])
+        })
+        .collect();
+    let headers = match mode {
+        StitchMode::Cross => ["DMC", "Name", "Stitches", "Skeins"],
+        StitchMode::Diamond => ["DMC", "Name", "Drills", "Bags of 200"],
+    };
+    chart::legend(&mut doc, &title, &headers, &rows);
+    doc.save(output).map_err(failure::write)?;
+    Ok(())
+}
@@ -0,0 +1,212 @@
+use image::{DynamicImage, GenericImageView};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tiff::tags::Tag;
+
+/// A source of RGBA8 pixel rows, read top to bottom.
+pub trait RowSource {
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Fill `buf` (width * 4 bytes) with the next row.
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Rows of an image that has already been decoded in full.
+pub struct ImageRows {
+    img: DynamicImage,
+    y: u32,
+}
+
+impl ImageRows {
+    pub fn new(img: DynamicImage) -> Self {
+        ImageRows { img, y: 0 }
+    }
+}
+
+impl RowSource for ImageRows {
+    fn dimensions(&self) -> (u32, u32) {
+        self.img.dimensions()
+    }
+
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(rgba) = self.img.as_rgba8() {
+            let start = self.y as usize * buf.len();
+            buf.copy_from_slice(&rgba.as_raw()[start..start + buf.len()]);
+            self.y += 1;
+            return Ok(());
+        }
+        for (x, dst) in buf.chunks_exact_mut(4).enumerate() {
+            dst.copy_from_slice(&self.img.get_pixel(x as u32, self.y).0);
+        }
+        self.y += 1;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Layout {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+}
+
+impl Layout {
+    fn channels(self) -> usize {
+        match self {
+            Layout::Gray => 1,
+            Layout::GrayAlpha => 2,
+            Layout::Rgb => 3,
+            Layout::Rgba => 4,
+        }
+    }
+}
+
+// Same rounding the image crate uses when narrowing 16-bit samples
+fn narrow(v: u16) -> u8 {
+    ((v as u32 + 128) / 257) as u8
+}
+
+fn write_rgba(layout: Layout, samples: &[u8], out: &mut [u8]) {
+    for (px, dst) in samples.chunks_exact(layout.channels()).zip(out.chunks_exact_mut(4)) {
+        let rgba = match layout {
+            Layout::Gray => [px[0], px[0], px[0], 255],
+            Layout::GrayAlpha => [px[0], px[0], px[0], px[1]],
+            Layout::Rgb => [px[0], px[1], px[2], 255],
+            Layout::Rgba => [px[0], px[1], px[2], px[3]],
+        };
+        dst.copy_from_slice(&rgba);
+    }
+}
+
+/// Row-by-row PNG decoding; only one scanline is held in memory.
+pub struct PngRows {
+    reader: png::Reader<BufReader<File>>,
+    layout: Layout,
+    sixteen_bit: bool,
+    scratch: Vec<u8>,
+}
+
+impl RowSource for PngRows {
+    fn dimensions(&self) -> (u32, u32) {
+        let info = self.reader.info();
+        (info.width, info.height)
+    }
+
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let row = self.reader.next_row()?.ok_or("PNG ended before all rows were decoded")?;
+        if self.sixteen_bit {
+            self.scratch.clear();
+            self.scratch.extend(row.data().chunks_exact(2).map(|s| narrow(u16::from_be_bytes([s[0], s[1]]))));
+            write_rgba(self.layout, &self.scratch, buf);
+        } else {
+            write_rgba(self.layout, row.data(), buf);
+        }
+        Ok(())
+    }
+}
+
+/// Strip-by-strip TIFF decoding; only one strip is held in memory.
+pub struct TiffRows {
+    decoder: tiff::decoder::Decoder<BufReader<File>>,
+    layout: Layout,
+    width: u32,
+    height: u32,
+    next_strip: u32,
+    strip: Vec<u8>,
+    strip_row: usize,
+    strip_rows: usize,
+}
+
+impl RowSource for TiffRows {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.strip_row == self.strip_rows {
+            let samples = match self.decoder.read_chunk(self.next_strip)? {
+                tiff::decoder::DecodingResult::U8(data) => data,
+                tiff::decoder::DecodingResult::U16(data) => data.into_iter().map(narrow).collect(),
+                _ => return Err("Unsupported TIFF sample format".into()),
+            };
+            self.strip_rows = self.decoder.chunk_data_dimensions(self.next_strip).1 as usize;
+            self.strip_row = 0;
+            self.strip = samples;
+            self.next_strip += 1;
+        }
+        let row_len = self.width as usize * self.layout.channels();
+        let start = self.strip_row * row_len;
+        let samples = self.strip.get(start..start + row_len).ok_or("TIFF strip is shorter than expected")?;
+        write_rgba(self.layout, samples, buf);
+        self.strip_row += 1;
+        Ok(())
+    }
+}
+
+/// Open `path` with a decoder that yields rows without decoding the whole image.
+///
+/// Returns `Ok(None)` when the format or layout can't be streamed (e.g. interlaced PNG,
+/// tiled or planar TIFF); callers should fall back to a full decode.
+pub fn open_streaming(path: &Path) -> Result<Option<Box<dyn RowSource>>, Box<dyn std::error::Error>> {
+    let format = image::ImageFormat::from_path(path).ok();
+    match format {
+        Some(image::ImageFormat::Png) => open_png(path),
+        Some(image::ImageFormat::Tiff) => open_tiff(path),
+        _ => Ok(None),
+    }
+}
+
+fn open_png(path: &Path) -> Result<Option<Box<dyn RowSource>>, Box<dyn std::error::Error>> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let reader = decoder.read_info()?;
+    if reader.info().interlaced {
+        return Ok(None);
+    }
+    let (color_type, bit_depth) = reader.output_color_type();
+    let layout = match color_type {
+        png::ColorType::Grayscale => Layout::Gray,
+        png::ColorType::GrayscaleAlpha => Layout::GrayAlpha,
+        png::ColorType::Rgb => Layout::Rgb,
+        png::ColorType::Rgba => Layout::Rgba,
+        png::ColorType::Indexed => return Ok(None),
+    };
+    Ok(Some(Box::new(PngRows {
+        reader,
+        layout,
+        sixteen_bit: bit_depth == png::BitDepth::Sixteen,
+        scratch: Vec::new(),
+    })))
+}
+
+fn open_tiff(path: &Path) -> Result<Option<Box<dyn RowSource>>, Box<dyn std::error::Error>> {
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(File::open(path)?))?;
+    if decoder.get_chunk_type() != tiff::decoder::ChunkType::Strip {
+        return Ok(None);
+    }
+    // 2 = planar (one plane per channel), which strips can't be interleaved from
+    if decoder.find_tag_unsigned::<u16>(Tag::PlanarConfiguration)?.unwrap_or(1) != 1 {
+        return Ok(None);
+    }
+    let photometric = decoder.find_tag_unsigned::<u16>(Tag::PhotometricInterpretation)?;
+    let layout = match (decoder.colortype()?, photometric) {
+        (tiff::ColorType::Gray(8 | 16), Some(1)) => Layout::Gray,
+        (tiff::ColorType::GrayA(8 | 16), Some(1)) => Layout::GrayAlpha,
+        (tiff::ColorType::RGB(8 | 16), Some(2)) => Layout::Rgb,
+        (tiff::ColorType::RGBA(8 | 16), Some(2)) => Layout::Rgba,
+        _ => return Ok(None),
+    };
+    let (width, height) = decoder.dimensions()?;
+    Ok(Some(Box::new(TiffRows {
+        decoder,
+        layout,
+        width,
+        height,
+        next_strip: 0,
+        strip: Vec::new(),
+        strip_row: 0,
+        strip_rows: 0,
+    })))
+}
@@ -0,0 +1,335 @@
+use clap::{Args, ValueEnum};
+use image::{Rgba, RgbaImage, imageops};
+use std::str::FromStr;
+
+use crate::{cache, failure, mapped, palette};
+
+// Largest brightness change of a mosaic tile, either way
+const JITTER: i32 = 10;
+
+// How far the corners of a CRT screen bend in, as a fraction of its half-size
+const CURVATURE: f32 = 0.08;
+
+// Brightness kept on the last pixel row of each cell, between scanlines
+const SCANLINE: f32 = 0.5;
+
+// Brightness kept on the two channels a column of the CRT's RGB mask doesn't show
+const MASK: f32 = 0.65;
+
+// Brightness of the left and right sides of extruded isometric cells, lit from the right
+const ISO_LEFT: f32 = 0.6;
+const ISO_RIGHT: f32 = 0.8;
+
+// Tallest block `--extrude` makes, in cells
+const MAX_EXTRUDE: f32 = 1000.0;
+
+// Samples per side of each pixel when measuring how much of it a dot covers
+const DOT_SAMPLES: u32 = 4;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Style {
+    /// One block of solid color per cell
+    #[default]
+    Flat,
+    /// Separate tiles with grout between them and slight color variation, like a tile mosaic
+    Mosaic,
+    /// A CRT screen: dark scanlines between rows of cells, RGB mask stripes and a slightly
+    /// curved screen with black corners
+    Crt,
+    /// Isometric 2:1 diamond tiles, raised into blocks by `--extrude`
+    Iso,
+    /// Halftone dots on `--background`, one circle per cell sized as `--dot-size` says
+    Dots,
+}
+
+/// How big the dots of the dots style are.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum DotSize {
+    /// Larger the further a cell's luminance is from the background's, like a halftone print:
+    /// dark cells make big dots on a light background
+    #[default]
+    Luminance,
+    /// As wide as the cell, whatever its color
+    Fixed,
+}
+
+/// Height to raise cells of a color by in the iso style, from `RRGGBB=HEIGHT`, in cells: 1
+/// makes cubes.
+#[derive(Clone, Copy, Debug)]
+pub struct Extrude {
+    color: Rgba<u8>,
+    height: f32,
+}
+
+impl FromStr for Extrude {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (color, height) = s.split_once('=').ok_or_else(|| format!("Expected COLOR=HEIGHT, got {}", s))?;
+        match height.trim().parse::<f32>() {
+            Ok(height) if (0.0..=MAX_EXTRUDE).contains(&height) => Ok(Extrude { color: palette::parse_color(color)?, height }),
+            _ => Err(format!("Invalid height {}; expected a number of cells from 0 to {}", height, MAX_EXTRUDE)),
+        }
+    }
+}
+
+/// How reconstructed cells are drawn.
+#[derive(Args, Clone, Debug)]
+pub struct StyleOptions {
+    /// Look of the cells
+    #[arg(long, value_enum, default_value_t)]
+    style: Style,
+
+    /// Pixels per cell, or tile width for iso [default: 1, 16 for mosaic, iso and dots, or 6 for
+    /// crt]
+    #[arg(long)]
+    scale: Option<u32>,
+
+    /// Grout width in pixels between mosaic tiles
+    #[arg(long, default_value_t = 2)]
+    gap: u32,
+
+    /// Grout color for mosaic tiles
+    #[arg(long, default_value = "333333", value_parser = palette::parse_color)]
+    gap_color: Rgba<u8>,
+
+    /// Raise the cells of a color (RRGGBB or RRGGBBAA) into blocks this many cells high in the
+    /// iso style; repeat for more colors
+    #[arg(long, value_name = "COLOR=HEIGHT")]
+    extrude: Vec<Extrude>,
+
+    /// How big each dot is in the dots style
+    #[arg(long, value_enum, default_value_t)]
+    dot_size: DotSize,
+
+    /// Color of every dot in the dots style, instead of the color of its cell
+    #[arg(long, value_parser = palette::parse_color)]
+    dot_color: Option<Rgba<u8>>,
+
+    /// Color between the dots of the dots style
+    #[arg(long, default_value = "ffffff", value_parser = palette::parse_color)]
+    background: Rgba<u8>,
+}
+
+// Same as the flag defaults
+impl Default for StyleOptions {
+    fn default() -> Self {
+        StyleOptions { style: Style::Flat, scale: None, gap: 2, gap_color: Rgba([0x33, 0x33, 0x33, 255]), extrude: Vec::new(), dot_size: DotSize::Luminance, dot_color: None, background: Rgba([255, 255, 255, 255]) }
+    }
+}
+
+impl StyleOptions {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match (self.style, self.scale) {
+            (_, Some(0)) => Err(failure::bad_input("Scale must be greater than 0")),
+            (Style::Mosaic, Some(scale)) if scale <= self.gap => Err(failure::bad_input("Scale must be larger than the gap so tiles show")),
+            (Style::Iso, Some(scale)) if scale < 2 || scale % 2 == 1 => Err(failure::bad_input("Scale must be an even number of pixels for iso, the width of a tile")),
+            (Style::Crt, Some(scale)) if scale < 3 => Err(failure::bad_input("Scale must be at least 3 for crt, so scanlines and the RGB mask show")),
+            (Style::Dots, Some(scale)) if scale < 4 => Err(failure::bad_input("Scale must be at least 4 for dots, so they come out round")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Width in pixels of the cells of hex and triangle maps, which only draw flat.
+    pub fn cell_width(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        match self.style {
+            Style::Flat => Ok(self.scale.unwrap_or(16)),
+            _ => Err(failure::bad_input("Hex and triangle maps can only be drawn in the flat style")),
+        }
+    }
+
+    /// Pixels per pixel of the image for segmented maps, which only draw flat.
+    pub fn segment_scale(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        match self.style {
+            Style::Flat => Ok(self.scale.unwrap_or(1)),
+            _ => Err(failure::bad_input("Segmented maps can only be drawn in the flat style")),
+        }
+    }
+
+    /// Draw `image`, rendered at one pixel per cell, in this style. Refuses drawings too large
+    /// to hold in memory.
+    pub fn apply(&self, image: RgbaImage) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+        match self.style {
+            Style::Flat => match self.scale {
+                Some(scale) if scale > 1 => {
+                    let (width, height) = canvas(&image, scale, scaled(image.width(), scale), scaled(image.height(), scale))?;
+                    Ok(imageops::resize(&image, width, height, imageops::FilterType::Nearest))
+                }
+                _ => Ok(image),
+            },
+            Style::Mosaic => self.mosaic(&image),
+            Style::Crt => self.crt(&image),
+            Style::Iso => self.iso(&image),
+            Style::Dots => self.dots(&image),
+        }
+    }
+
+    fn mosaic(&self, image: &RgbaImage) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+        let scale = self.scale.unwrap_or(16);
+        // Half the grout on each side of a tile, so neighbours end up `gap` apart
+        let (before, after) = (self.gap / 2, self.gap - self.gap / 2);
+        let (width, height) = canvas(image, scale, scaled(image.width(), scale), scaled(image.height(), scale))?;
+        let mut out = RgbaImage::new(width, height);
+        for (x, y, &pixel) in image.enumerate_pixels() {
+            // Empty cells get no tile and no grout
+            if pixel[3] == 0 {
+                continue;
+            }
+            let seed = [x.to_le_bytes(), y.to_le_bytes()].concat();
+            let shift = (cache::hash_pixels(cache::HASH_SEED, &seed) % (2 * JITTER as u64 + 1)) as i32 - JITTER;
+            let [r, g, b, a] = pixel.0;
+            let jitter = |c: u8| (c as i32 + shift).clamp(0, 255) as u8;
+            let tile = Rgba([jitter(r), jitter(g), jitter(b), a]);
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    let grout = dx < before || dy < before || dx >= scale - after || dy >= scale - after;
+                    out.put_pixel(x * scale + dx, y * scale + dy, if grout { self.gap_color } else { tile });
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn crt(&self, image: &RgbaImage) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+        let scale = self.scale.unwrap_or(6);
+        let (width, height) = canvas(image, scale, scaled(image.width(), scale), scaled(image.height(), scale))?;
+        Ok(RgbaImage::from_fn(width, height, |x, y| {
+            // Barrel distortion: pixels further from the center sample further out, so the
+            // picture bulges and the corners fall off the screen
+            let (u, v) = ((x as f32 + 0.5) / width as f32 * 2.0 - 1.0, (y as f32 + 0.5) / height as f32 * 2.0 - 1.0);
+            let bend = 1.0 + CURVATURE * (u * u + v * v);
+            let (u, v) = (u * bend, v * bend);
+            if u.abs() >= 1.0 || v.abs() >= 1.0 {
+                return Rgba([0, 0, 0, 255]);
+            }
+            let (sx, sy) = (((u + 1.0) / 2.0 * width as f32) as u32, ((v + 1.0) / 2.0 * height as f32) as u32);
+            let pixel = image.get_pixel(sx / scale, sy / scale);
+            let line = if sy % scale == scale - 1 { SCANLINE } else { 1.0 };
+            let mut out = pixel.0;
+            for (c, value) in out[..3].iter_mut().enumerate() {
+                let mask = if c as u32 == x % 3 { 1.0 } else { MASK };
+                *value = (*value as f32 * line * mask).round() as u8;
+            }
+            Rgba(out)
+        }))
+    }
+
+    fn iso(&self, image: &RgbaImage) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+        let tile_width = self.scale.unwrap_or(16);
+        let tile_height = tile_width / 2;
+        let (cols, rows) = image.dimensions();
+        // Cube sides are as tall as a tile
+        let pixels = |height: f32| (height * tile_height as f32).round() as u32;
+        let raise = |pixel: &Rgba<u8>| self.extrude.iter().find(|extrude| extrude.color == *pixel).map_or(0, |extrude| pixels(extrude.height));
+        // Room for the tallest block whether it shows or not, so frames of a map match in size
+        let highest = self.extrude.iter().map(|extrude| pixels(extrude.height)).max().unwrap_or(0);
+        let diagonal = cols as u64 + rows as u64;
+        let (width, height) = canvas(image, tile_width, diagonal.checked_mul(tile_width as u64).map(|width| width / 2), diagonal.checked_mul(tile_height as u64).map(|height| height / 2 + highest as u64))?;
+        let mut out = RgbaImage::new(width, height);
+
+        // Back to front, so nearer cells cover the ones behind
+        let mut cells: Vec<(u32, u32)> = (0..rows).flat_map(|y| (0..cols).map(move |x| (x, y))).collect();
+        cells.sort_by_key(|&(x, y)| x + y);
+        for (x, y) in cells {
+            let pixel = *image.get_pixel(x, y);
+            if pixel[3] == 0 {
+                continue;
+            }
+            let left = (rows - 1 + x - y) * tile_width / 2;
+            let top = (x + y) * tile_height / 2 + highest;
+            let height = raise(&pixel);
+            let shade = |factor: f32| {
+                let [r, g, b, a] = pixel.0;
+                let [r, g, b] = [r, g, b].map(|c| (c as f32 * factor).round() as u8);
+                Rgba([r, g, b, a])
+            };
+            // The sides as a stack of diamonds from the ground up, then the top
+            for lift in 0..=height {
+                for dy in 0..tile_height {
+                    for dx in 0..tile_width {
+                        // Inside the diamond, measured from its center in half-tiles
+                        let (u, v) = ((dx as f32 + 0.5) / (tile_width as f32 / 2.0) - 1.0, (dy as f32 + 0.5) / (tile_height as f32 / 2.0) - 1.0);
+                        if u.abs() + v.abs() > 1.0 {
+                            continue;
+                        }
+                        let color = if lift == height { pixel } else { shade(if dx < tile_width / 2 { ISO_LEFT } else { ISO_RIGHT }) };
+                        out.put_pixel(left + dx, top + dy - lift, color);
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn dots(&self, image: &RgbaImage) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+        let scale = self.scale.unwrap_or(16);
+        let (width, height) = canvas(image, scale, scaled(image.width(), scale), scaled(image.height(), scale))?;
+        let luminance = |color: Rgba<u8>| (0.2126 * color[0] as f32 + 0.7152 * color[1] as f32 + 0.0722 * color[2] as f32) / 255.0;
+        let background = luminance(self.background);
+        Ok(RgbaImage::from_fn(width, height, |x, y| {
+            let pixel = *image.get_pixel(x / scale, y / scale);
+            if pixel[3] == 0 {
+                return self.background;
+            }
+            // Dot areas follow the luminance, so the print keeps the map's tones
+            let half = scale as f32 / 2.0;
+            let radius = match self.dot_size {
+                DotSize::Luminance => half * (luminance(pixel) - background).abs().sqrt(),
+                DotSize::Fixed => half,
+            };
+            // Share of the pixel inside the dot, for smooth edges
+            let (dx, dy) = ((x % scale) as f32 - half, (y % scale) as f32 - half);
+            let step = 1.0 / DOT_SAMPLES as f32;
+            let inside = (0..DOT_SAMPLES * DOT_SAMPLES).filter(|i| {
+                let (sx, sy) = (dx + ((i % DOT_SAMPLES) as f32 + 0.5) * step, dy + ((i / DOT_SAMPLES) as f32 + 0.5) * step);
+                sx * sx + sy * sy <= radius * radius
+            });
+            let coverage = inside.count() as f32 / (DOT_SAMPLES * DOT_SAMPLES) as f32;
+            let dot = self.dot_color.unwrap_or(pixel);
+            let mut out = self.background.0;
+            for (c, value) in out.iter_mut().enumerate() {
+                *value = (*value as f32 + (dot[c] as f32 - *value as f32) * coverage).round() as u8;
+            }
+            Rgba(out)
+        }))
+    }
+}
+
+fn scaled(cells: u32, scale: u32) -> Option<u64> {
+    (cells as u64).checked_mul(scale as u64)
+}
+
+// Width and height of the drawing of `image` at `scale`, if it fits in memory
+fn canvas(image: &RgbaImage, scale: u32, width: Option<u64>, height: Option<u64>) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+    match (width.map(u32::try_from), height.map(u32::try_from)) {
+        (Some(Ok(width)), Some(Ok(height))) if !mapped::too_large(width, height) => Ok((width, height)),
+        _ => Err(failure::bad_input(format!("Map of {}x{} cells is too large to draw at scale {}", image.width(), image.height(), scale))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn huge(style: Style) -> bool {
+        let options = StyleOptions { style, scale: Some(2_000_000_000), ..StyleOptions::default() };
+        options.apply(RgbaImage::new(5, 5)).is_err()
+    }
+
+    #[test]
+    fn drawings_too_large_for_memory_are_refused() {
+        assert!(huge(Style::Flat));
+        assert!(huge(Style::Mosaic));
+        assert!(huge(Style::Crt));
+        assert!(huge(Style::Iso));
+        assert!(huge(Style::Dots));
+    }
+
+    #[test]
+    fn iso_counts_extruded_blocks_in_its_size() {
+        let flat = StyleOptions { style: Style::Iso, scale: Some(2000), ..StyleOptions::default() };
+        assert!(flat.apply(RgbaImage::new(1, 1)).is_ok());
+        let tall = StyleOptions { extrude: vec!["#000000=1000".parse().unwrap()], ..flat };
+        assert!(tall.apply(RgbaImage::new(1, 1)).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::matrix::Matrix;
+use crate::{hex_to_rgba, rgba_to_hex, triangle};
+
+/// Write `matrix` as an SVG of one unit square per cell: a path per color, drawn as runs of
+/// cells along each row. Fully transparent cells and those of unknown colors are left out.
+pub fn write(matrix: &Matrix, colors: &HashMap<u32, String>, path: &Path) -> io::Result<()> {
+    let mut paths: HashMap<u32, String> = HashMap::new();
+    let (mut width, mut height) = (0, 0);
+    matrix.for_each_row(|row| {
+        let mut x = 0;
+        while x < row.len() {
+            let run = row[x..].iter().take_while(|&&id| id == row[x]).count();
+            let _ = write!(paths.entry(row[x]).or_default(), "M{} {}h{}v1h-{}z", x, height, run, run);
+            x += run;
+        }
+        width = width.max(row.len());
+        height += 1;
+        Ok(())
+    })?;
+
+    document(path, &width.to_string(), &height.to_string(), r#" shape-rendering="crispEdges""#, &paths, colors)
+}
+
+/// Write `matrix`, a triangle map, as an SVG of triangles with sides one unit long: a path per
+/// color, as in [`write`], cut to the image the map was made from.
+pub fn write_triangles(matrix: &Matrix, colors: &HashMap<u32, String>, path: &Path) -> io::Result<()> {
+    // Thousandths are plenty for the irrational heights
+    let num = |value: f64| ((value * 1000.0).round() / 1000.0).to_string();
+    let mut paths: HashMap<u32, String> = HashMap::new();
+    let (mut cols, mut rows) = (0, 0);
+    matrix.for_each_row(|row| {
+        for (col, &id) in row.iter().enumerate() {
+            let [(ax, ay), (bx, by), (cx, cy)] = triangle::corners(rows, col, 1.0);
+            let _ = write!(paths.entry(id).or_default(), "M{} {}L{} {}L{} {}z", num(ax), num(ay), num(bx), num(by), num(cx), num(cy));
+        }
+        cols = cols.max(row.len());
+        rows += 1;
+        Ok(())
+    })?;
+    let (width, height) = triangle::picture_size(cols, rows, 1.0);
+    document(path, &num(width), &num(height), "", &paths, colors)
+}
+
+/// Write `polygons`, each the corners of a cell and its color ID, as an SVG `width` x `height`
+/// units with a path per color, as in [`write`].
+pub fn write_polygons(polygons: &[(u32, Vec<[f64; 2]>)], (width, height): (u32, u32), colors: &HashMap<u32, String>, path: &Path) -> io::Result<()> {
+    let num = |value: f64| ((value * 1000.0).round() / 1000.0).to_string();
+    let mut paths: HashMap<u32, String> = HashMap::new();
+    for (id, corners) in polygons {
+        let d = paths.entry(*id).or_default();
+        for (i, [x, y]) in corners.iter().enumerate() {
+            let _ = write!(d, "{}{} {}", if i == 0 { "M" } else { "L" }, num(*x), num(*y));
+        }
+        if !corners.is_empty() {
+            d.push('z');
+        }
+    }
+    document(path, &width.to_string(), &height.to_string(), "", &paths, colors)
+}
+
+// The SVG of `paths` for each color ID, `width` x `height` units, with `attributes` added to
+// the root element
+fn document(path: &Path, width: &str, height: &str, attributes: &str, paths: &HashMap<u32, String>, colors: &HashMap<u32, String>) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}"{}>"#, width, height, width, height, attributes)?;
+    let mut ids: Vec<&u32> = paths.keys().collect();
+    ids.sort();
+    for id in ids {
+        let Some(color) = colors.get(id).and_then(|hex| hex_to_rgba(hex).ok()) else {
+            continue;
+        };
+        let hex = rgba_to_hex(&color);
+        let rgb = &hex[..7];
+        match color[3] {
+            0 => continue,
+            255 => writeln!(w, r#"<path fill="{}" d="{}"/>"#, rgb, paths[id])?,
+            alpha => writeln!(w, r#"<path fill="{}" fill-opacity="{:.3}" d="{}"/>"#, rgb, alpha as f64 / 255.0, paths[id])?,
+        }
+    }
+    writeln!(w, "</svg>")?;
+    w.flush()
+}
@@ -0,0 +1,67 @@
+use image::{Rgba, RgbaImage, imageops};
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::failure::{self, Kind};
+use crate::style::StyleOptions;
+use crate::{MapFile, PixelError, animation, hex_to_rgba, mapped, palette, render_map, rgba_to_hex};
+
+/// `colors` with the colors that aren't fully transparent replaced by `palette` in ID order,
+/// each keeping its alpha; colors past the end of `palette` stay as they are.
+fn swap(colors: &HashMap<u32, String>, palette: &[Rgba<u8>]) -> Result<HashMap<u32, String>, Box<dyn std::error::Error>> {
+    let mut ids: Vec<(u32, Rgba<u8>)> = colors.iter().map(|(&id, hex)| Ok((id, hex_to_rgba(hex)?))).collect::<Result<_, PixelError>>().map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    ids.sort_by_key(|&(id, _)| id);
+    let mut swapped = colors.clone();
+    for ((id, color), replacement) in ids.into_iter().filter(|(_, color)| color[3] > 0).zip(palette) {
+        swapped.insert(id, rgba_to_hex(&Rgba([replacement[0], replacement[1], replacement[2], color[3]])));
+    }
+    Ok(swapped)
+}
+
+/// Render `frame` (0-based) of the map in `input` once as it is and once under each of
+/// `palettes`, and lay the renders out left to right in rows of `cols`, `spacing` pixels apart,
+/// into the image `output`.
+pub fn run(input: &Path, palettes: &[PathBuf], output: &Path, frame: usize, cols: Option<u32>, spacing: u32, style: &StyleOptions) -> Result<(), Box<dyn std::error::Error>> {
+    style.validate()?;
+    if cols == Some(0) {
+        return Err(failure::bad_input("--cols must be greater than 0"));
+    }
+    let MapFile { matrix, frames, colors, grid, .. } = serde_json::from_slice(&mapped::map_file(input)?)?;
+    grid.require_square()?;
+    let matrix = match (matrix, frames) {
+        (Some(matrix), None) if frame == 0 => matrix,
+        (None, Some(frames)) => {
+            let count = frames.len();
+            let frames = animation::resolve(frames).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+            frames.into_iter().nth(frame).ok_or_else(|| failure::bad_input(format!("No frame {}; the map has {} (counting from 0)", frame, count)))?.1
+        }
+        (Some(_), None) => return Err(failure::bad_input(format!("No frame {}; the map has no frames", frame))),
+        _ => return Err(failure::tag(Kind::InvalidJson, "Map needs either a matrix or frames")),
+    };
+
+    let mut panels = vec![style.apply(render_map(&matrix, &colors, &ProgressBar::hidden())?)?];
+    for path in palettes {
+        let colors = swap(&colors, &palette::load(path).map_err(|e| failure::bad_input(format!("Can't load palette {}: {}", path.display(), e)))?)?;
+        panels.push(style.apply(render_map(&matrix, &colors, &ProgressBar::hidden())?)?);
+    }
+
+    let (width, height) = (panels[0].width(), panels[0].height());
+    let cols = cols.unwrap_or(panels.len() as u32).min(panels.len() as u32);
+    let rows = (panels.len() as u32).div_ceil(cols);
+    let sheet_width = (cols as u64 * width as u64 + (cols as u64 - 1) * spacing as u64).try_into();
+    let sheet_height = (rows as u64 * height as u64 + (rows as u64 - 1) * spacing as u64).try_into();
+    let (Ok(sheet_width), Ok(sheet_height)) = (sheet_width, sheet_height) else {
+        return Err(failure::bad_input("The sheet would be too large; use fewer columns, a smaller --scale or --spacing"));
+    };
+    if mapped::too_large(sheet_width, sheet_height) {
+        return Err(failure::bad_input(format!("The sheet would be {}x{} pixels, too large to hold", sheet_width, sheet_height)));
+    }
+    let mut sheet = RgbaImage::new(sheet_width, sheet_height);
+    for (i, panel) in panels.iter().enumerate() {
+        let (col, row) = (i as u32 % cols, i as u32 / cols);
+        imageops::replace(&mut sheet, panel, (col * (width + spacing)) as i64, (row * (height + spacing)) as i64);
+    }
+    sheet.save(output).map_err(failure::write)?;
+    Ok(())
+}
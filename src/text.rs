@@ -0,0 +1,89 @@
+use clap::Args;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::failure;
+use crate::{Output, hex_to_rgba};
+
+/// Options of plain-text exports.
+#[derive(Args, Clone, Debug)]
+pub struct TextOptions {
+    /// Characters from lightest to darkest, for ASCII art
+    #[arg(long, default_value = " .:-=+*#%@")]
+    ramp: String,
+
+    /// How dark a cell must be, from 0 to 1, to raise its Braille dot
+    #[arg(long, default_value_t = 0.5)]
+    threshold: f32,
+}
+
+// Dot bits of a Braille pattern by row, for the left and right columns
+const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+// Darkness of each color from 0 (white or transparent) to 1 (black)
+fn darkness(map: &Output) -> Result<HashMap<u32, f32>, Box<dyn std::error::Error>> {
+    let mut darkness = HashMap::new();
+    for (&id, hex) in &map.colors {
+        let [r, g, b, a] = hex_to_rgba(hex)?.0;
+        let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0;
+        darkness.insert(id, (1.0 - luma) * a as f32 / 255.0);
+    }
+    Ok(darkness)
+}
+
+/// Write the map as ASCII art, one character per cell picked from the ramp by how dark the
+/// cell's color is.
+pub fn write_ascii(map: &Output, options: &TextOptions, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let ramp: Vec<char> = options.ramp.chars().collect();
+    if ramp.is_empty() {
+        return Err(failure::bad_input("Ramp needs at least one character"));
+    }
+    let darkness = darkness(map)?;
+    let mut text = String::new();
+    for row in &map.matrix {
+        let line: String = row
+            .iter()
+            .map(|id| {
+                let dark = darkness.get(id).copied().unwrap_or(0.0);
+                ramp[((dark * ramp.len() as f32) as usize).min(ramp.len() - 1)]
+            })
+            .collect();
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    fs::write(output, text)?;
+    Ok(())
+}
+
+/// Write the map as Unicode Braille patterns, each character covering 2x4 cells with a dot
+/// for every cell darker than the threshold.
+pub fn write_braille(map: &Output, options: &TextOptions, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !(0.0..=1.0).contains(&options.threshold) {
+        return Err(failure::bad_input("Threshold must be between 0 and 1"));
+    }
+    let darkness = darkness(map)?;
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let mut text = String::new();
+    for rows in map.matrix.chunks(4) {
+        let line: String = (0..width.div_ceil(2))
+            .map(|column| {
+                let mut pattern = 0;
+                for (row, bits) in rows.iter().zip(DOTS) {
+                    for (dx, bit) in bits.into_iter().enumerate() {
+                        if let Some(id) = row.get(column * 2 + dx)
+                            && darkness.get(id).copied().unwrap_or(0.0) > options.threshold
+                        {
+                            pattern |= bit;
+                        }
+                    }
+                }
+                char::from_u32(0x2800 + pattern).unwrap_or(' ')
+            })
+            .collect();
+        text.push_str(&line);
+        text.push('\n');
+    }
+    fs::write(output, text)?;
+    Ok(())
+}
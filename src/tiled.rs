@@ -0,0 +1,64 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::Output;
+use crate::tiles::{Placement, Tiles};
+
+fn gid(placement: Placement) -> u32 {
+    (placement.tile as u32 + 1) | (placement.flip_x as u32) << 31 | (placement.flip_y as u32) << 30
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Write `tiles` as a Tiled map: `output` (TMX) referencing `<stem>.tsx`, whose image is
+/// `<stem>-tiles.png`, all in the same directory.
+pub fn write(map: &Output, tiles: &Tiles, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let stem = output.file_stem().ok_or("Output has no file name")?.to_string_lossy().into_owned();
+    let tsx_name = format!("{}.tsx", stem);
+    let image_name = format!("{}-tiles.png", stem);
+    let (tile_width, tile_height) = (tiles.size.width, tiles.size.height);
+
+    let (columns, width, height) = tiles.save_atlas(&map.colors, &output.with_file_name(&image_name))?;
+
+    let tsx = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <tileset version=\"1.10\" name=\"{}\" tilewidth=\"{tile_width}\" tileheight=\"{tile_height}\" tilecount=\"{}\" columns=\"{columns}\">\n \
+         <image source=\"{}\" width=\"{}\" height=\"{}\"/>\n\
+         </tileset>\n",
+        escape(&stem),
+        tiles.tiles.len(),
+        escape(&image_name),
+        width,
+        height
+    );
+    fs::write(output.with_file_name(&tsx_name), tsx)?;
+
+    // Global tile IDs start at the tileset's firstgid (1), with the top bits flagging flips; 0 is
+    // an empty cell
+    let mut data = String::new();
+    for (y, row) in tiles.grid.iter().enumerate() {
+        let gids: Vec<String> = row.iter().map(|tile| tile.map_or(0, gid).to_string()).collect();
+        data.push_str(&gids.join(","));
+        data.push_str(if y + 1 < tiles.grid.len() { ",\n" } else { "\n" });
+    }
+    let mut tmx = String::new();
+    write!(
+        tmx,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{w}\" height=\"{h}\" tilewidth=\"{tile_width}\" tileheight=\"{tile_height}\" infinite=\"0\" nextlayerid=\"2\" nextobjectid=\"1\">\n \
+         <tileset firstgid=\"1\" source=\"{}\"/>\n \
+         <layer id=\"1\" name=\"{}\" width=\"{w}\" height=\"{h}\">\n  \
+         <data encoding=\"csv\">\n{data}</data>\n \
+         </layer>\n\
+         </map>\n",
+        escape(&tsx_name),
+        escape(&stem),
+        w = tiles.columns(),
+        h = tiles.rows(),
+    )?;
+    fs::write(output, tmx)?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{Output, render_map};
+
+/// Tile width and height in cells, from `N` (square) or `WxH`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileSize {
+    pub const fn square(size: u32) -> Self {
+        TileSize { width: size, height: size }
+    }
+}
+
+impl FromStr for TileSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s.split_once('x').unwrap_or((s, s));
+        match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok(TileSize { width, height }),
+            _ => Err(format!("Expected a tile size of N or WxH greater than 0, got {}", s)),
+        }
+    }
+}
+
+/// Where a distinct tile is drawn, and whether it's mirrored there.
+#[derive(Clone, Copy, Debug)]
+pub struct Placement {
+    pub tile: usize,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+/// A map cut into tiles, with repeated tiles stored once.
+pub struct Tiles {
+    pub size: TileSize,
+    /// Cell IDs of each distinct tile, row-major; cells past the map's edge are ID 0
+    pub tiles: Vec<Vec<u32>>,
+    /// Tile per grid position, row-major; `None` for fully transparent tiles
+    pub grid: Vec<Vec<Option<Placement>>>,
+}
+
+impl Tiles {
+    pub fn columns(&self) -> u32 {
+        self.grid.first().map_or(0, |row| row.len() as u32)
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.grid.len() as u32
+    }
+
+    /// Lay the distinct tiles out `columns` to a row, as one matrix to render as a tileset image.
+    pub fn atlas(&self, columns: u32) -> Vec<Vec<u32>> {
+        let (width, height) = (self.size.width as usize, self.size.height as usize);
+        // At least one (blank) tile, so a map with no tiles still gets an image
+        let rows = (self.tiles.len() as u32).div_ceil(columns.max(1)).max(1) as usize;
+        let mut matrix = vec![vec![0; columns as usize * width]; rows * height];
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let (tx, ty) = (i % columns as usize * width, i / columns as usize * height);
+            for (y, cells) in tile.chunks(width).enumerate() {
+                matrix[ty + y][tx..tx + width].copy_from_slice(cells);
+            }
+        }
+        matrix
+    }
+
+    /// Render the distinct tiles to a roughly square tileset image at `path`, returning its
+    /// column count and size in pixels.
+    pub fn save_atlas(&self, colors: &HashMap<u32, String>, path: &Path) -> Result<(u32, u32, u32), Box<dyn std::error::Error>> {
+        let columns = (self.tiles.len() as f64).sqrt().ceil().max(1.0) as u32;
+        let image = render_map(&self.atlas(columns), colors, &ProgressBar::hidden())?;
+        image.save(path)?;
+        Ok((columns, image.width(), image.height()))
+    }
+}
+
+fn mirror(cells: &[u32], width: usize, flip_x: bool, flip_y: bool) -> Vec<u32> {
+    let mut rows: Vec<Vec<u32>> = cells.chunks(width).map(<[u32]>::to_vec).collect();
+    if flip_x {
+        rows.iter_mut().for_each(|row| row.reverse());
+    }
+    if flip_y {
+        rows.reverse();
+    }
+    rows.concat()
+}
+
+/// Cut `map` into tiles of `size`; tiles whose cells all render transparent (ID 0 or unknown)
+/// are left empty. With `flips`, a tile that mirrors one already seen reuses it, flipped.
+pub fn slice(map: &Output, size: TileSize, flips: bool) -> Tiles {
+    let (width, height) = (size.width as usize, size.height as usize);
+    let map_width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let (columns, rows) = (map_width.div_ceil(width), map.matrix.len().div_ceil(height));
+    let is_clear = |id: u32| id == 0 || !map.colors.contains_key(&id);
+    let orientations: &[(bool, bool)] = if flips { &[(false, false), (true, false), (false, true), (true, true)] } else { &[(false, false)] };
+
+    let mut tiles = Vec::new();
+    let mut seen: HashMap<Vec<u32>, usize> = HashMap::new();
+    let mut grid = vec![vec![None; columns]; rows];
+    for (ty, grid_row) in grid.iter_mut().enumerate() {
+        for (tx, slot) in grid_row.iter_mut().enumerate() {
+            let mut cells = Vec::with_capacity(width * height);
+            for y in ty * height..(ty + 1) * height {
+                let row = map.matrix.get(y);
+                cells.extend((tx * width..(tx + 1) * width).map(|x| row.and_then(|row| row.get(x)).copied().unwrap_or(0)));
+            }
+            if cells.iter().all(|&id| is_clear(id)) {
+                continue;
+            }
+            // Mirroring twice is the identity, so the tile drawn flipped gives back these cells
+            let found = orientations.iter().find_map(|&(flip_x, flip_y)| {
+                let tile = *seen.get(&mirror(&cells, width, flip_x, flip_y))?;
+                Some(Placement { tile, flip_x, flip_y })
+            });
+            *slot = Some(found.unwrap_or_else(|| {
+                tiles.push(cells.clone());
+                seen.insert(cells, tiles.len() - 1);
+                Placement { tile: tiles.len() - 1, flip_x: false, flip_y: false }
+            }));
+        }
+    }
+    Tiles { size, tiles, grid }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use crate::failure::{self, Kind};
+use crate::matrix::Matrix;
+use crate::tiles::{self, TileSize};
+use crate::{load_map, mapped, write_rows};
+
+/// Cut the map in `input` into tiles of `size` and write the distinct ones to
+/// `<stem>-tiles.png` next to `output`, and to `output` a JSON index with one entry per tile
+/// position: 0 for empty, otherwise the tile's position in the image plus 1, reading rows left to
+/// right. With `flips`, a `flips` matrix follows: bit 0 mirrors the tile horizontally, bit 1
+/// vertically.
+pub fn run(input: &Path, output: &Path, size: TileSize, flips: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let map = load_map(input)?;
+    if map.matrix.is_empty() {
+        return Err(failure::tag(Kind::InvalidJson, "Matrix is empty"));
+    }
+    let stem = output.file_stem().ok_or_else(|| failure::bad_input("Output has no file name"))?.to_string_lossy().into_owned();
+    let image_name = format!("{}-tiles.png", stem);
+
+    let tiles = tiles::slice(&map, size, flips);
+    let (columns, _, _) = tiles.save_atlas(&map.colors, &output.with_file_name(&image_name)).map_err(failure::write)?;
+
+    let indices = Matrix::Memory(tiles.grid.iter().map(|row| row.iter().map(|tile| tile.map_or(0, |placement| placement.tile as u32 + 1)).collect()).collect());
+    let mirrored = Matrix::Memory(tiles.grid.iter().map(|row| row.iter().map(|tile| tile.map_or(0, |placement| placement.flip_x as u32 | (placement.flip_y as u32) << 1)).collect()).collect());
+    mapped::write_file(output, |w| {
+        write!(
+            w,
+            "{{\n  \"tile_width\": {},\n  \"tile_height\": {},\n  \"tile_count\": {},\n  \"columns\": {},\n  \"image\": ",
+            size.width,
+            size.height,
+            tiles.tiles.len(),
+            columns
+        )?;
+        serde_json::to_writer(&mut *w, &image_name)?;
+        w.write_all(b",\n  \"tiles\": [\n")?;
+        write_rows(&indices, b"    ", w)?;
+        if flips {
+            w.write_all(b"  ],\n  \"flips\": [\n")?;
+            write_rows(&mirrored, b"    ", w)?;
+        }
+        w.write_all(b"  ]\n}")
+    })
+    .map_err(failure::write)?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use image::Rgba;
+use std::collections::HashMap;
+
+use crate::{Output, color_distance_sq, hex_to_rgba, rgba_to_hex};
+
+/// Replace every color with its nearest `palette` entry. Used palette entries are numbered
+/// from 1 in palette order; fully transparent cells stay ID 0.
+pub fn quantize(map: &mut Output, palette: &[Rgba<u8>]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut remap: HashMap<u32, u32> = HashMap::new();
+    for (&id, hex) in &map.colors {
+        let color = hex_to_rgba(hex)?;
+        let new_id = if color[3] == 0 {
+            0
+        } else {
+            let nearest = (0..palette.len()).min_by_key(|&i| color_distance_sq(&color, &palette[i])).ok_or("Palette is empty")?;
+            nearest as u32 + 1
+        };
+        remap.insert(id, new_id);
+    }
+
+    let mut colors = HashMap::new();
+    colors.insert(0, "#00000000".to_string());
+    for row in &mut map.matrix {
+        for id in row {
+            *id = remap.get(id).copied().unwrap_or(0);
+            if *id != 0 {
+                colors.entry(*id).or_insert_with(|| rgba_to_hex(&palette[*id as usize - 1]));
+            }
+        }
+    }
+    map.colors = colors;
+    Ok(())
+}
+
+/// Crop rows and columns from the edges whose cells are all fully transparent.
+pub fn trim(map: &mut Output) -> Result<(), Box<dyn std::error::Error>> {
+    let mut clear = HashMap::new();
+    for (&id, hex) in &map.colors {
+        clear.insert(id, hex_to_rgba(hex)?[3] == 0);
+    }
+    // Unknown IDs render transparent too
+    let is_clear = |id: &u32| clear.get(id).copied().unwrap_or(true);
+
+    let width = map.matrix.iter().map(Vec::len).max().unwrap_or(0);
+    let used_rows: Vec<usize> = (0..map.matrix.len()).filter(|&y| !map.matrix[y].iter().all(is_clear)).collect();
+    let used_cols: Vec<usize> = (0..width).filter(|&x| !map.matrix.iter().all(|row| row.get(x).is_none_or(is_clear))).collect();
+
+    let (Some(&top), Some(&bottom)) = (used_rows.first(), used_rows.last()) else {
+        map.matrix.clear();
+        return Ok(());
+    };
+    let (left, right) = (used_cols[0], used_cols[used_cols.len() - 1]);
+
+    map.matrix = map.matrix[top..=bottom]
+        .iter()
+        .map(|row| (left..=right).map(|x| row.get(x).copied().unwrap_or(0)).collect())
+        .collect();
+    Ok(())
+}
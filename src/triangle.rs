@@ -0,0 +1,95 @@
+use image::RgbaImage;
+use pixel::{ColorMapper, PixelError};
+use std::collections::HashMap;
+
+use crate::grid;
+
+// Height of an equilateral triangle per unit of side
+const HEIGHT: f64 = 0.866_025_403_784_438_6;
+
+/// Row and column of the triangle, with sides `side` pixels long, that holds the point
+/// `(x, y)`. Triangle `c` of row `r` points up when `r + c` is even and spans `c - 1` to
+/// `c + 1` half-sides across, so the first and last of a row stick out of the image by half.
+fn cell_at(x: f64, y: f64, side: f64) -> (i64, i64) {
+    let (u, v) = (x / (side / 2.0), y / (side * HEIGHT));
+    let (strip, row) = (u.floor(), v.floor());
+    let (fx, fy) = (u - strip, v - row);
+    let (strip, row) = (strip as i64, row as i64);
+    // The edge across each half-side strip alternates between \ and /
+    let left = if (strip + row).rem_euclid(2) == 0 { fx < fy } else { fx + fy < 1.0 };
+    (row, if left { strip } else { strip + 1 })
+}
+
+/// Columns and rows of triangles with sides of `block_size` pixels that cover a `width` x
+/// `height` image.
+pub fn cells(width: u32, height: u32, block_size: u32) -> (usize, usize) {
+    let side = block_size as f64;
+    let cols = (width as f64 / (side / 2.0)).ceil() as u32 + 1;
+    let rows = (height as f64 / (side * HEIGHT)).ceil().max(1.0) as u32;
+    (cols as usize, rows as usize)
+}
+
+/// Corners of triangle `col` of row `row` for sides `side` long, apex first.
+pub fn corners(row: usize, col: usize, side: f64) -> [(f64, f64); 3] {
+    let (x, top, bottom) = (col as f64 * side / 2.0, row as f64 * side * HEIGHT, (row + 1) as f64 * side * HEIGHT);
+    let (left, right) = (x - side / 2.0, x + side / 2.0);
+    if (row + col).is_multiple_of(2) { [(x, top), (right, bottom), (left, bottom)] } else { [(x, bottom), (left, top), (right, top)] }
+}
+
+/// Size in pixels of the picture of `cols` x `rows` triangles with sides `side` long, without
+/// the halves of the first and last triangles of each row that stick out.
+pub fn picture_size(cols: usize, rows: usize, side: f64) -> (f64, f64) {
+    (cols.saturating_sub(1) as f64 * side / 2.0, rows as f64 * side * HEIGHT)
+}
+
+/// Average `image` over alternating up and down triangles with sides of `block_size` pixels
+/// and assign each a color ID.
+pub fn map_image(image: &RgbaImage, block_size: u32, mapper: &mut ColorMapper) -> Vec<Vec<u32>> {
+    let (width, height) = image.dimensions();
+    grid::average_cells(image, cells(width, height, block_size), |x, y| cell_at(x, y, block_size as f64), mapper)
+}
+
+/// Paint `matrix`, a triangle map, with triangles whose sides are `side` pixels long. IDs
+/// missing from `colors` become transparent and are passed to `missing`.
+pub fn render(matrix: &[Vec<u32>], colors: &HashMap<u32, String>, side: u32, missing: impl FnMut(u32)) -> Result<RgbaImage, PixelError> {
+    let (cols, rows) = (matrix.first().map_or(0, Vec::len), matrix.len());
+    if cols == 0 {
+        return Err(PixelError::Parse("Matrix is empty".to_string()));
+    }
+    grid::paint_cells(matrix, colors, picture_size(cols, rows, side as f64), |x, y| cell_at(x, y, side as f64), missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn triangles_alternate_up_and_down() {
+        let h = 2.0 * HEIGHT;
+        let up = corners(0, 0, 2.0);
+        for (corner, expected) in up.into_iter().zip([(0.0, 0.0), (1.0, h), (-1.0, h)]) {
+            assert!(close(corner, expected), "{:?}", up);
+        }
+        let down = corners(0, 1, 2.0);
+        for (corner, expected) in down.into_iter().zip([(1.0, h), (0.0, 0.0), (2.0, 0.0)]) {
+            assert!(close(corner, expected), "{:?}", down);
+        }
+        // Parity runs on across rows, so the first triangle of the second row points down
+        assert!(close(corners(1, 0, 2.0)[0], (0.0, 2.0 * h)));
+    }
+
+    #[test]
+    fn centroids_are_in_their_own_triangle() {
+        for row in 0..5 {
+            for col in 0..8 {
+                let [a, b, c] = corners(row, col, 10.0);
+                let (x, y) = ((a.0 + b.0 + c.0) / 3.0, (a.1 + b.1 + c.1) / 3.0);
+                assert_eq!(cell_at(x, y, 10.0), (row as i64, col as i64));
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use indicatif::ProgressBar;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::Path;
+
+use crate::edges::Blocks;
+use crate::stream::ImageRows;
+use crate::{ColorMapper, map_rows, mapped, render_map};
+
+const HELP: &str = "left/right tolerance  up/down block size  shift for steps of 10  enter accept  q quit";
+// Largest distance between two RGBA colors
+const MAX_TOLERANCE: f64 = 510.0;
+
+struct Tuner {
+    image: DynamicImage,
+    block_size: u32,
+    tolerance: f64,
+    /// The image mapped with the current parameters, one pixel per cell
+    preview: RgbaImage,
+    colors: usize,
+}
+
+// Quote `text` for POSIX shells when it has anything besides plain path characters
+fn shell_quote(text: &str) -> String {
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || "/._-+:,@".contains(c)) {
+        text.to_string()
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
+
+impl Tuner {
+    fn remap(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut source = ImageRows::new(self.image.clone());
+        let mut mapper = ColorMapper::new(self.tolerance);
+        let matrix = map_rows(&mut source, Blocks::plain(self.block_size), &mut mapper, None, None, &ProgressBar::hidden())?;
+        self.preview = render_map(&matrix.into_rows()?, &mapper.id_to_color, &ProgressBar::hidden())?;
+        self.colors = mapper.id_to_color.len();
+        Ok(())
+    }
+
+    /// Handle a key press; returns `Some(accepted)` once the session ends.
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        let step = if modifiers.contains(KeyModifiers::SHIFT) { 10 } else { 1 };
+        let (block_size, tolerance) = (self.block_size, self.tolerance);
+        match code {
+            KeyCode::Left | KeyCode::Char('h') => self.tolerance = (tolerance - step as f64).max(0.0),
+            KeyCode::Right | KeyCode::Char('l') => self.tolerance = (tolerance + step as f64).min(MAX_TOLERANCE),
+            KeyCode::Down | KeyCode::Char('j') => self.block_size = block_size.saturating_sub(step).max(1),
+            KeyCode::Up | KeyCode::Char('k') => self.block_size = block_size.saturating_add(step).min(self.image.width().max(self.image.height())),
+            KeyCode::Enter => return Ok(Some(true)),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(Some(false)),
+            _ => {}
+        }
+        if (self.block_size, self.tolerance) != (block_size, tolerance) {
+            self.remap()?;
+        }
+        Ok(None)
+    }
+
+    // Two cells per character: the upper half block in the top cell's color over the bottom one's
+    fn draw_preview(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(format!(" {}x{} cells ", self.preview.width(), self.preview.height()));
+        let inner = block.inner(area);
+        let (width, height) = (self.preview.width() as f64, self.preview.height() as f64);
+        let scale = (inner.width as f64 / width).min(inner.height as f64 * 2.0 / height);
+        let (columns, rows) = ((width * scale) as u32, (height * scale / 2.0) as u32);
+        let sample = |x: u32, y: u32| -> Color {
+            let pixel = self.preview.get_pixel(((x as f64 / scale) as u32).min(self.preview.width() - 1), ((y as f64 / scale) as u32).min(self.preview.height() - 1));
+            match pixel {
+                Rgba([r, g, b, a]) if *a > 0 => Color::Rgb(*r, *g, *b),
+                _ => Color::Reset,
+            }
+        };
+        let lines: Vec<Line> = (0..rows)
+            .map(|row| Line::from((0..columns).map(|x| Span::styled("▀", Style::new().fg(sample(x, row * 2)).bg(sample(x, row * 2 + 1)))).collect::<Vec<_>>()))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [preview, status, help] = Layout::vertical([Constraint::Min(3), Constraint::Length(1), Constraint::Length(1)]).areas(frame.area());
+        self.draw_preview(frame, preview);
+        frame.render_widget(Paragraph::new(format!("block size {}  tolerance {}  colors {}", self.block_size, self.tolerance, self.colors)), status);
+        frame.render_widget(Paragraph::new(HELP), help);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<bool, Box<dyn std::error::Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && let Some(accepted) = self.key(key.code, key.modifiers)?
+            {
+                return Ok(accepted);
+            }
+        }
+    }
+}
+
+/// Tune the block size and tolerance for `input` interactively, starting from the given values,
+/// and print the accepted ones as a `pixelate` command.
+pub fn run(input: &Path, block_size: u32, tolerance: f64) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tuner = Tuner { image: mapped::open_image(input, true)?, block_size, tolerance, preview: RgbaImage::new(1, 1), colors: 0 };
+    tuner.remap()?;
+
+    let mut terminal = ratatui::try_init()?;
+    let result = tuner.run(&mut terminal);
+    ratatui::try_restore()?;
+    if result? {
+        println!("pixel pixelate -i {} -b {} -t {}", shell_quote(&input.to_string_lossy()), tuner.block_size, tuner.tolerance);
+    }
+    Ok(())
+}
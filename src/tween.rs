@@ -0,0 +1,99 @@
+use image::Rgba;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::animation::{self, ResolvedFrame};
+use crate::failure::{self, Kind};
+use crate::matrix::Matrix;
+use crate::{MapFile, MapMeta, PixelError, color_distance_sq, hex_to_rgba, mapped, write_frames_json};
+
+/// Most in-between frames `--steps` inserts between two frames
+pub const MAX_STEPS: u32 = 1000;
+
+/// Colors of a map, matched by nearest RGBA distance with ties going to the lowest ID.
+struct Palette {
+    entries: Vec<(u32, Rgba<u8>)>,
+    by_id: HashMap<u32, Rgba<u8>>,
+    nearest: HashMap<[u8; 4], u32>,
+}
+
+impl Palette {
+    fn new(colors: &HashMap<u32, String>) -> Result<Self, PixelError> {
+        let mut entries = colors.iter().map(|(&id, hex)| Ok((id, hex_to_rgba(hex)?))).collect::<Result<Vec<_>, PixelError>>()?;
+        entries.sort_by_key(|&(id, _)| id);
+        let by_id = entries.iter().copied().collect();
+        Ok(Palette { entries, by_id, nearest: HashMap::new() })
+    }
+
+    // Unknown IDs count as transparent, as in reconstruct
+    fn color(&self, id: u32) -> Rgba<u8> {
+        self.by_id.get(&id).copied().unwrap_or(Rgba([0, 0, 0, 0]))
+    }
+
+    fn snap(&mut self, color: Rgba<u8>) -> u32 {
+        let entries = &self.entries;
+        *self.nearest.entry(color.0).or_insert_with(|| entries.iter().min_by_key(|(_, known)| color_distance_sq(&color, known)).map_or(0, |&(id, _)| id))
+    }
+}
+
+fn lerp(a: Rgba<u8>, b: Rgba<u8>, t: f32) -> Rgba<u8> {
+    let mut out = [0u8; 4];
+    for c in 0..4 {
+        out[c] = (a[c] as f32 + (b[c] as f32 - a[c] as f32) * t).round() as u8;
+    }
+    Rgba(out)
+}
+
+/// `steps` matrices blending from `from` to `to`, every cell snapped back to the palette.
+fn between(from: &[Vec<u32>], to: &[Vec<u32>], steps: u32, palette: &mut Palette) -> Result<Vec<Vec<Vec<u32>>>, String> {
+    if from.len() != to.len() || from.iter().zip(to).any(|(a, b)| a.len() != b.len()) {
+        return Err("Frames differ in size".to_string());
+    }
+    let mut frames = Vec::with_capacity(steps as usize);
+    for step in 1..=steps {
+        let t = step as f32 / (steps + 1) as f32;
+        let matrix = from
+            .iter()
+            .zip(to)
+            .map(|(a, b)| a.iter().zip(b).map(|(&a, &b)| if a == b { a } else { palette.snap(lerp(palette.color(a), palette.color(b), t)) }).collect())
+            .collect();
+        frames.push(matrix);
+    }
+    Ok(frames)
+}
+
+/// Insert `steps` in-between frames after every frame of the map in `input` but the last
+/// (or only between the two `frames` given), splitting each frame's delay among its
+/// in-betweens, and write the result to `output`.
+pub fn run(input: &Path, output: &Path, steps: u32, frames: Option<&[usize]>) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = mapped::map_file(input)?;
+    let data: MapFile = serde_json::from_slice(&contents)?;
+    let meta = serde_json::from_slice::<MapMeta>(&contents)?.fields();
+    let map_frames = data.frames.ok_or_else(|| failure::tag(Kind::InvalidJson, "Map has no frames"))?;
+    let mut resolved: Vec<ResolvedFrame> = animation::resolve(map_frames).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+
+    if let Some(frames) = frames {
+        let &[first, second] = frames else {
+            return Err(failure::bad_input("--frames takes two frames, like 3,4"));
+        };
+        let pick = |i: usize| resolved.get(i).cloned().ok_or_else(|| failure::bad_input(format!("No frame {}; the map has {} (counting from 0)", i, resolved.len())));
+        resolved = vec![pick(first)?, pick(second)?];
+    }
+
+    let mut palette = Palette::new(&data.colors).map_err(|e| failure::tag(Kind::InvalidJson, e))?;
+    let mut tweened = Vec::new();
+    for (i, (delay, matrix)) in resolved.iter().enumerate() {
+        let Some((_, next)) = resolved.get(i + 1) else {
+            tweened.push((*delay, Matrix::Memory(matrix.clone())));
+            break;
+        };
+        let delay = delay / (steps + 1);
+        tweened.push((delay, Matrix::Memory(matrix.clone())));
+        for inbetween in between(matrix, next, steps, &mut palette).map_err(|e| failure::tag(Kind::InvalidJson, e))? {
+            tweened.push((delay, Matrix::Memory(inbetween)));
+        }
+    }
+
+    mapped::write_file(output, |w| write_frames_json(&tweened, &data.colors, false, &meta, w)).map_err(failure::write)?;
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use indicatif::ProgressBar;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::{Output, PixelError, cache, failure, hex_to_rgba, render_map};
+
+#[derive(Serialize)]
+struct Vector2 {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Serialize)]
+struct Color {
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32,
+}
+
+#[derive(Serialize)]
+struct PaletteEntry {
+    id: u32,
+    color: Color,
+}
+
+// JsonUtility only reads flat arrays of plain fields, hence `cells` instead of rows
+#[derive(Serialize)]
+struct MapAsset {
+    width: u32,
+    height: u32,
+    /// Normalized, from the bottom-left corner as in Unity's sprites
+    pivot: Vector2,
+    palette: Vec<PaletteEntry>,
+    /// Color IDs, row-major starting at the top row
+    cells: Vec<u32>,
+    texture: String,
+}
+
+// Unity expects 32 hex digits; derived from the file name so re-exports keep references intact
+fn guid(name: &str) -> String {
+    let high = cache::hash_pixels(cache::HASH_SEED, name.as_bytes());
+    let low = cache::hash_pixels(high, name.as_bytes());
+    format!("{:016x}{:016x}", high, low)
+}
+
+/// Write the map for Unity: `output` as JSON for `JsonUtility.FromJsonOverwrite` into a
+/// ScriptableObject, plus the texture `<stem>.png` and a `.meta` importing it as an
+/// uncompressed, point-filtered sprite.
+pub fn write(map: &Output, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let stem = output.file_stem().ok_or("Output has no file name")?.to_string_lossy().into_owned();
+    let texture_name = format!("{}.png", stem);
+    let texture_path = output.with_file_name(&texture_name);
+    if texture_path == output {
+        return Err(failure::bad_input("Output must not end in .png; the texture is written next to it"));
+    }
+
+    let image = render_map(&map.matrix, &map.colors, &ProgressBar::hidden())?;
+    image.save(&texture_path)?;
+
+    let meta = format!(
+        "fileFormatVersion: 2\n\
+         guid: {}\n\
+         TextureImporter:\n  \
+           serializedVersion: 12\n  \
+           mipmaps:\n    \
+             enableMipMap: 0\n  \
+           textureSettings:\n    \
+             serializedVersion: 2\n    \
+             filterMode: 0\n    \
+             aniso: 1\n    \
+             mipBias: 0\n    \
+             wrapU: 1\n    \
+             wrapV: 1\n    \
+             wrapW: 1\n  \
+           npotScale: 0\n  \
+           alphaIsTransparency: 1\n  \
+           textureType: 8\n  \
+           spriteMode: 1\n  \
+           spritePixelsToUnits: 1\n  \
+           spritePivot: {{x: 0.5, y: 0.5}}\n  \
+           alignment: 0\n  \
+           platformSettings:\n  \
+           - serializedVersion: 3\n    \
+             buildTarget: DefaultTexturePlatform\n    \
+             textureCompression: 0\n    \
+             maxTextureSize: 16384\n",
+        guid(&texture_name)
+    );
+    fs::write(output.with_file_name(format!("{}.meta", texture_name)), meta)?;
+
+    let mut palette = map
+        .colors
+        .iter()
+        .map(|(&id, hex)| {
+            let [r, g, b, a] = hex_to_rgba(hex)?.0.map(|c| c as f32 / 255.0);
+            Ok(PaletteEntry { id, color: Color { r, g, b, a } })
+        })
+        .collect::<Result<Vec<_>, PixelError>>()?;
+    palette.sort_by_key(|entry| entry.id);
+
+    let asset = MapAsset {
+        width: image.width(),
+        height: image.height(),
+        pivot: Vector2 { x: 0.5, y: 0.5 },
+        palette,
+        cells: map.matrix.iter().flat_map(|row| (0..image.width() as usize).map(|x| row.get(x).copied().unwrap_or(0))).collect(),
+        texture: texture_name,
+    };
+    serde_json::to_writer_pretty(BufWriter::new(File::create(output)?), &asset)?;
+    Ok(())
+}
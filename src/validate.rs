@@ -0,0 +1,325 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::edges::Edges;
+use crate::failure::{self, Kind};
+use crate::grid::Grid;
+use crate::mapped;
+use crate::segment::Segmentation;
+
+/// JSON Schema of map files, which `validate` checks them against.
+pub const SCHEMA: &str = include_str!("../schema/map.schema.json");
+
+// Problems found so far, each with the JSON pointer of where it is
+#[derive(Default)]
+struct Problems {
+    found: Vec<(String, String)>,
+    // First cell using each color ID, to check against the palette at the end
+    used: BTreeMap<u64, String>,
+}
+
+impl Problems {
+    fn add(&mut self, at: &str, problem: impl Into<String>) {
+        self.found.push((at.to_string(), problem.into()));
+    }
+
+    fn id(&mut self, value: &Value, at: &str) {
+        match value.as_u64() {
+            Some(id) if id <= u32::MAX as u64 => {
+                self.used.entry(id).or_insert_with(|| at.to_string());
+            }
+            _ => self.add(at, format!("expected a color ID from 0 to {}, got {}", u32::MAX, value)),
+        }
+    }
+
+    // Rows and columns of a valid `matrix`, or `None` after adding its problems
+    fn matrix(&mut self, matrix: &Value, at: &str) -> Option<(usize, usize)> {
+        let Some(rows) = matrix.as_array() else {
+            self.add(at, "expected an array of rows");
+            return None;
+        };
+        let before = self.found.len();
+        let width = rows.first().and_then(Value::as_array).map_or(0, Vec::len);
+        for (y, row) in rows.iter().enumerate() {
+            let at = format!("{}/{}", at, y);
+            let Some(row) = row.as_array() else {
+                self.add(&at, "expected an array of color IDs");
+                continue;
+            };
+            if row.len() != width {
+                self.add(&at, format!("row has {} cells, the first has {}", row.len(), width));
+            }
+            for (x, id) in row.iter().enumerate() {
+                self.id(id, &format!("{}/{}", at, x));
+            }
+        }
+        (self.found.len() == before).then_some((width, rows.len()))
+    }
+
+    fn frames(&mut self, frames: &Value) {
+        let Some(frames) = frames.as_array() else {
+            self.add("/frames", "expected an array of frames");
+            return;
+        };
+        if frames.is_empty() {
+            self.add("/frames", "map has no frames");
+        }
+        // Size of the frames so far, once a full matrix gives it
+        let mut size = None;
+        for (i, frame) in frames.iter().enumerate() {
+            let at = format!("/frames/{}", i);
+            let Some(frame) = frame.as_object() else {
+                self.add(&at, "expected an object");
+                continue;
+            };
+            match frame.get("delay_ms") {
+                Some(delay) if delay.as_u64().is_some_and(|delay| delay <= u32::MAX as u64) => {}
+                Some(delay) => self.add(&format!("{}/delay_ms", at), format!("expected milliseconds from 0 to {}, got {}", u32::MAX, delay)),
+                None => self.add(&at, "missing delay_ms"),
+            }
+            match (frame.get("matrix"), frame.get("changes")) {
+                (Some(matrix), None) => {
+                    let frame_size = self.matrix(matrix, &format!("{}/matrix", at));
+                    if let (Some((width, height)), Some((first_width, first_height))) = (frame_size, size)
+                        && (width, height) != (first_width, first_height)
+                    {
+                        self.add(&format!("{}/matrix", at), format!("frame is {}x{}, earlier frames are {}x{}", width, height, first_width, first_height));
+                    }
+                    size = size.or(frame_size);
+                }
+                (None, Some(changes)) => {
+                    if i == 0 {
+                        self.add(&at, "the first frame needs a full matrix");
+                    }
+                    self.changes(changes, &format!("{}/changes", at), size);
+                }
+                (Some(_), Some(_)) => self.add(&at, "frame has both a matrix and changes"),
+                (None, None) => self.add(&at, "frame needs either a matrix or changes"),
+            }
+        }
+    }
+
+    fn changes(&mut self, changes: &Value, at: &str, size: Option<(usize, usize)>) {
+        let Some(changes) = changes.as_array() else {
+            self.add(at, "expected an array of [x, y, id] changes");
+            return;
+        };
+        for (i, change) in changes.iter().enumerate() {
+            let at = format!("{}/{}", at, i);
+            let Some([x, y, id]) = change.as_array().map(Vec::as_slice) else {
+                self.add(&at, "expected [x, y, id]");
+                continue;
+            };
+            match (x.as_u64(), y.as_u64()) {
+                (Some(x), Some(y)) => {
+                    if let Some((width, height)) = size
+                        && (x >= width as u64 || y >= height as u64)
+                    {
+                        self.add(&at, format!("cell ({}, {}) is outside the {}x{} matrix", x, y, width, height));
+                    }
+                }
+                _ => self.add(&at, format!("expected cell coordinates, got ({}, {})", x, y)),
+            }
+            self.id(id, &format!("{}/2", at));
+        }
+    }
+
+    // A size in pixels under `key`, or `None` after adding its problem
+    fn pixels(&mut self, map: &Map<String, Value>, key: &str) -> Option<u64> {
+        match map.get(key) {
+            Some(value) => match value.as_u64() {
+                Some(pixels) if (1..=u32::MAX as u64).contains(&pixels) => Some(pixels),
+                _ => {
+                    self.add(&format!("/{}", key), format!("expected a number of pixels from 1 to {}, got {}", u32::MAX, value));
+                    None
+                }
+            },
+            None => {
+                self.add("", format!("missing {}", key));
+                None
+            }
+        }
+    }
+
+    // A segmented map: its size, the cells of its segmentation and a color ID for each
+    fn segments(&mut self, map: &Map<String, Value>, segmentation: &Value) {
+        let size = self.pixels(map, "width").zip(self.pixels(map, "height"));
+        let cells = match map.get("cells") {
+            Some(Value::Array(cells)) => {
+                for (i, id) in cells.iter().enumerate() {
+                    self.id(id, &format!("/cells/{}", i));
+                }
+                Some(cells.len())
+            }
+            Some(_) => {
+                self.add("/cells", "expected an array of color IDs");
+                None
+            }
+            None => {
+                self.add("", "missing cells");
+                None
+            }
+        };
+        match Segmentation::value_variants().iter().find(|variant| segmentation.as_str() == Some(variant.name())) {
+            Some(Segmentation::Voronoi) => self.sites(map.get("sites"), size, cells),
+            Some(Segmentation::Slic) => self.labels(map.get("labels"), size, cells),
+            None => {
+                let names: Vec<&str> = Segmentation::value_variants().iter().map(|variant| variant.name()).collect();
+                self.add("/segmentation", format!("expected one of {}, got {}", names.join(", "), segmentation));
+            }
+        }
+    }
+
+    fn sites(&mut self, sites: Option<&Value>, size: Option<(u64, u64)>, cells: Option<usize>) {
+        let Some(sites) = sites else {
+            self.add("", "missing sites");
+            return;
+        };
+        let Some(sites) = sites.as_array() else {
+            self.add("/sites", "expected an array of [x, y] sites");
+            return;
+        };
+        if sites.is_empty() {
+            self.add("/sites", "map has no sites");
+        }
+        for (i, site) in sites.iter().enumerate() {
+            let at = format!("/sites/{}", i);
+            match site.as_array().map(Vec::as_slice) {
+                Some([x, y]) if x.is_number() && y.is_number() => {
+                    let (x, y) = (x.as_f64().unwrap_or_default(), y.as_f64().unwrap_or_default());
+                    if let Some((width, height)) = size
+                        && !((0.0..=width as f64).contains(&x) && (0.0..=height as f64).contains(&y))
+                    {
+                        self.add(&at, format!("site ({}, {}) is outside the {}x{} image", x, y, width, height));
+                    }
+                }
+                _ => self.add(&at, "expected [x, y] in pixels"),
+            }
+        }
+        if let Some(cells) = cells
+            && cells != sites.len()
+        {
+            self.add("/cells", format!("{} cells for {} sites", cells, sites.len()));
+        }
+    }
+
+    fn labels(&mut self, labels: Option<&Value>, size: Option<(u64, u64)>, cells: Option<usize>) {
+        let Some(labels) = labels else {
+            self.add("", "missing labels");
+            return;
+        };
+        let Some(rows) = labels.as_array() else {
+            self.add("/labels", "expected an array of rows of labels");
+            return;
+        };
+        if let Some((_, height)) = size
+            && rows.len() as u64 != height
+        {
+            self.add("/labels", format!("expected {} rows of labels, got {}", height, rows.len()));
+        }
+        for (y, row) in rows.iter().enumerate() {
+            let Some(row) = row.as_array() else {
+                self.add(&format!("/labels/{}", y), "expected a row of labels");
+                continue;
+            };
+            if let Some((width, _)) = size
+                && row.len() as u64 != width
+            {
+                self.add(&format!("/labels/{}", y), format!("expected {} labels, got {}", width, row.len()));
+            }
+            for (x, label) in row.iter().enumerate() {
+                match label.as_u64() {
+                    Some(label) if cells.is_none_or(|cells| label < cells as u64) => {}
+                    Some(_) => self.add(&format!("/labels/{}/{}", y, x), format!("label {} is past the {} cells", label, cells.unwrap_or_default())),
+                    None => self.add(&format!("/labels/{}/{}", y, x), format!("expected the index of a cell, got {}", label)),
+                }
+            }
+        }
+    }
+
+    fn colors(&mut self, colors: &Map<String, Value>) -> HashSet<u64> {
+        let mut ids = HashSet::new();
+        for (key, color) in colors {
+            let at = format!("/colors/{}", key);
+            match key.parse::<u32>() {
+                Ok(id) if id.to_string() == *key => {
+                    ids.insert(id as u64);
+                }
+                _ => self.add(&at, format!("expected a color ID from 0 to {} as key", u32::MAX)),
+            }
+            match color.as_str() {
+                Some(hex) if pixel::hex_to_rgba(hex).is_ok() => {}
+                _ => self.add(&at, format!("expected a hex color such as #RRGGBBAA, got {}", color)),
+            }
+        }
+        ids
+    }
+}
+
+/// Check the map file `input` against [`SCHEMA`] and for what the schema can't express: rows
+/// of equal length, frames of equal size, changes inside the matrix, a cell for every site or
+/// label of segmented maps and a color for every ID used. Prints every problem found rather than
+/// stopping at the first.
+pub fn run(input: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = mapped::map_file(input)?;
+    let map: Value = serde_json::from_slice(&contents)?;
+    let Some(map) = map.as_object() else {
+        return Err(failure::tag(Kind::InvalidJson, format!("{}: expected a JSON object", input.display())));
+    };
+
+    let mut problems = Problems::default();
+    match (map.get("matrix"), map.get("frames"), map.get("segmentation")) {
+        (Some(matrix), None, None) => {
+            problems.matrix(matrix, "/matrix");
+        }
+        (None, Some(frames), None) => problems.frames(frames),
+        (None, None, Some(segmentation)) => problems.segments(map, segmentation),
+        (Some(_), Some(_), _) => problems.add("", "map has both a matrix and frames"),
+        (_, _, Some(_)) => problems.add("", "segmented maps have cells instead of a matrix or frames"),
+        (None, None, None) => problems.add("", "map needs either a matrix, frames or a segmentation"),
+    }
+    if let Some(edges) = map.get("edges")
+        && edges.as_str().is_none_or(|edges| Edges::from_str(edges, false).is_err())
+    {
+        let names: Vec<&str> = Edges::value_variants().iter().map(|edges| edges.name()).collect();
+        problems.add("/edges", format!("expected one of {}, got {}", names.join(", "), edges));
+    }
+    if let Some(depth) = map.get("depth")
+        && !matches!(depth.as_u64(), Some(8 | 16))
+    {
+        problems.add("/depth", format!("expected 8 or 16, got {}", depth));
+    }
+    if let Some(grid) = map.get("grid")
+        && grid.as_str().is_none_or(|grid| Grid::from_str(grid, false).is_err())
+    {
+        let names: Vec<&str> = Grid::value_variants().iter().map(|grid| grid.name()).collect();
+        problems.add("/grid", format!("expected one of {}, got {}", names.join(", "), grid));
+    }
+    let ids = match map.get("colors") {
+        Some(Value::Object(colors)) => problems.colors(colors),
+        Some(_) => {
+            problems.add("/colors", "expected an object of colors by ID");
+            HashSet::new()
+        }
+        None => {
+            problems.add("", "missing colors");
+            HashSet::new()
+        }
+    };
+    for (id, at) in std::mem::take(&mut problems.used) {
+        if !ids.contains(&id) {
+            problems.add(&at, format!("color ID {} has no entry in colors", id));
+        }
+    }
+
+    for (at, problem) in &problems.found {
+        println!("{}#{}: {}", input.display(), at, problem);
+    }
+    match problems.found.len() {
+        0 => Ok(()),
+        1 => Err(failure::tag(Kind::InvalidJson, format!("{}: 1 problem", input.display()))),
+        count => Err(failure::tag(Kind::InvalidJson, format!("{}: {} problems", input.display(), count))),
+    }
+}
@@ -0,0 +1,89 @@
+use image::{ImageError, ImageResult, RgbaImage};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use crate::animation::Frames;
+
+const EXTENSIONS: [&str; 5] = ["mp4", "webm", "mov", "mkv", "avi"];
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+pub fn dimensions(path: &Path) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=width,height", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Can't run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    let text = String::from_utf8(output.stdout)?;
+    let (width, height) = text.trim().split_once(',').ok_or("ffprobe didn't report a video size")?;
+    Ok((width.parse()?, height.parse()?))
+}
+
+/// Raw RGBA frames read from an ffmpeg child process.
+struct VideoFrames {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    width: u32,
+    height: u32,
+    delay: u32,
+    done: bool,
+}
+
+impl Iterator for VideoFrames {
+    type Item = ImageResult<(u32, RgbaImage)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut buf = vec![0u8; self.width as usize * self.height as usize * 4];
+        match self.stdout.read_exact(&mut buf) {
+            Ok(()) => Some(Ok((self.delay, RgbaImage::from_raw(self.width, self.height, buf)?))),
+            Err(e) => {
+                self.done = true;
+                if e.kind() != io::ErrorKind::UnexpectedEof {
+                    return Some(Err(ImageError::IoError(e)));
+                }
+                match self.child.wait() {
+                    Ok(status) if status.success() => None,
+                    Ok(status) => Some(Err(ImageError::IoError(io::Error::other(format!("ffmpeg exited with {}", status))))),
+                    Err(e) => Some(Err(ImageError::IoError(e))),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for VideoFrames {
+    fn drop(&mut self) {
+        // Stop decoding when mapping gave up early; harmless if ffmpeg already exited
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Decode the video at `path` with ffmpeg, sampled at `fps` frames per second.
+pub fn frames(path: &Path, fps: f64) -> Result<Frames, Box<dyn std::error::Error>> {
+    if !fps.is_finite() || fps <= 0.0 {
+        return Err("--fps must be greater than 0".into());
+    }
+    let (width, height) = dimensions(path)?;
+    // Without -noautorotate, rotated videos would come out with width and height swapped
+    // relative to what ffprobe reports
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-noautorotate", "-i"])
+        .arg(path)
+        .args(["-vf", &format!("fps={}", fps), "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Can't run ffmpeg: {}", e))?;
+    let stdout = BufReader::new(child.stdout.take().ok_or("ffmpeg has no output")?);
+
+    Ok(Box::new(VideoFrames { child, stdout, width, height, delay: (1000.0 / fps).round() as u32, done: false }))
+}
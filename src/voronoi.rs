@@ -0,0 +1,200 @@
+use clap::ValueEnum;
+
+// Candidate points tried for each site when spreading them out; more spread them more evenly
+const CANDIDATES: usize = 10;
+
+/// How Voronoi sites are placed.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Seeding {
+    /// Uniformly at random, so cells vary widely in size
+    Random,
+    /// Spread out like a Poisson-disk sample, the best of several random candidates each, so
+    /// cells come out of similar size
+    #[default]
+    Poisson,
+}
+
+// splitmix64, so a seed places the same sites on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn distance_sq(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)
+}
+
+/// Voronoi sites in a `width` x `height` area, bucketed by position for nearest-site queries.
+pub struct Sites {
+    points: Vec<[f64; 2]>,
+    bucket: f64,
+    cols: usize,
+    rows: usize,
+    // Indices of the points in each bucket, row by row
+    buckets: Vec<Vec<u32>>,
+}
+
+impl Sites {
+    // No sites yet, with buckets sized to hold about one each of `count`
+    fn with_capacity(width: u32, height: u32, count: usize) -> Self {
+        let bucket = (width as f64 * height as f64 / count.max(1) as f64).sqrt().max(1.0);
+        let cols = (width as f64 / bucket).ceil().max(1.0) as usize;
+        let rows = (height as f64 / bucket).ceil().max(1.0) as usize;
+        Sites { points: Vec::with_capacity(count), bucket, cols, rows, buckets: vec![Vec::new(); cols * rows] }
+    }
+
+    pub fn new(points: &[[f64; 2]], width: u32, height: u32) -> Self {
+        let mut sites = Sites::with_capacity(width, height, points.len());
+        for &point in points {
+            sites.push(point);
+        }
+        sites
+    }
+
+    /// `count` sites placed by `seeding` from `seed`, with coordinates rounded to hundredths so
+    /// they read back the same from a map. No two sites fall on the same point.
+    pub fn seed(width: u32, height: u32, count: usize, seeding: Seeding, seed: u64) -> Self {
+        let mut rng = Rng(seed);
+        let mut sites = Sites::with_capacity(width, height, count);
+        let mut random = || [((rng.unit() * width as f64 * 100.0).floor() / 100.0), ((rng.unit() * height as f64 * 100.0).floor() / 100.0)];
+        while sites.points.len() < count {
+            let point = match seeding {
+                Seeding::Random => random(),
+                Seeding::Poisson => (0..CANDIDATES).map(|_| random()).max_by(|&a, &b| sites.clearance(a).total_cmp(&sites.clearance(b))).unwrap_or_default(),
+            };
+            if sites.clearance(point) > 0.0 {
+                sites.push(point);
+            }
+        }
+        sites
+    }
+
+    fn push(&mut self, point: [f64; 2]) {
+        let (col, row) = self.bucket_of(point);
+        self.buckets[row * self.cols + col].push(self.points.len() as u32);
+        self.points.push(point);
+    }
+
+    pub fn points(&self) -> &[[f64; 2]] {
+        &self.points
+    }
+
+    fn bucket_of(&self, [x, y]: [f64; 2]) -> (usize, usize) {
+        (((x / self.bucket) as usize).min(self.cols - 1), ((y / self.bucket) as usize).min(self.rows - 1))
+    }
+
+    // Hand `visit` the sites of the buckets around `point` a ring at a time, nearest ring first,
+    // with how close any site of the later rings can be, until it returns true
+    fn search(&self, point: [f64; 2], mut visit: impl FnMut(&[u32], f64) -> bool) {
+        let (col, row) = self.bucket_of(point);
+        let mut ring = Vec::new();
+        for r in 0..=self.cols.max(self.rows) {
+            ring.clear();
+            for y in row.saturating_sub(r)..=(row + r).min(self.rows - 1) {
+                for x in col.saturating_sub(r)..=(col + r).min(self.cols - 1) {
+                    if x.abs_diff(col) == r || y.abs_diff(row) == r {
+                        ring.extend_from_slice(&self.buckets[y * self.cols + x]);
+                    }
+                }
+            }
+            if visit(&ring, r as f64 * self.bucket) {
+                return;
+            }
+        }
+    }
+
+    /// Index of the site nearest to `point`, the lowest of those as near; `None` without sites.
+    pub fn nearest(&self, point: [f64; 2]) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        self.search(point, |ring, reach| {
+            for &i in ring {
+                let (i, distance) = (i as usize, distance_sq(point, self.points[i as usize]));
+                if best.is_none_or(|(j, nearest)| distance < nearest || (distance == nearest && i < j)) {
+                    best = Some((i, distance));
+                }
+            }
+            best.is_some_and(|(_, nearest)| nearest < reach * reach)
+        });
+        best.map(|(i, _)| i)
+    }
+
+    // Squared distance from `point` to the nearest site, infinite without sites
+    fn clearance(&self, point: [f64; 2]) -> f64 {
+        self.nearest(point).map_or(f64::INFINITY, |i| distance_sq(point, self.points[i]))
+    }
+
+    /// Corners of the Voronoi cell of site `i`, cut to the `width` x `height` area.
+    pub fn cell(&self, i: usize, width: u32, height: u32) -> Vec<[f64; 2]> {
+        let (width, height) = (width as f64, height as f64);
+        let site = self.points[i];
+        let mut polygon = vec![[0.0, 0.0], [width, 0.0], [width, height], [0.0, height]];
+        self.search(site, |ring, reach| {
+            for &j in ring {
+                if j as usize != i {
+                    polygon = clip(&polygon, site, self.points[j as usize]);
+                }
+            }
+            // Sites past twice the farthest corner can't cut the cell any more
+            let farthest = polygon.iter().map(|&corner| distance_sq(site, corner)).fold(0.0, f64::max);
+            4.0 * farthest <= reach * reach
+        });
+        polygon
+    }
+}
+
+// The part of convex `polygon` nearer to `site` than to `other`
+fn clip(polygon: &[[f64; 2]], site: [f64; 2], other: [f64; 2]) -> Vec<[f64; 2]> {
+    let normal = [other[0] - site[0], other[1] - site[1]];
+    let middle = [(site[0] + other[0]) / 2.0, (site[1] + other[1]) / 2.0];
+    let side = |p: [f64; 2]| (p[0] - middle[0]) * normal[0] + (p[1] - middle[1]) * normal[1];
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (k, &p) in polygon.iter().enumerate() {
+        let q = polygon[(k + 1) % polygon.len()];
+        let (sp, sq) = (side(p), side(q));
+        if sp <= 0.0 {
+            clipped.push(p);
+        }
+        if (sp < 0.0 && sq > 0.0) || (sp > 0.0 && sq < 0.0) {
+            let t = sp / (sp - sq);
+            clipped.push([p[0] + (q[0] - p[0]) * t, p[1] + (q[1] - p[1]) * t]);
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [[f64; 2]; 4] = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+
+    #[test]
+    fn clip_keeps_the_side_of_the_site() {
+        // The bisector of (1, 2) and (3, 2) is x = 2
+        assert_eq!(clip(&SQUARE, [1.0, 2.0], [3.0, 2.0]), vec![[0.0, 0.0], [2.0, 0.0], [2.0, 4.0], [0.0, 4.0]]);
+        assert_eq!(clip(&SQUARE, [3.0, 2.0], [1.0, 2.0]), vec![[2.0, 0.0], [4.0, 0.0], [4.0, 4.0], [2.0, 4.0]]);
+        // The bisector of opposite corners is the other diagonal
+        assert_eq!(clip(&SQUARE, [0.0, 0.0], [4.0, 4.0]), vec![[0.0, 0.0], [4.0, 0.0], [0.0, 4.0]]);
+    }
+
+    #[test]
+    fn cells_split_the_area_between_sites() {
+        let sites = Sites::new(&[[1.0, 2.0], [3.0, 2.0]], 4, 4);
+        assert_eq!(sites.cell(0, 4, 4), vec![[0.0, 0.0], [2.0, 0.0], [2.0, 4.0], [0.0, 4.0]]);
+        assert_eq!(sites.nearest([2.5, 0.0]), Some(1));
+        // Ties go to the lower index
+        assert_eq!(sites.nearest([2.0, 3.0]), Some(0));
+    }
+}
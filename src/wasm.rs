@@ -0,0 +1,32 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{Output, map_encoded, render_png};
+
+/// Options of [`map`], as for `pixel pixelate`.
+#[wasm_bindgen]
+pub struct MapOptions {
+    pub block_size: u32,
+    pub tolerance: f64,
+}
+
+#[wasm_bindgen]
+impl MapOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(block_size: u32, tolerance: f64) -> MapOptions {
+        MapOptions { block_size, tolerance }
+    }
+}
+
+/// Map an encoded image (PNG, JPEG, GIF, ...) to a JSON map.
+#[wasm_bindgen]
+pub fn map(bytes: &[u8], options: &MapOptions) -> Result<String, JsError> {
+    let map = map_encoded(bytes, options.block_size, options.tolerance).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(serde_json::to_string(&map)?)
+}
+
+/// Paint a JSON map with a single matrix back into a PNG.
+#[wasm_bindgen]
+pub fn reconstruct(json: &str) -> Result<Vec<u8>, JsError> {
+    let map: Output = serde_json::from_str(json)?;
+    render_png(&map).map_err(|e| JsError::new(&e.to_string()))
+}
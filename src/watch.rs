@@ -0,0 +1,52 @@
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+// Editors often write a file in several steps; wait for them to settle
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Run `process` now and again every time `input` is written, until interrupted.
+///
+/// The parent directory is watched rather than the file, so saves that replace the file
+/// (write to temp + rename) are still picked up. Errors are reported and watching continues.
+pub fn run(input: &Path, mut process: impl FnMut() -> Result<(), Box<dyn std::error::Error>>) -> Result<(), Box<dyn std::error::Error>> {
+    let file_name = input.file_name().ok_or("Input must be a file")?.to_os_string();
+    let dir = match input.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    let mut run_once = || {
+        let start = Instant::now();
+        match process() {
+            Ok(()) => info!(input = %input.display(), elapsed_ms = start.elapsed().as_millis() as u64, "processed"),
+            Err(e) => error!(input = %input.display(), "{}", e),
+        }
+    };
+
+    run_once();
+    info!(input = %input.display(), "watching for changes (Ctrl+C to stop)");
+
+    let touches_input = |event: &notify::Event| {
+        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+            && event.paths.iter().any(|p| p.file_name() == Some(file_name.as_os_str()))
+    };
+
+    for event in &rx {
+        if !touches_input(&event?) {
+            continue;
+        }
+        // Swallow the rest of this save's events before processing
+        while rx.recv_timeout(SETTLE).is_ok() {}
+        if input.exists() {
+            run_once();
+        }
+    }
+    Ok(())
+}
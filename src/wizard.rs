@@ -0,0 +1,50 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+use dialoguer::Input;
+use std::ffi::OsString;
+use std::io::IsTerminal;
+
+/// Whether to ask for what's missing from `args` instead of failing: on a terminal, when no
+/// command or only part of `pixelate` was given.
+pub fn wanted(command: &Command, args: &[OsString]) -> bool {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return false;
+    }
+    let partial = command.clone().ignore_errors(true).get_matches_from(args);
+    matches!(partial.subcommand_name(), None | Some("pixelate"))
+}
+
+fn given(matches: Option<&ArgMatches>, id: &str) -> bool {
+    matches.and_then(|matches| matches.value_source(id)).is_some_and(|source| source == ValueSource::CommandLine)
+}
+
+/// Ask for the input, block size and output of `pixelate`, leaving out those already in `args`,
+/// and return `args` completed with the answers.
+pub fn ask(command: &Command, mut args: Vec<OsString>) -> Result<Vec<OsString>, Box<dyn std::error::Error>> {
+    let partial = command.clone().ignore_errors(true).get_matches_from(&args);
+    let matches = partial.subcommand_matches("pixelate");
+    if matches.is_none() {
+        args.push("pixelate".into());
+    }
+
+    eprintln!("Pixelate an image; press enter to take the default in brackets.");
+    if !given(matches, "input") {
+        let input: String = Input::new().with_prompt("Input image (path or URL)").interact_text()?;
+        args.extend(["--input".into(), input.into()]);
+    }
+    if !given(matches, "block_size") {
+        let block_size: u32 = Input::new()
+            .with_prompt("Block size, in pixels")
+            .default(10)
+            .validate_with(|block_size: &u32| if *block_size > 0 { Ok(()) } else { Err("must be greater than 0") })
+            .interact_text()?;
+        args.extend(["--block-size".into(), block_size.to_string().into()]);
+    }
+    if !given(matches, "output") {
+        let output: String = Input::new().with_prompt("Output JSON file (empty for stdout)").allow_empty(true).interact_text()?;
+        if !output.is_empty() {
+            args.extend(["--output".into(), output.into()]);
+        }
+    }
+    Ok(args)
+}